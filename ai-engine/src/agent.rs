@@ -0,0 +1,159 @@
+//! Agentes autônomos da simulação de cidade inteligente
+
+use std::f64::consts::FRAC_PI_4;
+
+use anyhow::Result;
+use ndarray::Array1;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::environment::Environment;
+use crate::{AIConfig, Action, AgentState, PerformanceMetrics};
+
+/// Limite de energia abaixo do qual o agente prioriza coletar recursos
+const LOW_ENERGY_THRESHOLD: f64 = 30.0;
+
+/// Número de características da observação de um agente, a entrada das redes de aprendizado
+pub const OBSERVATION_SIZE: usize = 20;
+
+/// Número de ações discretas entre as quais as redes escolhem
+pub const ACTION_COUNT: usize = 10;
+
+/// Raio em que os vizinhos de um agente entram na sua observação
+const NEIGHBOR_RADIUS: f64 = 50.0;
+
+/// Agente controlado pela IA
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Agent {
+    agent_type: String,
+    state: AgentState,
+    config: AIConfig,
+}
+
+impl Agent {
+    /// Cria um novo agente a partir do estado inicial
+    pub fn new(agent_type: String, initial_state: AgentState, config: AIConfig) -> Self {
+        Self {
+            agent_type,
+            state: initial_state,
+            config,
+        }
+    }
+
+    /// Identificador único do agente
+    pub fn get_id(&self) -> Uuid {
+        self.state.id
+    }
+
+    /// Tipo do agente (citizen, business, government...)
+    pub fn get_type(&self) -> &str {
+        &self.agent_type
+    }
+
+    /// Estado atual do agente
+    pub fn get_state(&self) -> &AgentState {
+        &self.state
+    }
+
+    /// Métricas de performance do agente
+    pub fn get_performance_metrics(&self) -> &PerformanceMetrics {
+        &self.state.performance_metrics
+    }
+
+    /// Decide a próxima ação com base no estado e no ambiente
+    ///
+    /// A exploração sorteia de `rng`, então um gerador com semente reproduz as decisões.
+    pub async fn decide_action(&self, environment: &Environment, rng: &mut impl Rng) -> Result<Action> {
+        // Exploração aleatória
+        if rng.gen::<f64>() < self.config.exploration_rate {
+            let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+            return Ok(Action::Move {
+                direction: (angle.cos(), angle.sin()),
+                speed: 1.0,
+            });
+        }
+
+        // Energia baixa: coletar recursos se houver disponibilidade
+        if self.state.energy < LOW_ENERGY_THRESHOLD && environment.get_resource("energy") > 0.0 {
+            return Ok(Action::Collect {
+                resource_type: "energy".to_string(),
+                amount: LOW_ENERGY_THRESHOLD - self.state.energy,
+            });
+        }
+
+        // Caso contrário, mover em direção ao centro da cidade
+        Ok(self.move_to_center(environment))
+    }
+
+    /// Observação do agente no ambiente, completada com zeros até `OBSERVATION_SIZE`
+    ///
+    /// Posição e distância ao centro são normalizadas pelas dimensões da cidade e a energia por 100.
+    pub fn observe(&self, environment: &Environment) -> Array1<f64> {
+        let (x, y) = self.position(environment);
+        let (center_x, center_y) = environment.get_center();
+        let neighbors = environment.count_agents_near((x, y), NEIGHBOR_RADIUS, Some(self.get_id()));
+
+        let mut observation = Array1::zeros(OBSERVATION_SIZE);
+        observation[0] = x / environment.width;
+        observation[1] = y / environment.height;
+        observation[2] = (center_x - x) / environment.width;
+        observation[3] = (center_y - y) / environment.height;
+        observation[4] = self.state.energy / 100.0;
+        observation[5] = neighbors as f64;
+        observation[6] = if environment.get_resource("energy") > 0.0 { 1.0 } else { 0.0 };
+        observation
+    }
+
+    /// Ação correspondente a um índice escolhido pela rede, em `0..ACTION_COUNT`
+    ///
+    /// Os índices 0 a 7 movem nas oito direções da bússola, 8 coleta a energia que falta
+    /// até o limite de energia baixa e os demais movem em direção ao centro.
+    pub fn action_for(&self, index: usize, environment: &Environment) -> Action {
+        match index {
+            0..=7 => {
+                let angle = index as f64 * FRAC_PI_4;
+                Action::Move {
+                    direction: (angle.cos(), angle.sin()),
+                    speed: 1.0,
+                }
+            }
+            8 => Action::Collect {
+                resource_type: "energy".to_string(),
+                amount: (LOW_ENERGY_THRESHOLD - self.state.energy).max(0.0),
+            },
+            _ => self.move_to_center(environment),
+        }
+    }
+
+    /// Receita e custos por passo de uma empresa, lidos de `revenue` e `costs` em seus recursos
+    ///
+    /// Agentes de outros tipos não têm lucro.
+    pub fn profit(&self) -> Option<(f64, f64)> {
+        if self.agent_type != "business" {
+            return None;
+        }
+        let amount = |resource: &str| self.state.resources.get(resource).copied().unwrap_or(0.0);
+        Some((amount("revenue"), amount("costs")))
+    }
+
+    fn position(&self, environment: &Environment) -> (f64, f64) {
+        environment
+            .get_agent_position(self.get_id())
+            .unwrap_or(self.state.position)
+    }
+
+    fn move_to_center(&self, environment: &Environment) -> Action {
+        let (x, y) = self.position(environment);
+        let (center_x, center_y) = environment.get_center();
+        let (dx, dy) = (center_x - x, center_y - y);
+        let distance = (dx * dx + dy * dy).sqrt();
+        let direction = if distance > f64::EPSILON {
+            (dx / distance, dy / distance)
+        } else {
+            (0.0, 0.0)
+        };
+
+        Action::Move { direction, speed: 1.0 }
+    }
+}
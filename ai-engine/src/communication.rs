@@ -0,0 +1,285 @@
+//! Hub de comunicação entre agentes
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Mensagem trocada entre agentes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub sender: Uuid,
+    pub payload: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl Message {
+    /// Cria uma mensagem com o horário atual
+    pub fn new(sender: Uuid, payload: serde_json::Value) -> Self {
+        Self {
+            sender,
+            payload,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Hub central de comunicação
+///
+/// Entrega mensagens diretamente a um agente, aos inscritos em tópicos ou a todos
+/// os agentes conhecidos. Cada entrega chega no máximo uma vez a cada agente, e as
+/// mensagens ficam na caixa de entrada do destinatário até `drain`.
+pub struct CommunicationHub {
+    registered: RwLock<HashSet<Uuid>>,
+    subscriptions: RwLock<HashMap<String, HashSet<Uuid>>>,
+    inboxes: Mutex<HashMap<Uuid, Vec<Message>>>,
+}
+
+impl CommunicationHub {
+    pub fn new() -> Self {
+        Self {
+            registered: RwLock::new(HashSet::new()),
+            subscriptions: RwLock::new(HashMap::new()),
+            inboxes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Inicializa o hub de comunicação
+    pub async fn initialize(&self) -> Result<()> {
+        info!("Hub de comunicação inicializado");
+        Ok(())
+    }
+
+    /// Registra um agente para receber broadcasts
+    pub async fn register_agent(&self, agent_id: Uuid) {
+        self.registered.write().await.insert(agent_id);
+    }
+
+    /// Remove um agente, suas inscrições e mensagens pendentes
+    pub async fn unregister_agent(&self, agent_id: Uuid) {
+        self.registered.write().await.remove(&agent_id);
+        let mut subscriptions = self.subscriptions.write().await;
+        for subscribers in subscriptions.values_mut() {
+            subscribers.remove(&agent_id);
+        }
+        subscriptions.retain(|_, subscribers| !subscribers.is_empty());
+        self.inboxes.lock().await.remove(&agent_id);
+    }
+
+    /// Inscreve um agente em um tópico
+    pub async fn subscribe(&self, agent_id: Uuid, topic: String) {
+        self.subscriptions.write().await.entry(topic).or_default().insert(agent_id);
+    }
+
+    /// Cancela a inscrição de um agente em um tópico
+    pub async fn unsubscribe(&self, agent_id: Uuid, topic: &str) {
+        let mut subscriptions = self.subscriptions.write().await;
+        if let Some(subscribers) = subscriptions.get_mut(topic) {
+            subscribers.remove(&agent_id);
+            if subscribers.is_empty() {
+                subscriptions.remove(topic);
+            }
+        }
+    }
+
+    /// Publica uma mensagem para todos os inscritos no tópico, exceto o remetente
+    ///
+    /// Retorna o número de agentes que receberam a mensagem.
+    pub async fn publish(&self, topic: String, message: Message) -> usize {
+        self.publish_to_topics(&[topic], message).await
+    }
+
+    /// Publica uma mensagem em vários tópicos, entregando-a uma única vez a quem
+    /// estiver inscrito em mais de um deles
+    pub async fn publish_to_topics(&self, topics: &[String], message: Message) -> usize {
+        let recipients: HashSet<Uuid> = {
+            let subscriptions = self.subscriptions.read().await;
+            topics
+                .iter()
+                .filter_map(|topic| subscriptions.get(topic))
+                .flatten()
+                .copied()
+                .collect()
+        };
+
+        debug!("Mensagem de {} publicada em {:?}", message.sender, topics);
+        self.deliver(recipients, message).await
+    }
+
+    /// Envia uma mensagem a todos os agentes registrados ou inscritos em algum tópico,
+    /// exceto o remetente
+    pub async fn broadcast(&self, message: Message) -> usize {
+        let recipients = self.known_agents().await;
+        self.deliver(recipients, message).await
+    }
+
+    /// Envia uma mensagem aos agentes conhecidos a até `radius` de `center`
+    ///
+    /// Agentes sem posição em `positions` não recebem a mensagem.
+    pub async fn broadcast_within(
+        &self,
+        center: (f64, f64),
+        radius: f64,
+        positions: &HashMap<Uuid, (f64, f64)>,
+        message: Message,
+    ) -> usize {
+        let recipients: HashSet<Uuid> = self
+            .known_agents()
+            .await
+            .into_iter()
+            .filter(|id| {
+                positions.get(id).is_some_and(|&(x, y)| {
+                    ((x - center.0).powi(2) + (y - center.1).powi(2)).sqrt() <= radius
+                })
+            })
+            .collect();
+        self.deliver(recipients, message).await
+    }
+
+    /// Agentes registrados e inscritos em qualquer tópico
+    async fn known_agents(&self) -> HashSet<Uuid> {
+        let mut agents = self.registered.read().await.clone();
+        for subscribers in self.subscriptions.read().await.values() {
+            agents.extend(subscribers.iter().copied());
+        }
+        agents
+    }
+
+    /// Entrega uma cópia da mensagem a cada destinatário, exceto o remetente
+    async fn deliver(&self, mut recipients: HashSet<Uuid>, message: Message) -> usize {
+        recipients.remove(&message.sender);
+
+        let mut inboxes = self.inboxes.lock().await;
+        for recipient in &recipients {
+            inboxes.entry(*recipient).or_default().push(message.clone());
+        }
+        recipients.len()
+    }
+
+    /// Envia uma mensagem diretamente a um agente
+    pub async fn send(&self, recipient: Uuid, message: Message) {
+        self.inboxes.lock().await.entry(recipient).or_default().push(message);
+    }
+
+    /// Retira todas as mensagens pendentes de um agente, na ordem de chegada
+    pub async fn drain(&self, agent_id: Uuid) -> Vec<Message> {
+        self.inboxes.lock().await.remove(&agent_id).unwrap_or_default()
+    }
+}
+
+impl Default for CommunicationHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_point_to_point_delivery() {
+        let hub = CommunicationHub::new();
+        let (sender, recipient, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        hub.send(recipient, Message::new(sender, json!({"offer": 10.0}))).await;
+
+        let received = hub.drain(recipient).await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].sender, sender);
+        assert_eq!(received[0].payload["offer"], 10.0);
+        assert!(hub.drain(recipient).await.is_empty());
+        assert!(hub.drain(other).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_topic_broadcast_to_subscribers() {
+        let hub = CommunicationHub::new();
+        let sender = Uuid::new_v4();
+        let subscribers = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+        for id in subscribers.iter().chain([&sender]) {
+            hub.subscribe(*id, "traffic".to_string()).await;
+        }
+
+        let delivered = hub.publish("traffic".to_string(), Message::new(sender, json!("jam on 5th"))).await;
+
+        assert_eq!(delivered, 3);
+        for id in subscribers {
+            let received = hub.drain(id).await;
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].payload, json!("jam on 5th"));
+        }
+        assert!(hub.drain(sender).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribed_agents_receive_nothing() {
+        let hub = CommunicationHub::new();
+        let (sender, listener, outsider) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        hub.subscribe(listener, "energy".to_string()).await;
+        hub.subscribe(outsider, "energy".to_string()).await;
+        hub.unsubscribe(outsider, "energy").await;
+
+        hub.publish("energy".to_string(), Message::new(sender, json!({"price": 1.2}))).await;
+        hub.publish("other".to_string(), Message::new(sender, json!(null))).await;
+
+        assert_eq!(hub.drain(listener).await.len(), 1);
+        assert!(hub.drain(outsider).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_within_excludes_far_agents() {
+        let hub = CommunicationHub::new();
+        let government = Uuid::new_v4();
+        let (near, edge, far, unplaced) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for id in [government, near, edge, far, unplaced] {
+            hub.register_agent(id).await;
+        }
+        let positions = HashMap::from([
+            (government, (100.0, 100.0)),
+            (near, (110.0, 95.0)),
+            (edge, (100.0, 150.0)),
+            (far, (400.0, 400.0)),
+        ]);
+
+        let delivered = hub
+            .broadcast_within((100.0, 100.0), 50.0, &positions, Message::new(government, json!("curfew")))
+            .await;
+
+        assert_eq!(delivered, 2);
+        assert_eq!(hub.drain(near).await.len(), 1);
+        assert_eq!(hub.drain(edge).await.len(), 1);
+        assert!(hub.drain(far).await.is_empty());
+        assert!(hub.drain(unplaced).await.is_empty());
+        assert!(hub.drain(government).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_deduplicated() {
+        let hub = CommunicationHub::new();
+        let sender = Uuid::new_v4();
+        let (listener, subscriber_only) = (Uuid::new_v4(), Uuid::new_v4());
+        hub.register_agent(listener).await;
+        hub.subscribe(listener, "traffic".to_string()).await;
+        hub.subscribe(listener, "energy".to_string()).await;
+        hub.subscribe(subscriber_only, "energy".to_string()).await;
+
+        // Registrado e inscrito em dois tópicos, mas recebe o broadcast uma única vez
+        assert_eq!(hub.broadcast(Message::new(sender, json!("hello"))).await, 2);
+        assert_eq!(hub.drain(listener).await.len(), 1);
+        assert_eq!(hub.drain(subscriber_only).await.len(), 1);
+
+        let topics = ["traffic".to_string(), "energy".to_string()];
+        assert_eq!(hub.publish_to_topics(&topics, Message::new(sender, json!("update"))).await, 2);
+        assert_eq!(hub.drain(listener).await.len(), 1);
+
+        hub.unregister_agent(listener).await;
+        assert_eq!(hub.broadcast(Message::new(sender, json!("bye"))).await, 1);
+        assert!(hub.drain(listener).await.is_empty());
+    }
+}
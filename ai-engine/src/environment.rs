@@ -0,0 +1,432 @@
+//! Ambiente da simulação de cidade inteligente
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::Action;
+
+/// Tamanho padrão, em unidades da cidade, de uma célula dos campos de recursos
+pub const DEFAULT_FIELD_CELL_SIZE: f64 = 50.0;
+
+/// Quantidade inicial de cada recurso padrão, distribuída igualmente entre as células
+pub const DEFAULT_RESOURCE_STOCK: f64 = 10000.0;
+
+/// Fração da capacidade de uma célula recuperada a cada `update`
+pub const DEFAULT_REGENERATION_FRACTION: f64 = 0.02;
+
+/// Forma como as células de um campo de recursos se recuperam
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RegenerationCurve {
+    /// Recupera `regeneration` a cada passo, em ritmo constante
+    #[default]
+    Linear,
+    /// Crescimento logístico com pico de `regeneration` na metade da capacidade
+    ///
+    /// A recuperação desacelera perto da capacidade, e uma célula esgotada não se
+    /// recupera mais, modelando estoques finitos.
+    Logistic,
+    /// Recupera `regeneration * interval` de uma vez a cada `interval` passos
+    Step { interval: u64 },
+}
+
+impl RegenerationCurve {
+    /// Quantidade recuperada por uma célula com `level` de `capacity` no passo `step`
+    fn growth(&self, level: f64, capacity: f64, regeneration: f64, step: u64) -> f64 {
+        match *self {
+            RegenerationCurve::Linear => regeneration,
+            RegenerationCurve::Logistic => {
+                if capacity <= 0.0 {
+                    return 0.0;
+                }
+                4.0 * regeneration * level * (1.0 - level / capacity) / capacity
+            }
+            RegenerationCurve::Step { interval } => {
+                let interval = interval.max(1);
+                if step.is_multiple_of(interval) {
+                    regeneration * interval as f64
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Grade com a densidade de um recurso por célula
+///
+/// Os agentes consomem o recurso da célula em que estão, e cada célula se regenera
+/// a cada passo até a capacidade.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResourceField {
+    pub cell_size: f64,
+    pub columns: usize,
+    pub rows: usize,
+    /// Quantidade máxima por célula
+    pub capacity: f64,
+    /// Quantidade recuperada por célula a cada passo
+    pub regeneration: f64,
+    /// Forma da recuperação
+    #[serde(default)]
+    pub curve: RegenerationCurve,
+    cells: Vec<f64>,
+    /// Passos de regeneração já aplicados
+    #[serde(default)]
+    steps: u64,
+}
+
+impl ResourceField {
+    /// Cria um campo cobrindo `width` x `height`, com todas as células cheias
+    pub fn new(width: f64, height: f64, cell_size: f64, capacity: f64, regeneration: f64) -> Self {
+        let cell_size = cell_size.max(f64::EPSILON);
+        let columns = ((width / cell_size).ceil() as usize).max(1);
+        let rows = ((height / cell_size).ceil() as usize).max(1);
+        Self {
+            cell_size,
+            columns,
+            rows,
+            capacity: capacity.max(0.0),
+            regeneration: regeneration.max(0.0),
+            curve: RegenerationCurve::default(),
+            cells: vec![capacity.max(0.0); columns * rows],
+            steps: 0,
+        }
+    }
+
+    /// Define a curva de regeneração do campo
+    pub fn with_curve(mut self, curve: RegenerationCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Índice da célula que contém a posição; posições fora da grade usam a célula da borda
+    fn cell_index(&self, (x, y): (f64, f64)) -> usize {
+        let column = ((x / self.cell_size).floor().max(0.0) as usize).min(self.columns - 1);
+        let row = ((y / self.cell_size).floor().max(0.0) as usize).min(self.rows - 1);
+        row * self.columns + column
+    }
+
+    /// Quantidade disponível na célula da posição
+    pub fn sample(&self, position: (f64, f64)) -> f64 {
+        self.cells[self.cell_index(position)]
+    }
+
+    /// Retira até `amount` da célula da posição, retornando quanto foi retirado
+    pub fn consume(&mut self, position: (f64, f64), amount: f64) -> f64 {
+        let index = self.cell_index(position);
+        let taken = amount.max(0.0).min(self.cells[index]);
+        self.cells[index] -= taken;
+        taken
+    }
+
+    /// Recupera cada célula segundo a curva, sem passar da capacidade
+    pub fn regenerate(&mut self) {
+        self.steps += 1;
+        for cell in &mut self.cells {
+            let growth = self.curve.growth(*cell, self.capacity, self.regeneration, self.steps);
+            *cell = (*cell + growth.max(0.0)).min(self.capacity);
+        }
+    }
+
+    /// Quantidade total disponível no campo
+    pub fn total(&self) -> f64 {
+        self.cells.iter().sum()
+    }
+
+    /// Densidade por célula, linha a linha, para mapas de calor
+    pub fn heatmap(&self) -> Vec<Vec<f64>> {
+        self.cells.chunks(self.columns).map(|row| row.to_vec()).collect()
+    }
+}
+
+/// Estado físico e de recursos da cidade
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Environment {
+    pub width: f64,
+    pub height: f64,
+    pub tick: u64,
+    pub agent_positions: HashMap<Uuid, (f64, f64)>,
+    pub resources: HashMap<String, f64>,
+    /// Recursos distribuídos no espaço; têm precedência sobre `resources` para o mesmo tipo
+    #[serde(default)]
+    pub resource_fields: HashMap<String, ResourceField>,
+    pub actions_executed: u64,
+}
+
+impl Environment {
+    /// Cria um novo ambiente com dimensões padrão
+    pub fn new() -> Self {
+        Self {
+            width: 1000.0,
+            height: 1000.0,
+            tick: 0,
+            agent_positions: HashMap::new(),
+            resources: HashMap::new(),
+            resource_fields: HashMap::new(),
+            actions_executed: 0,
+        }
+    }
+
+    /// Inicializa os recursos do ambiente
+    pub async fn initialize(&mut self) -> Result<()> {
+        let cells = (self.width / DEFAULT_FIELD_CELL_SIZE).ceil().max(1.0)
+            * (self.height / DEFAULT_FIELD_CELL_SIZE).ceil().max(1.0);
+        let capacity = DEFAULT_RESOURCE_STOCK / cells;
+        for resource_type in ["energy", "food"] {
+            let regeneration = capacity * DEFAULT_REGENERATION_FRACTION;
+            self.add_resource_field(resource_type, DEFAULT_FIELD_CELL_SIZE, capacity, regeneration);
+        }
+        info!("Ambiente inicializado ({}x{})", self.width, self.height);
+        Ok(())
+    }
+
+    /// Registra a posição de um agente no ambiente
+    pub fn register_agent(&mut self, agent_id: Uuid, position: (f64, f64)) {
+        self.agent_positions.insert(agent_id, self.clamp_position(position));
+    }
+
+    /// Distribui um recurso em uma grade sobre a cidade, substituindo o campo anterior do mesmo tipo
+    pub fn add_resource_field(&mut self, resource_type: &str, cell_size: f64, capacity: f64, regeneration: f64) {
+        let field = ResourceField::new(self.width, self.height, cell_size, capacity, regeneration);
+        self.resource_fields.insert(resource_type.to_string(), field);
+    }
+
+    /// Altera a curva de regeneração de um campo existente
+    pub fn set_regeneration_curve(&mut self, resource_type: &str, curve: RegenerationCurve) -> Result<()> {
+        let field = self
+            .resource_fields
+            .get_mut(resource_type)
+            .ok_or_else(|| anyhow!("Campo de recurso {} não encontrado", resource_type))?;
+        field.curve = curve;
+        Ok(())
+    }
+
+    /// Campo de um recurso, se ele for distribuído no espaço
+    pub fn get_resource_field(&self, resource_type: &str) -> Option<&ResourceField> {
+        self.resource_fields.get(resource_type)
+    }
+
+    /// Remove um agente do ambiente
+    pub fn unregister_agent(&mut self, agent_id: Uuid) {
+        self.agent_positions.remove(&agent_id);
+    }
+
+    /// Executa a ação de um agente
+    pub async fn execute_action(&mut self, agent_id: Uuid, action: Action) -> Result<()> {
+        match action {
+            Action::Move { direction, speed } => {
+                let (x, y) = *self
+                    .agent_positions
+                    .get(&agent_id)
+                    .ok_or_else(|| anyhow!("Agente {} não registrado no ambiente", agent_id))?;
+                let position = (x + direction.0 * speed, y + direction.1 * speed);
+                self.agent_positions.insert(agent_id, self.clamp_position(position));
+            }
+            Action::Collect { resource_type, amount } => {
+                if let Some(field) = self.resource_fields.get_mut(&resource_type) {
+                    let position = *self
+                        .agent_positions
+                        .get(&agent_id)
+                        .ok_or_else(|| anyhow!("Agente {} não registrado no ambiente", agent_id))?;
+                    field.consume(position, amount);
+                } else {
+                    let available = self.resources.entry(resource_type).or_insert(0.0);
+                    *available = (*available - amount.max(0.0)).max(0.0);
+                }
+            }
+            Action::Produce { product_type, amount } => {
+                *self.resources.entry(product_type).or_insert(0.0) += amount.max(0.0);
+            }
+            Action::Interact { .. } | Action::Communicate { .. } | Action::Optimize { .. } => {
+                debug!("Ação sem efeito físico executada pelo agente {}", agent_id);
+            }
+        }
+
+        self.actions_executed += 1;
+        Ok(())
+    }
+
+    /// Avança o ambiente em um passo
+    pub async fn update(&mut self) -> Result<()> {
+        self.tick += 1;
+        for field in self.resource_fields.values_mut() {
+            field.regenerate();
+        }
+        Ok(())
+    }
+
+    /// Estado do ambiente em formato JSON
+    pub async fn get_state(&self) -> Result<serde_json::Value> {
+        Ok(serde_json::json!({
+            "tick": self.tick,
+            "width": self.width,
+            "height": self.height,
+            "agents": self.agent_positions.len(),
+            "resources": self.resources,
+            "resource_fields": self
+                .resource_fields
+                .iter()
+                .map(|(resource_type, field)| (resource_type.clone(), field.total()))
+                .collect::<HashMap<_, _>>(),
+            "actions_executed": self.actions_executed,
+        }))
+    }
+
+    /// Quantidade disponível de um recurso, somando todas as células se ele tiver um campo
+    pub fn get_resource(&self, resource_type: &str) -> f64 {
+        match self.resource_fields.get(resource_type) {
+            Some(field) => field.total(),
+            None => self.resources.get(resource_type).copied().unwrap_or(0.0),
+        }
+    }
+
+    /// Posição atual de um agente
+    pub fn get_agent_position(&self, agent_id: Uuid) -> Option<(f64, f64)> {
+        self.agent_positions.get(&agent_id).copied()
+    }
+
+    /// Conta agentes dentro de um raio, opcionalmente ignorando um agente
+    pub fn count_agents_near(&self, position: (f64, f64), radius: f64, exclude: Option<Uuid>) -> usize {
+        self.agent_positions
+            .iter()
+            .filter(|(id, _)| Some(**id) != exclude)
+            .filter(|(_, (x, y))| ((x - position.0).powi(2) + (y - position.1).powi(2)).sqrt() <= radius)
+            .count()
+    }
+
+    /// Centro da cidade
+    pub fn get_center(&self) -> (f64, f64) {
+        (self.width / 2.0, self.height / 2.0)
+    }
+
+    fn clamp_position(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        (x.clamp(0.0, self.width), y.clamp(0.0, self.height))
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collected_cell_depletes_and_regenerates() {
+        let mut environment = Environment::new();
+        environment.add_resource_field("food", 100.0, 10.0, 2.0);
+        let agent_id = Uuid::new_v4();
+        environment.register_agent(agent_id, (150.0, 250.0));
+
+        let collect = |amount| Action::Collect {
+            resource_type: "food".to_string(),
+            amount,
+        };
+        environment.execute_action(agent_id, collect(8.0)).await.unwrap();
+
+        let field = environment.get_resource_field("food").unwrap();
+        assert_eq!(field.sample((150.0, 250.0)), 2.0);
+        assert_eq!(field.sample((199.0, 299.0)), 2.0);
+        assert_eq!(field.sample((50.0, 250.0)), 10.0);
+        assert_eq!(environment.get_resource("food"), 10.0 * 100.0 - 8.0);
+
+        // Only what the cell holds can be taken
+        environment.execute_action(agent_id, collect(5.0)).await.unwrap();
+        assert_eq!(environment.get_resource_field("food").unwrap().sample((150.0, 250.0)), 0.0);
+
+        let mut levels = Vec::new();
+        for _ in 0..6 {
+            environment.update().await.unwrap();
+            levels.push(environment.get_resource_field("food").unwrap().sample((150.0, 250.0)));
+        }
+        assert_eq!(levels, vec![2.0, 4.0, 6.0, 8.0, 10.0, 10.0]);
+    }
+
+    #[tokio::test]
+    async fn test_default_fields_hold_initial_stock() {
+        let mut environment = Environment::new();
+        environment.initialize().await.unwrap();
+
+        for resource_type in ["energy", "food"] {
+            assert!((environment.get_resource(resource_type) - DEFAULT_RESOURCE_STOCK).abs() < 1e-6);
+        }
+        assert_eq!(environment.get_resource_field("energy").unwrap().heatmap().len(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_logistic_regeneration_slows_near_capacity() {
+        let mut environment = Environment::new();
+        environment.add_resource_field("energy", 1000.0, 100.0, 10.0);
+        environment.add_resource_field("water", 1000.0, 100.0, 10.0);
+        environment
+            .set_regeneration_curve("water", RegenerationCurve::Logistic)
+            .unwrap();
+        assert!(environment
+            .set_regeneration_curve("gold", RegenerationCurve::Logistic)
+            .is_err());
+
+        let agent_id = Uuid::new_v4();
+        environment.register_agent(agent_id, (10.0, 10.0));
+        for resource_type in ["energy", "water"] {
+            let collect = Action::Collect {
+                resource_type: resource_type.to_string(),
+                amount: 90.0,
+            };
+            environment.execute_action(agent_id, collect).await.unwrap();
+        }
+
+        let mut linear = Vec::new();
+        let mut logistic = Vec::new();
+        for _ in 0..8 {
+            let before = (
+                environment.get_resource_field("energy").unwrap().sample((10.0, 10.0)),
+                environment.get_resource_field("water").unwrap().sample((10.0, 10.0)),
+            );
+            environment.update().await.unwrap();
+            linear.push(environment.get_resource_field("energy").unwrap().sample((10.0, 10.0)) - before.0);
+            logistic.push(environment.get_resource_field("water").unwrap().sample((10.0, 10.0)) - before.1);
+        }
+
+        // Linear refills at a constant rate until it is full
+        assert!(linear.iter().all(|&rate| (rate - 10.0).abs() < 1e-9));
+
+        // Logistic speeds up towards half capacity, then slows down as it fills
+        let level_after = |steps: usize| 10.0 + logistic[..steps].iter().sum::<f64>();
+        let peak = logistic
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(index, _)| index)
+            .unwrap();
+        assert!(level_after(peak) <= 60.0);
+        for window in logistic[peak..].windows(2) {
+            assert!(window[1] < window[0]);
+        }
+        assert!(*logistic.last().unwrap() < 10.0);
+        assert!(level_after(8) < 100.0);
+
+        // A depleted logistic cell stays empty
+        let mut finite = ResourceField::new(100.0, 100.0, 100.0, 100.0, 10.0).with_curve(RegenerationCurve::Logistic);
+        finite.consume((0.0, 0.0), 100.0);
+        finite.regenerate();
+        assert_eq!(finite.total(), 0.0);
+
+        let mut step = ResourceField::new(100.0, 100.0, 100.0, 100.0, 10.0)
+            .with_curve(RegenerationCurve::Step { interval: 3 });
+        step.consume((0.0, 0.0), 100.0);
+        let levels: Vec<f64> = (0..6)
+            .map(|_| {
+                step.regenerate();
+                step.total()
+            })
+            .collect();
+        assert_eq!(levels, vec![0.0, 0.0, 30.0, 30.0, 30.0, 60.0]);
+    }
+}
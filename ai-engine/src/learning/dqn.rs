@@ -2,10 +2,12 @@
 //! Version 1.2 - Advanced AI algorithms
 
 use ndarray::{Array1, Array2, Array3, Axis};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use tracing::{debug, info, warn};
+use uuid::Uuid;
 
 /// Configuration for DQN
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,6 +23,46 @@ pub struct DQNConfig {
     pub hidden_layers: Vec<usize>,
     pub input_size: usize,
     pub output_size: usize,
+    /// Optional per-feature (min, max) bounds applied to states before they enter the network
+    #[serde(default)]
+    pub observation_bounds: Option<Vec<(f64, f64)>>,
+    /// How the learning rate evolves over training steps, starting from `learning_rate`
+    #[serde(default)]
+    pub learning_rate_schedule: LearningRateSchedule,
+    /// Split the output into a state-value stream and an advantage stream, combined as
+    /// `Q = V + (A - mean(A))`
+    #[serde(default)]
+    pub dueling: bool,
+    /// Number of steps summed into each return before bootstrapping (1 for plain TD targets)
+    #[serde(default = "default_n_step")]
+    pub n_step: usize,
+    /// How epsilon moves from `epsilon_start` to `epsilon_end` over training steps
+    #[serde(default)]
+    pub epsilon_schedule: EpsilonSchedule,
+    /// How the greedy policy picks among actions whose Q-values tie
+    #[serde(default)]
+    pub tie_breaking: TieBreaking,
+    /// Seed of the generator used for exploration, replay sampling and random tie-breaking;
+    /// `None` seeds from system entropy
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+fn default_n_step() -> usize {
+    1
+}
+
+/// Q-values closer than this to the best one count as tied
+pub const ACTION_TIE_TOLERANCE: f64 = 1e-9;
+
+/// Choice among greedy actions whose Q-values tie within `ACTION_TIE_TOLERANCE`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TieBreaking {
+    /// Always the tied action with the lowest index
+    #[default]
+    LowestIndex,
+    /// A uniformly random tied action, drawn from the network's generator
+    Random,
 }
 
 impl Default for DQNConfig {
@@ -37,10 +79,227 @@ impl Default for DQNConfig {
             hidden_layers: vec![128, 64, 32],
             input_size: 20,
             output_size: 10,
+            observation_bounds: None,
+            learning_rate_schedule: LearningRateSchedule::Constant,
+            dueling: false,
+            n_step: default_n_step(),
+            epsilon_schedule: EpsilonSchedule::Exponential,
+            tie_breaking: TieBreaking::LowestIndex,
+            seed: None,
+        }
+    }
+}
+
+impl DQNConfig {
+    /// Start building a configuration from the defaults
+    pub fn builder() -> DQNConfigBuilder {
+        DQNConfigBuilder::default()
+    }
+
+    /// Check that sizes are positive, rates lie in [0, 1] and `epsilon_end <= epsilon_start`
+    pub fn validate(&self) -> Result<(), String> {
+        let sizes = [
+            ("batch_size", self.batch_size),
+            ("memory_size", self.memory_size),
+            ("target_update_frequency", self.target_update_frequency),
+            ("input_size", self.input_size),
+            ("output_size", self.output_size),
+            ("n_step", self.n_step),
+        ];
+        for (name, size) in sizes {
+            if size == 0 {
+                return Err(format!("{} must be positive", name));
+            }
+        }
+        if self.hidden_layers.contains(&0) {
+            return Err("hidden layer sizes must be positive".to_string());
+        }
+
+        let rates = [
+            ("learning_rate", self.learning_rate),
+            ("gamma", self.gamma),
+            ("epsilon_start", self.epsilon_start),
+            ("epsilon_end", self.epsilon_end),
+            ("epsilon_decay", self.epsilon_decay),
+        ];
+        for (name, rate) in rates {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("{} must be in [0, 1], got {}", name, rate));
+            }
+        }
+        if self.learning_rate == 0.0 {
+            return Err("learning_rate must be positive".to_string());
+        }
+        if self.epsilon_end > self.epsilon_start {
+            return Err(format!(
+                "epsilon_end ({}) must not exceed epsilon_start ({})",
+                self.epsilon_end, self.epsilon_start
+            ));
+        }
+        if self.batch_size > self.memory_size {
+            return Err(format!(
+                "batch_size ({}) must not exceed memory_size ({})",
+                self.batch_size, self.memory_size
+            ));
+        }
+        if let Some(bounds) = &self.observation_bounds {
+            if bounds.len() != self.input_size || bounds.iter().any(|(min, max)| min > max) {
+                return Err("observation_bounds needs one (min, max) pair with min <= max per input".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Builder for `DQNConfig` that validates the result
+#[derive(Debug, Clone, Default)]
+pub struct DQNConfigBuilder {
+    config: DQNConfig,
+}
+
+impl DQNConfigBuilder {
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.config.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f64) -> Self {
+        self.config.gamma = gamma;
+        self
+    }
+
+    /// Exploration goes from `start` to `end` following `schedule`
+    pub fn epsilon(mut self, start: f64, end: f64, schedule: EpsilonSchedule) -> Self {
+        self.config.epsilon_start = start;
+        self.config.epsilon_end = end;
+        self.config.epsilon_schedule = schedule;
+        self
+    }
+
+    pub fn epsilon_decay(mut self, epsilon_decay: f64) -> Self {
+        self.config.epsilon_decay = epsilon_decay;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.config.batch_size = batch_size;
+        self
+    }
+
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.config.memory_size = memory_size;
+        self
+    }
+
+    pub fn target_update_frequency(mut self, frequency: usize) -> Self {
+        self.config.target_update_frequency = frequency;
+        self
+    }
+
+    /// Network shape: input features, hidden layer sizes and number of actions
+    pub fn layers(mut self, input_size: usize, hidden_layers: Vec<usize>, output_size: usize) -> Self {
+        self.config.input_size = input_size;
+        self.config.hidden_layers = hidden_layers;
+        self.config.output_size = output_size;
+        self
+    }
+
+    pub fn observation_bounds(mut self, bounds: Vec<(f64, f64)>) -> Self {
+        self.config.observation_bounds = Some(bounds);
+        self
+    }
+
+    pub fn learning_rate_schedule(mut self, schedule: LearningRateSchedule) -> Self {
+        self.config.learning_rate_schedule = schedule;
+        self
+    }
+
+    pub fn dueling(mut self, dueling: bool) -> Self {
+        self.config.dueling = dueling;
+        self
+    }
+
+    pub fn n_step(mut self, n_step: usize) -> Self {
+        self.config.n_step = n_step;
+        self
+    }
+
+    pub fn tie_breaking(mut self, tie_breaking: TieBreaking) -> Self {
+        self.config.tie_breaking = tie_breaking;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// Validate and return the configuration
+    pub fn build(self) -> Result<DQNConfig, String> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Learning rate schedule applied per training step
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum LearningRateSchedule {
+    /// Keep the initial learning rate for the whole run
+    #[default]
+    Constant,
+    /// Multiply the learning rate by `factor` every `step_size` steps
+    StepDecay { step_size: usize, factor: f64 },
+    /// Anneal from the initial learning rate to `end_learning_rate` over `total_steps` along a half cosine
+    Cosine { end_learning_rate: f64, total_steps: usize },
+}
+
+impl LearningRateSchedule {
+    /// Effective learning rate at a training step given the initial learning rate
+    pub fn learning_rate_at(&self, initial: f64, step: usize) -> f64 {
+        match self {
+            LearningRateSchedule::Constant => initial,
+            LearningRateSchedule::StepDecay { step_size, factor } => {
+                initial * factor.powi((step / (*step_size).max(1)) as i32)
+            }
+            LearningRateSchedule::Cosine { end_learning_rate, total_steps } => {
+                let progress = (step as f64 / (*total_steps).max(1) as f64).min(1.0);
+                let cosine = 0.5 * (1.0 + (std::f64::consts::PI * progress).cos());
+                end_learning_rate + (initial - end_learning_rate) * cosine
+            }
         }
     }
 }
 
+/// Exploration schedule applied per training step, never going below `epsilon_end`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum EpsilonSchedule {
+    /// Multiply epsilon by `epsilon_decay` every step
+    #[default]
+    Exponential,
+    /// Decrease linearly, reaching `epsilon_end` after `steps` steps
+    Linear { steps: usize },
+    /// Anneal along a half cosine, reaching `epsilon_end` after `steps` steps
+    Cosine { steps: usize },
+}
+
+impl EpsilonSchedule {
+    /// Epsilon at a training step given the start, floor and exponential decay factor
+    pub fn epsilon_at(&self, start: f64, end: f64, decay: f64, step: usize) -> f64 {
+        let epsilon = match self {
+            EpsilonSchedule::Exponential => start * decay.powf(step as f64),
+            EpsilonSchedule::Linear { steps } => {
+                let progress = (step as f64 / (*steps).max(1) as f64).min(1.0);
+                start * (1.0 - progress) + end * progress
+            }
+            EpsilonSchedule::Cosine { steps } => {
+                let progress = (step as f64 / (*steps).max(1) as f64).min(1.0);
+                end + (start - end) * 0.5 * (1.0 + (std::f64::consts::PI * progress).cos())
+            }
+        };
+        epsilon.max(end)
+    }
+}
+
 /// Experience for replay buffer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Experience {
@@ -51,15 +310,28 @@ pub struct Experience {
     pub done: bool,
 }
 
+/// Summary of the experiences held in a replay buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BufferStats {
+    pub count: usize,
+    /// Fraction of `memory_size` in use
+    pub utilization: f64,
+    pub mean_reward: f64,
+    pub min_reward: f64,
+    pub max_reward: f64,
+    /// Fraction of experiences that end an episode
+    pub terminal_fraction: f64,
+}
+
 /// Neural Network layer
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Layer {
     weights: Array2<f64>,
     biases: Array1<f64>,
     activation: ActivationFunction,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ActivationFunction {
     ReLU,
     Sigmoid,
@@ -67,9 +339,32 @@ pub enum ActivationFunction {
     Linear,
 }
 
+impl ActivationFunction {
+    fn apply(&self, x: f64) -> f64 {
+        match self {
+            ActivationFunction::ReLU => if x > 0.0 { x } else { 0.0 },
+            ActivationFunction::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            ActivationFunction::Tanh => x.tanh(),
+            ActivationFunction::Linear => x,
+        }
+    }
+
+    fn derivative(&self, x: f64) -> f64 {
+        match self {
+            ActivationFunction::ReLU => if x > 0.0 { 1.0 } else { 0.0 },
+            ActivationFunction::Sigmoid => {
+                let s = self.apply(x);
+                s * (1.0 - s)
+            }
+            ActivationFunction::Tanh => 1.0 - x.tanh().powi(2),
+            ActivationFunction::Linear => 1.0,
+        }
+    }
+}
+
 impl Layer {
-    pub fn new(input_size: usize, output_size: usize, activation: ActivationFunction) -> Self {
-        let mut rng = rand::thread_rng();
+    /// Layer with weights drawn uniformly from [-0.1, 0.1) and zero biases
+    pub fn new(input_size: usize, output_size: usize, activation: ActivationFunction, rng: &mut impl Rng) -> Self {
         let weights = Array2::from_shape_fn((output_size, input_size), |_| {
             rng.gen_range(-0.1..0.1)
         });
@@ -83,88 +378,448 @@ impl Layer {
     }
 
     pub fn forward(&self, input: &Array1<f64>) -> Array1<f64> {
-        let output = &self.weights.dot(input) + &self.biases;
-        self.activate(output)
+        self.pre_activation(input).mapv(|x| self.activation.apply(x))
     }
 
-    fn activate(&self, input: &Array1<f64>) -> Array1<f64> {
-        match &self.activation {
-            ActivationFunction::ReLU => input.mapv(|x| if x > 0.0 { x } else { 0.0 }),
-            ActivationFunction::Sigmoid => input.mapv(|x| 1.0 / (1.0 + (-x).exp())),
-            ActivationFunction::Tanh => input.mapv(|x| x.tanh()),
-            ActivationFunction::Linear => input.clone(),
-        }
+    fn pre_activation(&self, input: &Array1<f64>) -> Array1<f64> {
+        &self.weights.dot(input) + &self.biases
     }
 
-    pub fn backward(&mut self, gradient: &Array1<f64>, learning_rate: f64) {
-        // Simplified gradient descent update
-        let weight_gradient = gradient.outer(&Array1::ones(self.weights.ncols()));
+    /// Forward pass for a batch of inputs, one sample per row
+    pub fn forward_batch(&self, input: &Array2<f64>) -> Array2<f64> {
+        let output = input.dot(&self.weights.t()) + &self.biases;
+        output.mapv(|x| self.activation.apply(x))
+    }
+
+    /// Gradient descent update given the loss gradient w.r.t. this layer's pre-activation
+    pub fn backward(&mut self, gradient: &Array1<f64>, input: &Array1<f64>, learning_rate: f64) {
+        let weight_gradient = gradient
+            .view()
+            .insert_axis(Axis(1))
+            .dot(&input.view().insert_axis(Axis(0)));
         self.weights = &self.weights - &(weight_gradient * learning_rate);
         self.biases = &self.biases - &(gradient * learning_rate);
     }
 }
 
+/// Output head of a dueling network
+///
+/// Both streams read the last hidden representation: `value` estimates how good the
+/// state is and `advantage` how much better each action is than average.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuelingHead {
+    value: Layer,
+    advantage: Layer,
+}
+
+impl DuelingHead {
+    pub fn new(input_size: usize, output_size: usize, rng: &mut impl Rng) -> Self {
+        Self {
+            value: Layer::new(input_size, 1, ActivationFunction::Linear, rng),
+            advantage: Layer::new(input_size, output_size, ActivationFunction::Linear, rng),
+        }
+    }
+
+    /// Q-values for one hidden representation
+    pub fn forward(&self, features: &Array1<f64>) -> Array1<f64> {
+        let value = self.value.forward(features)[0];
+        let advantages = self.advantage.forward(features);
+        let mean = advantages.mean().unwrap_or(0.0);
+        advantages.mapv(|a| value + a - mean)
+    }
+
+    /// Q-values for a batch of hidden representations, one per row
+    pub fn forward_batch(&self, features: &Array2<f64>) -> Array2<f64> {
+        let values = self.value.forward_batch(features);
+        let advantages = self.advantage.forward_batch(features);
+        let means = advantages.mean_axis(Axis(1)).unwrap().insert_axis(Axis(1));
+        advantages - &means + &values
+    }
+
+    /// Update both streams given the loss gradient w.r.t. the Q-values, returning the
+    /// gradient w.r.t. the hidden representation
+    fn backward(&mut self, q_gradient: &Array1<f64>, features: &Array1<f64>, learning_rate: f64) -> Array1<f64> {
+        // dQ_i/dV = 1 and dQ_i/dA_j = [i == j] - 1/n
+        let value_gradient = Array1::from_elem(1, q_gradient.sum());
+        let advantage_gradient = q_gradient - q_gradient.mean().unwrap_or(0.0);
+
+        let feature_gradient =
+            self.value.weights.t().dot(&value_gradient) + self.advantage.weights.t().dot(&advantage_gradient);
+        self.value.backward(&value_gradient, features, learning_rate);
+        self.advantage.backward(&advantage_gradient, features, learning_rate);
+        feature_gradient
+    }
+}
+
+/// Actions whose Q-values tie with the best non-NaN one among `candidates`
+fn tied_actions(q_values: &Array1<f64>, candidates: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let scored: Vec<(usize, f64)> = candidates
+        .into_iter()
+        .map(|action| (action, q_values[action]))
+        .filter(|(_, q)| !q.is_nan())
+        .collect();
+    let best = scored
+        .iter()
+        .map(|&(_, q)| q)
+        .fold(f64::NEG_INFINITY, f64::max);
+    scored
+        .iter()
+        .filter(|&&(_, q)| q >= best - ACTION_TIE_TOLERANCE)
+        .map(|&(action, _)| action)
+        .collect()
+}
+
+/// Fail with a descriptive message if a state does not have `input_size` features
+fn check_state(config: &DQNConfig, name: &str, state: &Array1<f64>) -> Result<(), String> {
+    if state.len() != config.input_size {
+        return Err(format!(
+            "{} has {} features but the network expects input_size {}",
+            name,
+            state.len(),
+            config.input_size
+        ));
+    }
+    Ok(())
+}
+
+/// Clip each feature to the configured bounds; features without bounds pass through
+fn clip_observation(config: &DQNConfig, state: &Array1<f64>) -> Array1<f64> {
+    let mut clipped = state.clone();
+    if let Some(bounds) = &config.observation_bounds {
+        for (value, &(min, max)) in clipped.iter_mut().zip(bounds) {
+            *value = value.clamp(min, max);
+        }
+    }
+    clipped
+}
+
+/// Q-values of a single state through a network's layers and, if present, its dueling head
+fn forward_state(config: &DQNConfig, network: &[Layer], head: Option<&DuelingHead>, state: &Array1<f64>) -> Result<Array1<f64>, String> {
+    check_state(config, "state", state)?;
+    let mut output = clip_observation(config, state);
+    for layer in network {
+        output = layer.forward(&output);
+    }
+    Ok(match head {
+        Some(head) => head.forward(&output),
+        None => output,
+    })
+}
+
+/// Trained network for inference only: no replay buffer, target network or exploration
+///
+/// Obtained from `DQN::into_policy`. It is immutable, so it can be shared across threads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Policy {
+    config: DQNConfig,
+    network: Vec<Layer>,
+    head: Option<DuelingHead>,
+}
+
+impl Policy {
+    /// Greedy action for a state, the lowest index among tied Q-values
+    ///
+    /// Fails if `state` does not have `input_size` features or every Q-value is NaN.
+    pub fn act(&self, state: &Array1<f64>) -> Result<usize, String> {
+        let q_values = self.q_values(state)?;
+        tied_actions(&q_values, 0..self.config.output_size)
+            .into_iter()
+            .min()
+            .ok_or_else(|| "Every candidate action has a NaN Q-value".to_string())
+    }
+
+    /// Get Q-values for given state
+    ///
+    /// Fails if `state` does not have `input_size` features.
+    pub fn q_values(&self, state: &Array1<f64>) -> Result<Array1<f64>, String> {
+        forward_state(&self.config, &self.network, self.head.as_ref(), state)
+    }
+
+    pub fn config(&self) -> &DQNConfig {
+        &self.config
+    }
+}
+
 /// Deep Q-Network
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DQN {
     config: DQNConfig,
     main_network: Vec<Layer>,
     target_network: Vec<Layer>,
+    /// Dueling heads on top of the hidden layers, when `config.dueling` is set
+    #[serde(default)]
+    main_head: Option<DuelingHead>,
+    #[serde(default)]
+    target_head: Option<DuelingHead>,
     replay_buffer: VecDeque<Experience>,
     epsilon: f64,
     step_count: usize,
-    rng: rand::rngs::ThreadRng,
+    /// Last transitions of each source not yet folded into an n-step experience
+    #[serde(skip)]
+    n_step_windows: HashMap<Uuid, VecDeque<Experience>>,
+    /// Reseeded from entropy when a saved model is loaded
+    #[serde(skip, default = "StdRng::from_entropy")]
+    rng: StdRng,
 }
 
 impl DQN {
     pub fn new(config: DQNConfig) -> Self {
         let mut main_network = Vec::new();
         let mut target_network = Vec::new();
+        // Weights come from the same generator, so a seeded network starts identically
+        let mut rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
 
         // Build network layers
         let mut input_size = config.input_size;
         for &hidden_size in &config.hidden_layers {
-            main_network.push(Layer::new(input_size, hidden_size, ActivationFunction::ReLU));
-            target_network.push(Layer::new(input_size, hidden_size, ActivationFunction::ReLU));
+            main_network.push(Layer::new(input_size, hidden_size, ActivationFunction::ReLU, &mut rng));
+            target_network.push(Layer::new(input_size, hidden_size, ActivationFunction::ReLU, &mut rng));
             input_size = hidden_size;
         }
         
-        // Output layer
-        main_network.push(Layer::new(input_size, config.output_size, ActivationFunction::Linear));
-        target_network.push(Layer::new(input_size, config.output_size, ActivationFunction::Linear));
+        let epsilon = config.epsilon_start;
+
+        // Output layer, or value and advantage streams
+        let (main_head, target_head) = if config.dueling {
+            (
+                Some(DuelingHead::new(input_size, config.output_size, &mut rng)),
+                Some(DuelingHead::new(input_size, config.output_size, &mut rng)),
+            )
+        } else {
+            main_network.push(Layer::new(input_size, config.output_size, ActivationFunction::Linear, &mut rng));
+            target_network.push(Layer::new(input_size, config.output_size, ActivationFunction::Linear, &mut rng));
+            (None, None)
+        };
 
         Self {
             config,
             main_network,
             target_network,
+            main_head,
+            target_head,
             replay_buffer: VecDeque::with_capacity(10000),
-            epsilon: 1.0,
+            epsilon,
             step_count: 0,
-            rng: rand::thread_rng(),
+            n_step_windows: HashMap::new(),
+            rng,
         }
     }
 
     /// Select action using epsilon-greedy policy
-    pub fn select_action(&mut self, state: &Array1<f64>) -> usize {
+    ///
+    /// Fails if `state` does not have `input_size` features.
+    pub fn select_action(&mut self, state: &Array1<f64>) -> Result<usize, String> {
+        self.check_state("state", state)?;
         if self.rng.gen::<f64>() < self.epsilon {
             // Random action
-            self.rng.gen_range(0..self.config.output_size)
+            Ok(self.rng.gen_range(0..self.config.output_size))
         } else {
             // Greedy action
-            self.get_q_values(state).argmax().unwrap()
+            let q_values = self.get_q_values(state)?;
+            self.greedy_action(&q_values, 0..self.config.output_size)
         }
     }
 
+    /// Best of the `candidates` actions by Q-value, ignoring NaN values
+    ///
+    /// Actions within `ACTION_TIE_TOLERANCE` of the best one are tied and resolved by
+    /// `config.tie_breaking`. Fails if every candidate's Q-value is NaN.
+    pub fn greedy_action(&mut self, q_values: &Array1<f64>, candidates: impl IntoIterator<Item = usize>) -> Result<usize, String> {
+        let tied = tied_actions(q_values, candidates);
+
+        match (tied.is_empty(), self.config.tie_breaking) {
+            (true, _) => Err("Every candidate action has a NaN Q-value".to_string()),
+            (false, TieBreaking::LowestIndex) => Ok(tied.into_iter().min().unwrap()),
+            (false, TieBreaking::Random) => Ok(tied[self.rng.gen_range(0..tied.len())]),
+        }
+    }
+
+    /// Select an action with epsilon-greedy, restricted to actions where `mask` is true
+    ///
+    /// Masked actions get a Q-value of -inf, so the greedy choice never picks them, and
+    /// exploration samples uniformly among the valid actions. Fails if the state does not
+    /// fit `input_size`, the mask length differs from the number of actions or every
+    /// action is masked.
+    pub fn select_action_masked(&mut self, state: &Array1<f64>, mask: &[bool]) -> Result<usize, String> {
+        self.check_state("state", state)?;
+        if mask.len() != self.config.output_size {
+            return Err(format!(
+                "Action mask has {} entries but the network has {} actions",
+                mask.len(),
+                self.config.output_size
+            ));
+        }
+
+        let valid: Vec<usize> = (0..mask.len()).filter(|&action| mask[action]).collect();
+        if valid.is_empty() {
+            return Err("Every action is masked".to_string());
+        }
+
+        if self.rng.gen::<f64>() < self.epsilon {
+            return Ok(valid[self.rng.gen_range(0..valid.len())]);
+        }
+
+        let q_values = self.get_q_values(state)?;
+        self.greedy_action(&q_values, valid)
+    }
+
+    /// Replace the Q-values of masked actions with -inf
+    pub fn mask_q_values(q_values: &Array1<f64>, mask: &[bool]) -> Array1<f64> {
+        let mut masked = q_values.clone();
+        for (value, &allowed) in masked.iter_mut().zip(mask) {
+            if !allowed {
+                *value = f64::NEG_INFINITY;
+            }
+        }
+        masked
+    }
+
+    /// Clip each feature to its configured bounds; features without bounds pass through
+    pub fn clip_observation(&self, state: &Array1<f64>) -> Array1<f64> {
+        clip_observation(&self.config, state)
+    }
+
+    /// Clip every row of a batch of states to the configured bounds
+    fn clip_observation_batch(&self, states: &Array2<f64>) -> Array2<f64> {
+        let mut clipped = states.clone();
+        if let Some(bounds) = &self.config.observation_bounds {
+            for mut row in clipped.rows_mut() {
+                for (value, &(min, max)) in row.iter_mut().zip(bounds) {
+                    *value = value.clamp(min, max);
+                }
+            }
+        }
+        clipped
+    }
+
+    /// Fail with a descriptive message if a state does not have `input_size` features
+    fn check_state(&self, name: &str, state: &Array1<f64>) -> Result<(), String> {
+        check_state(&self.config, name, state)
+    }
+
     /// Get Q-values for given state
-    pub fn get_q_values(&self, state: &Array1<f64>) -> Array1<f64> {
-        let mut output = state.clone();
-        for layer in &self.main_network {
-            output = layer.forward(&output);
+    ///
+    /// Fails if `state` does not have `input_size` features.
+    pub fn get_q_values(&self, state: &Array1<f64>) -> Result<Array1<f64>, String> {
+        forward_state(&self.config, &self.main_network, self.main_head.as_ref(), state)
+    }
+
+    /// Keep only the main network for inference, dropping the replay buffer and training state
+    pub fn into_policy(self) -> Policy {
+        Policy {
+            config: self.config,
+            network: self.main_network,
+            head: self.main_head,
+        }
+    }
+
+    /// Get Q-values for a batch of states, one state per row
+    pub fn get_q_values_batch(&self, states: &Array2<f64>) -> Array2<f64> {
+        Self::forward_batch(&self.main_network, self.main_head.as_ref(), &self.clip_observation_batch(states))
+    }
+
+    /// Forward a batch through a network's layers and, if present, its dueling head
+    fn forward_batch(network: &[Layer], head: Option<&DuelingHead>, states: &Array2<f64>) -> Array2<f64> {
+        let mut output = states.clone();
+        for layer in network {
+            output = layer.forward_batch(&output);
+        }
+        match head {
+            Some(head) => head.forward_batch(&output),
+            None => output,
         }
-        output
     }
 
     /// Store experience in replay buffer
-    pub fn store_experience(&mut self, experience: Experience) {
+    ///
+    /// With `n_step > 1`, consecutive transitions are expected in order and the buffer
+    /// receives one n-step experience per transition once enough of them arrived: the
+    /// discounted reward sum of the next `n_step` transitions with the state reached
+    /// after them. A terminal transition flushes the window with shorter, terminal returns.
+    ///
+    /// Fails without storing anything if the state or next state does not have
+    /// `input_size` features. Transitions from several agents must go through
+    /// `store_experience_from` so their windows stay apart.
+    pub fn store_experience(&mut self, experience: Experience) -> Result<(), String> {
+        self.store_experience_from(Uuid::nil(), experience)
+    }
+
+    /// Store an experience of one source, e.g. an agent, folding n-step returns only
+    /// over that source's own transitions
+    pub fn store_experience_from(&mut self, source: Uuid, mut experience: Experience) -> Result<(), String> {
+        self.check_experience(&experience)?;
+        experience.state = self.clip_observation(&experience.state);
+        experience.next_state = self.clip_observation(&experience.next_state);
+
+        if self.config.n_step <= 1 {
+            self.push_to_buffer(experience);
+            return Ok(());
+        }
+
+        let mut window = self.n_step_windows.remove(&source).unwrap_or_default();
+        let done = experience.done;
+        window.push_back(experience);
+        if done {
+            while !window.is_empty() {
+                let n_step_experience = self.n_step_experience(&window);
+                self.push_to_buffer(n_step_experience);
+                window.pop_front();
+            }
+        } else {
+            if window.len() >= self.config.n_step {
+                let n_step_experience = self.n_step_experience(&window);
+                self.push_to_buffer(n_step_experience);
+                window.pop_front();
+            }
+            self.n_step_windows.insert(source, window);
+        }
+        Ok(())
+    }
+
+    /// Drop the transitions of a source still waiting for an n-step return, e.g. of a removed agent
+    pub fn clear_n_step_window(&mut self, source: Uuid) {
+        self.n_step_windows.remove(&source);
+    }
+
+    /// Fail with a descriptive message if an experience's states do not have `input_size` features
+    pub fn check_experience(&self, experience: &Experience) -> Result<(), String> {
+        self.check_state("state", &experience.state)?;
+        self.check_state("next_state", &experience.next_state)
+    }
+
+    /// Fold a pending window into one experience starting at its oldest transition
+    fn n_step_experience(&self, window: &VecDeque<Experience>) -> Experience {
+        let first = window.front().unwrap();
+        let last = window.back().unwrap();
+        let reward = window
+            .iter()
+            .rev()
+            .fold(0.0, |sum, experience| experience.reward + self.config.gamma * sum);
+        Experience {
+            state: first.state.clone(),
+            action: first.action,
+            reward,
+            next_state: last.next_state.clone(),
+            done: last.done,
+        }
+    }
+
+    /// TD target of an experience given the best Q-value of its next state
+    ///
+    /// Non-terminal experiences span exactly `n_step` transitions, so the bootstrap
+    /// is discounted by `gamma^n_step`.
+    pub fn td_target(&self, experience: &Experience, max_next_q: f64) -> f64 {
+        if experience.done {
+            experience.reward
+        } else {
+            experience.reward + self.config.gamma.powi(self.config.n_step.max(1) as i32) * max_next_q
+        }
+    }
+
+    fn push_to_buffer(&mut self, experience: Experience) {
         if self.replay_buffer.len() >= self.config.memory_size {
             self.replay_buffer.pop_front();
         }
@@ -172,34 +827,34 @@ impl DQN {
     }
 
     /// Train the network on a batch of experiences
+    #[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
     pub fn train(&mut self) -> Result<f64, String> {
         if self.replay_buffer.len() < self.config.batch_size {
             return Ok(0.0);
         }
 
         // Sample batch
-        let batch: Vec<Experience> = (0..self.config.batch_size)
-            .map(|_| {
-                let idx = self.rng.gen_range(0..self.replay_buffer.len());
-                self.replay_buffer[idx].clone()
-            })
-            .collect();
+        let batch = self.sample_batch(self.config.batch_size);
+        for experience in &batch {
+            self.check_state("state", &experience.state)?;
+            self.check_state("next_state", &experience.next_state)?;
+        }
+
+        // Batched forward passes for current and next states
+        let states = Self::stack_states(batch.iter().map(|e| &e.state));
+        let next_states = Self::stack_states(batch.iter().map(|e| &e.next_state));
+        let current_q_values = self.get_q_values_batch(&states);
+        let next_q_values = self.get_target_q_values_batch(&next_states);
 
         let mut total_loss = 0.0;
 
-        for experience in &batch {
+        for (i, experience) in batch.iter().enumerate() {
             // Current Q-values
-            let current_q_values = self.get_q_values(&experience.state);
-            let current_q = current_q_values[experience.action];
+            let current_q = current_q_values[[i, experience.action]];
 
             // Target Q-values
-            let target_q = if experience.done {
-                experience.reward
-            } else {
-                let next_q_values = self.get_target_q_values(&experience.next_state);
-                let max_next_q = next_q_values.iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
-                experience.reward + self.config.gamma * max_next_q
-            };
+            let max_next_q = next_q_values.row(i).iter().fold(f64::NEG_INFINITY, |a, &b| a.max(b));
+            let target_q = self.td_target(experience, max_next_q);
 
             // Calculate loss (simplified)
             let loss = (current_q - target_q).powi(2);
@@ -209,12 +864,17 @@ impl DQN {
             self.update_network(&experience.state, experience.action, target_q);
         }
 
+        self.step_count += 1;
+
         // Update epsilon
-        self.epsilon = (self.epsilon * self.config.epsilon_decay)
-            .max(self.config.epsilon_end);
+        self.epsilon = self.config.epsilon_schedule.epsilon_at(
+            self.config.epsilon_start,
+            self.config.epsilon_end,
+            self.config.epsilon_decay,
+            self.step_count,
+        );
 
         // Update target network
-        self.step_count += 1;
         if self.step_count % self.config.target_update_frequency == 0 {
             self.update_target_network();
             info!("Target network updated at step {}", self.step_count);
@@ -223,38 +883,47 @@ impl DQN {
         Ok(total_loss / self.config.batch_size as f64)
     }
 
-    /// Get Q-values from target network
-    fn get_target_q_values(&self, state: &Array1<f64>) -> Array1<f64> {
-        let mut output = state.clone();
-        for layer in &self.target_network {
-            output = layer.forward(&output);
-        }
-        output
+    /// Get Q-values from target network for a batch of states
+    fn get_target_q_values_batch(&self, states: &Array2<f64>) -> Array2<f64> {
+        Self::forward_batch(&self.target_network, self.target_head.as_ref(), &self.clip_observation_batch(states))
+    }
+
+    /// Stack state vectors into a matrix with one state per row
+    fn stack_states<'a>(states: impl ExactSizeIterator<Item = &'a Array1<f64>>) -> Array2<f64> {
+        let rows: Vec<&Array1<f64>> = states.collect();
+        let cols = rows.first().map_or(0, |row| row.len());
+        Array2::from_shape_fn((rows.len(), cols), |(i, j)| rows[i][j])
     }
 
-    /// Update main network (simplified)
+    /// Update main network towards the target for the taken action
     fn update_network(&mut self, state: &Array1<f64>, action: usize, target: f64) {
-        // Simplified gradient descent update
-        let learning_rate = self.config.learning_rate;
-        
-        // Forward pass
+        let learning_rate = self.get_learning_rate();
+
+        // Forward pass keeping pre-activations for the backward pass
         let mut activations = vec![state.clone()];
-        let mut current = state.clone();
-        
+        let mut pre_activations = Vec::with_capacity(self.main_network.len());
         for layer in &self.main_network {
-            current = layer.forward(&current);
-            activations.push(current.clone());
+            let z = layer.pre_activation(activations.last().unwrap());
+            activations.push(z.mapv(|x| layer.activation.apply(x)));
+            pre_activations.push(z);
         }
 
-        // Backward pass (simplified)
+        // Gradient of the squared TD error, only through the taken action
         let mut gradient = Array1::zeros(self.config.output_size);
-        gradient[action] = target - activations.last().unwrap()[action];
+        match &mut self.main_head {
+            Some(head) => {
+                let features = activations.last().unwrap();
+                gradient[action] = head.forward(features)[action] - target;
+                gradient = head.backward(&gradient, features, learning_rate);
+            }
+            None => gradient[action] = activations.last().unwrap()[action] - target,
+        }
 
-        // Update layers
+        // Backward pass
         for (i, layer) in self.main_network.iter_mut().enumerate().rev() {
-            if i > 0 {
-                layer.backward(&gradient, learning_rate);
-            }
+            let delta = &gradient * &pre_activations[i].mapv(|x| layer.activation.derivative(x));
+            gradient = layer.weights.t().dot(&delta);
+            layer.backward(&delta, &activations[i], learning_rate);
         }
     }
 
@@ -264,6 +933,14 @@ impl DQN {
             target_layer.weights = main_layer.weights.clone();
             target_layer.biases = main_layer.biases.clone();
         }
+        self.target_head = self.main_head.clone();
+    }
+
+    /// Get the learning rate for the current training step
+    pub fn get_learning_rate(&self) -> f64 {
+        self.config
+            .learning_rate_schedule
+            .learning_rate_at(self.config.learning_rate, self.step_count)
     }
 
     /// Get current epsilon value
@@ -276,6 +953,42 @@ impl DQN {
         self.replay_buffer.len()
     }
 
+    /// Count, fill level, reward range and share of terminal transitions of the replay buffer
+    ///
+    /// Reward statistics are zero while the buffer is empty.
+    pub fn get_buffer_stats(&self) -> BufferStats {
+        let count = self.replay_buffer.len();
+        if count == 0 {
+            return BufferStats::default();
+        }
+
+        let rewards = self.replay_buffer.iter().map(|experience| experience.reward);
+        let terminal = self.replay_buffer.iter().filter(|experience| experience.done).count();
+        BufferStats {
+            count,
+            utilization: count as f64 / self.config.memory_size as f64,
+            mean_reward: rewards.clone().sum::<f64>() / count as f64,
+            min_reward: rewards.clone().fold(f64::INFINITY, f64::min),
+            max_reward: rewards.fold(f64::NEG_INFINITY, f64::max),
+            terminal_fraction: terminal as f64 / count as f64,
+        }
+    }
+
+    /// Draw `n` experiences uniformly with replacement, as `train` does, without training
+    ///
+    /// Returns nothing while the buffer is empty.
+    pub fn sample_batch(&mut self, n: usize) -> Vec<Experience> {
+        if self.replay_buffer.is_empty() {
+            return Vec::new();
+        }
+        (0..n)
+            .map(|_| {
+                let idx = self.rng.gen_range(0..self.replay_buffer.len());
+                self.replay_buffer[idx].clone()
+            })
+            .collect()
+    }
+
     /// Save model to file
     pub fn save_model(&self, path: &str) -> Result<(), String> {
         let model_data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
@@ -291,6 +1004,69 @@ impl DQN {
         info!("Model loaded from {}", path);
         Ok(model)
     }
+
+    /// Save only the replay buffer experiences to file
+    pub fn export_buffer(&self, path: &str) -> Result<(), String> {
+        let buffer_data = serde_json::to_string(&self.replay_buffer).map_err(|e| e.to_string())?;
+        std::fs::write(path, buffer_data).map_err(|e| e.to_string())?;
+        info!("Replay buffer with {} experiences exported to {}", self.replay_buffer.len(), path);
+        Ok(())
+    }
+
+    /// Append experiences exported with `export_buffer` to the replay buffer
+    ///
+    /// Fails without importing anything if an experience does not fit the configured
+    /// `input_size`/`output_size`.
+    pub fn import_buffer(&mut self, path: &str) -> Result<usize, String> {
+        let buffer_data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let experiences: Vec<Experience> = serde_json::from_str(&buffer_data).map_err(|e| e.to_string())?;
+
+        for (i, experience) in experiences.iter().enumerate() {
+            if experience.state.len() != self.config.input_size
+                || experience.next_state.len() != self.config.input_size
+            {
+                return Err(format!(
+                    "experience {} has state size {}/{}, expected {}",
+                    i,
+                    experience.state.len(),
+                    experience.next_state.len(),
+                    self.config.input_size
+                ));
+            }
+            if experience.action >= self.config.output_size {
+                return Err(format!(
+                    "experience {} has action {}, expected less than {}",
+                    i, experience.action, self.config.output_size
+                ));
+            }
+        }
+
+        // Exported experiences are already n-step folded, so they skip `store_experience`
+        let count = experiences.len();
+        for mut experience in experiences {
+            experience.state = self.clip_observation(&experience.state);
+            experience.next_state = self.clip_observation(&experience.next_state);
+            self.push_to_buffer(experience);
+        }
+        info!("Imported {} experiences from {}", count, path);
+        Ok(count)
+    }
+
+    /// Save model to file in compact binary format
+    pub fn save_model_binary(&self, path: &str) -> Result<(), String> {
+        let model_data = bincode::serialize(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, model_data).map_err(|e| e.to_string())?;
+        info!("Binary model saved to {}", path);
+        Ok(())
+    }
+
+    /// Load model from binary file
+    pub fn load_model_binary(path: &str) -> Result<Self, String> {
+        let model_data = std::fs::read(path).map_err(|e| e.to_string())?;
+        let model: Self = bincode::deserialize(&model_data).map_err(|e| e.to_string())?;
+        info!("Binary model loaded from {}", path);
+        Ok(model)
+    }
 }
 
 #[cfg(test)]
@@ -311,7 +1087,7 @@ mod tests {
         let mut dqn = DQN::new(config);
         let state = Array1::zeros(20);
         
-        let action = dqn.select_action(&state);
+        let action = dqn.select_action(&state).unwrap();
         assert!(action < 10);
     }
 
@@ -328,7 +1104,678 @@ mod tests {
             done: false,
         };
         
-        dqn.store_experience(experience);
+        dqn.store_experience(experience).unwrap();
         assert_eq!(dqn.get_memory_size(), 1);
     }
+
+    #[test]
+    fn test_batch_q_values_match_single() {
+        let config = DQNConfig::default();
+        let dqn = DQN::new(config);
+        let states = Array2::from_shape_fn((32, 20), |(i, j)| ((i * 20 + j) as f64 * 0.37).sin());
+
+        let batch_q_values = dqn.get_q_values_batch(&states);
+        assert_eq!(batch_q_values.dim(), (32, 10));
+
+        for (i, state) in states.outer_iter().enumerate() {
+            let single_q_values = dqn.get_q_values(&state.to_owned()).unwrap();
+            for (batch_q, single_q) in batch_q_values.row(i).iter().zip(single_q_values.iter()) {
+                assert!((batch_q - single_q).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_model_round_trip() {
+        let config = DQNConfig {
+            batch_size: 4,
+            ..DQNConfig::default()
+        };
+        let mut dqn = DQN::new(config);
+
+        for i in 0..8 {
+            dqn.store_experience(Experience {
+                state: Array1::from_elem(20, i as f64 * 0.1),
+                action: i % 10,
+                reward: 1.0,
+                next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                done: false,
+            }).unwrap();
+        }
+        dqn.train().unwrap();
+
+        let path = std::env::temp_dir().join(format!("dqn_model_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        dqn.save_model_binary(path).unwrap();
+        let loaded = DQN::load_model_binary(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let state = Array1::from_elem(20, 0.5);
+        assert_eq!(dqn.get_q_values(&state).unwrap(), loaded.get_q_values(&state).unwrap());
+        assert_eq!(dqn.get_memory_size(), loaded.get_memory_size());
+        assert_eq!(dqn.get_epsilon(), loaded.get_epsilon());
+    }
+
+    #[test]
+    fn test_observation_clipping() {
+        let config = DQNConfig {
+            input_size: 4,
+            observation_bounds: Some(vec![(-1.0, 1.0); 4]),
+            ..DQNConfig::default()
+        };
+        let mut dqn = DQN::new(config);
+        let state = Array1::from(vec![5.0, -5.0, 0.5, -0.2]);
+
+        let clipped = dqn.clip_observation(&state);
+        assert_eq!(clipped, Array1::from(vec![1.0, -1.0, 0.5, -0.2]));
+        assert_eq!(dqn.get_q_values(&state).unwrap(), dqn.get_q_values(&clipped).unwrap());
+
+        dqn.store_experience(Experience {
+            state: state.clone(),
+            action: 0,
+            reward: 0.0,
+            next_state: state * 100.0,
+            done: false,
+        }).unwrap();
+        let stored = &dqn.replay_buffer[0];
+        assert!(stored.state.iter().chain(stored.next_state.iter()).all(|x| (-1.0..=1.0).contains(x)));
+        assert_eq!(stored.state[2], 0.5);
+    }
+
+    #[test]
+    fn test_cosine_learning_rate_schedule() {
+        let config = DQNConfig {
+            learning_rate: 0.01,
+            batch_size: 1,
+            learning_rate_schedule: LearningRateSchedule::Cosine {
+                end_learning_rate: 0.001,
+                total_steps: 20,
+            },
+            ..DQNConfig::default()
+        };
+        let mut dqn = DQN::new(config);
+        dqn.store_experience(Experience {
+            state: Array1::zeros(20),
+            action: 0,
+            reward: 1.0,
+            next_state: Array1::zeros(20),
+            done: true,
+        }).unwrap();
+
+        for step in 0..30 {
+            let progress = (step as f64 / 20.0).min(1.0);
+            let expected = 0.001 + 0.009 * 0.5 * (1.0 + (std::f64::consts::PI * progress).cos());
+            assert!((dqn.get_learning_rate() - expected).abs() < 1e-12);
+            dqn.train().unwrap();
+        }
+
+        assert!((dqn.get_learning_rate() - 0.001).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_replay_buffer_export_import() {
+        let mut dqn = DQN::new(DQNConfig::default());
+        for i in 0..12 {
+            dqn.store_experience(Experience {
+                state: Array1::from_elem(20, i as f64 * 0.1),
+                action: i % 10,
+                reward: i as f64,
+                next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                done: i == 11,
+            }).unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("dqn_buffer_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        dqn.export_buffer(path).unwrap();
+
+        let mut fresh = DQN::new(DQNConfig::default());
+        assert_eq!(fresh.import_buffer(path).unwrap(), 12);
+        assert_eq!(fresh.get_memory_size(), dqn.get_memory_size());
+        let (original, imported) = (&dqn.replay_buffer[5], &fresh.replay_buffer[5]);
+        assert_eq!(original.state, imported.state);
+        assert_eq!(original.action, imported.action);
+        assert_eq!(original.reward, imported.reward);
+        assert_eq!(original.next_state, imported.next_state);
+        assert_eq!(original.done, imported.done);
+
+        let mut mismatched = DQN::new(DQNConfig {
+            input_size: 8,
+            ..DQNConfig::default()
+        });
+        let result = mismatched.import_buffer(path);
+        std::fs::remove_file(path).unwrap();
+        assert!(result.is_err());
+        assert_eq!(mismatched.get_memory_size(), 0);
+    }
+
+    #[test]
+    fn test_masked_action_never_selected() {
+        let mut dqn = DQN::new(DQNConfig {
+            hidden_layers: vec![8],
+            input_size: 4,
+            output_size: 5,
+            ..DQNConfig::default()
+        });
+        // Q-values equal the output biases: action 3 is the best, then action 1
+        let output_layer = dqn.main_network.last_mut().unwrap();
+        output_layer.weights.fill(0.0);
+        output_layer.biases = Array1::from(vec![0.1, 0.5, 0.2, 2.0, 0.3]);
+        let state = Array1::from_elem(4, 1.0);
+        let mask = [true, true, true, false, true];
+
+        dqn.epsilon = 0.0;
+        assert_eq!(dqn.get_q_values(&state).unwrap()[3], 2.0);
+        assert_eq!(dqn.select_action_masked(&state, &mask), Ok(1));
+        assert_eq!(DQN::mask_q_values(&dqn.get_q_values(&state).unwrap(), &mask)[3], f64::NEG_INFINITY);
+
+        dqn.epsilon = 1.0;
+        for _ in 0..200 {
+            assert_ne!(dqn.select_action_masked(&state, &mask), Ok(3));
+        }
+
+        assert!(dqn.select_action_masked(&state, &[false; 5]).is_err());
+        assert!(dqn.select_action_masked(&state, &[true; 4]).is_err());
+    }
+
+    #[test]
+    fn test_dueling_q_values_center_advantages() {
+        let config = DQNConfig {
+            hidden_layers: vec![16, 8],
+            input_size: 6,
+            output_size: 4,
+            learning_rate: 0.01,
+            dueling: true,
+            ..DQNConfig::default()
+        };
+        let mut dqn = DQN::new(config);
+        assert_eq!(dqn.main_network.len(), 2);
+
+        let state = Array1::from(vec![0.3, -0.8, 1.5, 0.0, 2.0, -0.4]);
+        let hidden = dqn.main_network.iter().fold(state.clone(), |output, layer| layer.forward(&output));
+        let head = dqn.main_head.as_ref().unwrap();
+        let value = head.value.forward(&hidden)[0];
+        let advantages = head.advantage.forward(&hidden);
+
+        let q_values = dqn.get_q_values(&state).unwrap();
+        assert_eq!(q_values.len(), 4);
+        let mean_advantage = advantages.mean().unwrap();
+        for (q, a) in q_values.iter().zip(advantages.iter()) {
+            assert!((q - (value + a - mean_advantage)).abs() < 1e-12);
+        }
+        // The advantage contribution averages to zero, so the mean Q-value is V
+        assert!((q_values.mean().unwrap() - value).abs() < 1e-12);
+
+        let batch = DQN::stack_states([&state, &state].into_iter());
+        assert!((&dqn.get_q_values_batch(&batch).row(1) - &q_values).iter().all(|d| d.abs() < 1e-12));
+
+        // Training through both streams moves the taken action toward its target
+        let error = |dqn: &DQN| (dqn.get_q_values(&state).unwrap()[2] - 5.0).abs();
+        let initial_error = error(&dqn);
+        for _ in 0..100 {
+            dqn.update_network(&state, 2, 5.0);
+        }
+        assert!(error(&dqn) < initial_error * 0.5);
+    }
+
+    #[test]
+    fn test_n_step_targets_sum_discounted_rewards() {
+        let gamma: f64 = 0.9;
+        let mut dqn = DQN::new(DQNConfig {
+            gamma,
+            n_step: 3,
+            input_size: 1,
+            ..DQNConfig::default()
+        });
+        let rewards = [1.0, 2.0, 3.0, 4.0, 5.0];
+        for (t, &reward) in rewards.iter().enumerate() {
+            dqn.store_experience(Experience {
+                state: Array1::from_elem(1, t as f64),
+                action: t,
+                reward,
+                next_state: Array1::from_elem(1, (t + 1) as f64),
+                done: t == rewards.len() - 1,
+            }).unwrap();
+            // Nothing is stored until three transitions have arrived
+            if t == 1 {
+                assert_eq!(dqn.get_memory_size(), 0);
+            }
+        }
+        assert_eq!(dqn.get_memory_size(), 5);
+
+        let first = &dqn.replay_buffer[0];
+        let expected_return = 1.0 + gamma * 2.0 + gamma.powi(2) * 3.0;
+        assert!((first.reward - expected_return).abs() < 1e-12);
+        assert_eq!((first.state[0], first.action, first.next_state[0]), (0.0, 0, 3.0));
+        assert!(!first.done);
+        assert!((dqn.td_target(first, 10.0) - (expected_return + gamma.powi(3) * 10.0)).abs() < 1e-12);
+
+        // The terminal transition cuts the remaining returns short
+        let tail: Vec<(f64, bool)> = dqn.replay_buffer.iter().skip(2).map(|e| (e.reward, e.done)).collect();
+        let expected_tail = [3.0 + gamma * 4.0 + gamma.powi(2) * 5.0, 4.0 + gamma * 5.0, 5.0];
+        for ((reward, done), expected) in tail.iter().zip(expected_tail) {
+            assert!((reward - expected).abs() < 1e-12);
+            assert!(done);
+        }
+        assert_eq!(dqn.td_target(&dqn.replay_buffer[4], 10.0), 5.0);
+
+        // One-step targets are unchanged
+        let one_step = DQN::new(DQNConfig { gamma, ..DQNConfig::default() });
+        assert!((one_step.td_target(first, 10.0) - (expected_return + gamma * 10.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_linear_epsilon_reaches_end_at_steps() {
+        let mut dqn = DQN::new(DQNConfig {
+            epsilon_start: 0.8,
+            epsilon_end: 0.05,
+            batch_size: 1,
+            epsilon_schedule: EpsilonSchedule::Linear { steps: 10 },
+            ..DQNConfig::default()
+        });
+        dqn.store_experience(Experience {
+            state: Array1::zeros(20),
+            action: 0,
+            reward: 1.0,
+            next_state: Array1::zeros(20),
+            done: true,
+        }).unwrap();
+        assert_eq!(dqn.get_epsilon(), 0.8);
+
+        let mut epsilons = Vec::new();
+        for _ in 0..15 {
+            dqn.train().unwrap();
+            epsilons.push(dqn.get_epsilon());
+        }
+        assert!(epsilons[8] > 0.05);
+        assert_eq!(epsilons[9], 0.05);
+        assert!(epsilons[9..].iter().all(|&epsilon| epsilon == 0.05));
+        assert!(((epsilons[0] - epsilons[1]) - 0.075).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_cosine_epsilon_is_non_increasing() {
+        let schedule = EpsilonSchedule::Cosine { steps: 100 };
+        let epsilons: Vec<f64> = (0..=150).map(|step| schedule.epsilon_at(1.0, 0.1, 0.995, step)).collect();
+
+        assert_eq!(epsilons[0], 1.0);
+        assert!(epsilons.windows(2).all(|w| w[1] <= w[0]));
+        assert!((epsilons[50] - 0.55).abs() < 1e-12);
+        assert_eq!(epsilons[100], 0.1);
+        assert_eq!(epsilons[150], 0.1);
+
+        // Exponential keeps the multiplicative decay
+        let exponential = EpsilonSchedule::Exponential.epsilon_at(1.0, 0.1, 0.9, 3);
+        assert!((exponential - 0.729).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_config_builder_validates_invariants() {
+        let config = DQNConfig::builder()
+            .layers(6, vec![16, 8], 4)
+            .epsilon(0.9, 0.05, EpsilonSchedule::Linear { steps: 500 })
+            .gamma(0.99)
+            .n_step(3)
+            .build()
+            .unwrap();
+        assert_eq!((config.input_size, config.output_size), (6, 4));
+        assert_eq!(config.epsilon_end, 0.05);
+        assert_eq!(config.n_step, 3);
+        assert!(DQNConfig::default().validate().is_ok());
+
+        let inverted = DQNConfig::builder().epsilon(0.1, 0.5, EpsilonSchedule::Exponential).build();
+        assert!(inverted.unwrap_err().contains("epsilon_end"));
+        assert!(DQNConfig::builder().gamma(1.5).build().is_err());
+        assert!(DQNConfig::builder().learning_rate(0.0).build().is_err());
+        assert!(DQNConfig::builder().batch_size(0).build().is_err());
+        assert!(DQNConfig::builder().layers(4, vec![8, 0], 2).build().is_err());
+        assert!(DQNConfig::builder().batch_size(64).memory_size(32).build().is_err());
+    }
+
+    #[test]
+    fn test_wrong_state_length_is_a_clear_error() {
+        let mut dqn = DQN::new(DQNConfig {
+            hidden_layers: vec![8],
+            input_size: 4,
+            output_size: 3,
+            batch_size: 2,
+            ..DQNConfig::default()
+        });
+        let good = Array1::from_elem(4, 0.5);
+
+        for len in [0, 3, 6] {
+            let bad = Array1::from_elem(len, 0.5);
+            let expected = format!("state has {} features but the network expects input_size 4", len);
+            assert_eq!(dqn.get_q_values(&bad), Err(expected.clone()));
+            assert_eq!(dqn.select_action(&bad), Err(expected.clone()));
+            assert_eq!(dqn.select_action_masked(&bad, &[true; 3]), Err(expected.clone()));
+
+            let experience = |state: &Array1<f64>, next_state: &Array1<f64>| Experience {
+                state: state.clone(),
+                action: 0,
+                reward: 1.0,
+                next_state: next_state.clone(),
+                done: false,
+            };
+            assert_eq!(dqn.store_experience(experience(&bad, &good)), Err(expected));
+            assert_eq!(
+                dqn.store_experience(experience(&good, &bad)),
+                Err(format!("next_state has {} features but the network expects input_size 4", len))
+            );
+        }
+        assert_eq!(dqn.get_memory_size(), 0);
+
+        // A buffer filled behind the checks fails training instead of panicking
+        for _ in 0..2 {
+            dqn.replay_buffer.push_back(Experience {
+                state: Array1::from_elem(3, 0.5),
+                action: 0,
+                reward: 1.0,
+                next_state: good.clone(),
+                done: false,
+            });
+        }
+        assert!(dqn.train().unwrap_err().contains("input_size 4"));
+        assert!(dqn.select_action(&good).is_ok());
+    }
+
+    #[test]
+    fn test_tied_q_values_select_stably() {
+        let tied_network = |tie_breaking: TieBreaking| {
+            let mut dqn = DQN::new(DQNConfig {
+                hidden_layers: vec![4],
+                input_size: 2,
+                output_size: 4,
+                tie_breaking,
+                seed: Some(3),
+                ..DQNConfig::default()
+            });
+            dqn.epsilon = 0.0;
+            // Every Q-value equals the output bias, so all actions tie
+            let output_layer = dqn.main_network.last_mut().unwrap();
+            output_layer.weights.fill(0.0);
+            output_layer.biases.fill(0.7);
+            dqn
+        };
+        let state = Array1::from_elem(2, 1.0);
+
+        let mut lowest = tied_network(TieBreaking::LowestIndex);
+        for _ in 0..20 {
+            assert_eq!(lowest.select_action(&state), Ok(0));
+        }
+        assert_eq!(lowest.select_action_masked(&state, &[false, true, true, false]), Ok(1));
+
+        // Random ties spread over every action and repeat for the same seed
+        let picks = |dqn: &mut DQN| (0..200).map(|_| dqn.select_action(&state).unwrap()).collect::<Vec<_>>();
+        let first = picks(&mut tied_network(TieBreaking::Random));
+        assert_eq!(first, picks(&mut tied_network(TieBreaking::Random)));
+        for action in 0..4 {
+            assert!(first.contains(&action));
+        }
+
+        // NaN values are skipped instead of panicking
+        let q_values = Array1::from(vec![f64::NAN, 0.5, 0.5 + 1e-12, 0.2]);
+        assert_eq!(lowest.greedy_action(&q_values, 0..4), Ok(1));
+        let all_nan = Array1::from_elem(4, f64::NAN);
+        assert!(lowest.greedy_action(&all_nan, 0..4).is_err());
+    }
+
+    #[test]
+    fn test_buffer_stats_summarize_stored_experiences() {
+        let mut dqn = DQN::new(DQNConfig {
+            hidden_layers: vec![4],
+            input_size: 2,
+            output_size: 2,
+            batch_size: 4,
+            memory_size: 8,
+            seed: Some(5),
+            ..DQNConfig::default()
+        });
+        assert_eq!(dqn.get_buffer_stats(), BufferStats::default());
+        assert!(dqn.sample_batch(3).is_empty());
+
+        for (i, reward) in [2.0, -1.0, 4.0, 0.5, 3.0, -2.5].into_iter().enumerate() {
+            dqn.store_experience(Experience {
+                state: Array1::from_elem(2, i as f64),
+                action: i % 2,
+                reward,
+                next_state: Array1::from_elem(2, 0.0),
+                done: i % 3 == 2,
+            })
+            .unwrap();
+        }
+
+        let stats = dqn.get_buffer_stats();
+        assert_eq!(stats.count, 6);
+        assert_eq!(stats.utilization, 0.75);
+        assert!((stats.mean_reward - 1.0).abs() < 1e-12);
+        assert_eq!(stats.min_reward, -2.5);
+        assert_eq!(stats.max_reward, 4.0);
+        assert!((stats.terminal_fraction - 2.0 / 6.0).abs() < 1e-12);
+
+        // Sampling only reads the buffer
+        let batch = dqn.sample_batch(20);
+        assert_eq!(batch.len(), 20);
+        assert!(batch.iter().all(|experience| [2.0, -1.0, 4.0, 0.5, 3.0, -2.5].contains(&experience.reward)));
+        assert_eq!(dqn.get_buffer_stats(), stats);
+        assert_eq!(dqn.step_count, 0);
+    }
+
+    #[test]
+    fn test_policy_matches_trained_greedy_actions() {
+        fn assert_sync<T: Sync>() {}
+        assert_sync::<Policy>();
+
+        for dueling in [false, true] {
+            let mut dqn = DQN::new(DQNConfig {
+                hidden_layers: vec![8],
+                input_size: 3,
+                output_size: 4,
+                batch_size: 4,
+                dueling,
+                seed: Some(11),
+                ..DQNConfig::default()
+            });
+            for i in 0..16 {
+                let x = i as f64 / 16.0;
+                dqn.store_experience(Experience {
+                    state: Array1::from(vec![x, 1.0 - x, 0.5]),
+                    action: i % 4,
+                    reward: x,
+                    next_state: Array1::from(vec![1.0 - x, x, 0.5]),
+                    done: i % 5 == 0,
+                })
+                .unwrap();
+            }
+            for _ in 0..5 {
+                dqn.train().unwrap();
+            }
+            dqn.epsilon = 0.0;
+
+            let states: Vec<Array1<f64>> = (0..10)
+                .map(|i| Array1::from(vec![i as f64 * 0.3 - 1.0, (i as f64).sin(), 2.0 - i as f64 * 0.2]))
+                .collect();
+            let expected: Vec<(usize, Array1<f64>)> = states
+                .iter()
+                .map(|state| (dqn.select_action(state).unwrap(), dqn.get_q_values(state).unwrap()))
+                .collect();
+
+            let policy = dqn.into_policy();
+            for (state, (action, q_values)) in states.iter().zip(expected) {
+                assert_eq!(policy.act(state), Ok(action));
+                assert_eq!(policy.q_values(state).unwrap(), q_values);
+            }
+            assert!(policy.act(&Array1::zeros(2)).unwrap_err().contains("input_size 3"));
+        }
+    }
+
+    #[test]
+    fn test_n_step_windows_are_kept_per_source() {
+        let gamma: f64 = 0.5;
+        let mut dqn = DQN::new(DQNConfig {
+            gamma,
+            n_step: 2,
+            input_size: 1,
+            ..DQNConfig::default()
+        });
+        let (first_agent, second_agent) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+        // Interleaved transitions of two agents, each agent's rewards constant
+        for t in 0..4 {
+            for (source, reward) in [(first_agent, 1.0), (second_agent, 10.0)] {
+                dqn.store_experience_from(source, Experience {
+                    state: Array1::from_elem(1, reward + t as f64),
+                    action: 0,
+                    reward,
+                    next_state: Array1::from_elem(1, reward + (t + 1) as f64),
+                    done: false,
+                }).unwrap();
+            }
+        }
+
+        // Each stored return only folds transitions of one agent, in that agent's order
+        assert_eq!(dqn.get_memory_size(), 6);
+        for experience in &dqn.replay_buffer {
+            let reward = if experience.state[0] < 10.0 { 1.0 } else { 10.0 };
+            assert_eq!(experience.reward, reward + gamma * reward);
+            assert_eq!(experience.next_state[0], experience.state[0] + 2.0);
+        }
+
+        dqn.clear_n_step_window(first_agent);
+        dqn.store_experience_from(first_agent, Experience {
+            state: Array1::from_elem(1, 0.0),
+            action: 0,
+            reward: 1.0,
+            next_state: Array1::from_elem(1, 1.0),
+            done: false,
+        }).unwrap();
+        assert_eq!(dqn.get_memory_size(), 6);
+    }
+
+    #[test]
+    fn test_n_step_buffer_round_trip_is_unchanged() {
+        let config = DQNConfig {
+            n_step: 3,
+            ..DQNConfig::default()
+        };
+        let mut dqn = DQN::new(config.clone());
+        for i in 0..12 {
+            dqn.store_experience(Experience {
+                state: Array1::from_elem(20, i as f64 * 0.1),
+                action: i % 10,
+                reward: i as f64,
+                next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                done: i == 7,
+            }).unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("dqn_n_step_buffer_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        dqn.export_buffer(path).unwrap();
+        let mut fresh = DQN::new(config);
+        let imported = fresh.import_buffer(path);
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(imported.unwrap(), dqn.get_memory_size());
+        assert_eq!(
+            serde_json::to_string(&fresh.replay_buffer).unwrap(),
+            serde_json::to_string(&dqn.replay_buffer).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_activation_derivatives_match_finite_differences() {
+        let h = 1e-6;
+        for activation in [
+            ActivationFunction::ReLU,
+            ActivationFunction::Sigmoid,
+            ActivationFunction::Tanh,
+            ActivationFunction::Linear,
+        ] {
+            for x in [-1.3, -0.2, 0.4, 2.1] {
+                let numeric = (activation.apply(x + h) - activation.apply(x - h)) / (2.0 * h);
+                assert!((activation.derivative(x) - numeric).abs() < 1e-6, "{:?} at {}", activation, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_update_network_follows_the_loss_gradient() {
+        let mut dqn = DQN::new(DQNConfig {
+            hidden_layers: vec![6, 4],
+            input_size: 3,
+            output_size: 2,
+            learning_rate: 1e-3,
+            seed: Some(5),
+            ..DQNConfig::default()
+        });
+        let state = Array1::from(vec![0.3, -0.8, 0.5]);
+        let (action, target) = (1, 2.0);
+        let loss = |dqn: &DQN| 0.5 * (dqn.get_q_values(&state).unwrap()[action] - target).powi(2);
+
+        // Central differences of the squared TD error for every weight and bias
+        let h = 1e-6;
+        let mut probe = dqn.clone();
+        let mut numeric = Vec::new();
+        for layer in 0..probe.main_network.len() {
+            let (rows, cols) = probe.main_network[layer].weights.dim();
+            for r in 0..rows {
+                for c in 0..cols {
+                    probe.main_network[layer].weights[[r, c]] += h;
+                    let plus = loss(&probe);
+                    probe.main_network[layer].weights[[r, c]] -= 2.0 * h;
+                    let minus = loss(&probe);
+                    probe.main_network[layer].weights[[r, c]] += h;
+                    numeric.push((plus - minus) / (2.0 * h));
+                }
+                probe.main_network[layer].biases[r] += h;
+                let plus = loss(&probe);
+                probe.main_network[layer].biases[r] -= 2.0 * h;
+                let minus = loss(&probe);
+                probe.main_network[layer].biases[r] += h;
+                numeric.push((plus - minus) / (2.0 * h));
+            }
+        }
+
+        let before = dqn.clone();
+        let loss_before = loss(&dqn);
+        dqn.update_network(&state, action, target);
+
+        // A plain gradient descent step moves each parameter by -learning_rate * gradient
+        let mut analytic = Vec::new();
+        for (old, new) in before.main_network.iter().zip(&dqn.main_network) {
+            for r in 0..old.weights.nrows() {
+                for c in 0..old.weights.ncols() {
+                    analytic.push((old.weights[[r, c]] - new.weights[[r, c]]) / 1e-3);
+                }
+                analytic.push((old.biases[r] - new.biases[r]) / 1e-3);
+            }
+        }
+        assert_eq!(analytic.len(), numeric.len());
+        for (a, n) in analytic.iter().zip(&numeric) {
+            assert!((a - n).abs() < 1e-4 * (1.0 + n.abs()), "analytic {} vs numeric {}", a, n);
+        }
+        assert!(loss(&dqn) < loss_before);
+    }
+
+    #[test]
+    fn test_seeded_networks_start_identical() {
+        let state = Array1::from_elem(20, 0.4);
+        for dueling in [false, true] {
+            let network = |seed: u64| {
+                DQN::new(DQNConfig {
+                    dueling,
+                    seed: Some(seed),
+                    ..DQNConfig::default()
+                })
+            };
+            let (first, second) = (network(42), network(42));
+            assert_eq!(first.get_q_values(&state).unwrap(), second.get_q_values(&state).unwrap());
+            assert_eq!(
+                serde_json::to_string(&first.target_network).unwrap(),
+                serde_json::to_string(&second.target_network).unwrap()
+            );
+            assert_ne!(first.get_q_values(&state).unwrap(), network(43).get_q_values(&state).unwrap());
+        }
+    }
 }
@@ -0,0 +1,986 @@
+//! Learning engine for smart city agents
+//! Coordinates experience collection and DQN training
+
+pub mod dqn;
+pub mod reward;
+
+use std::collections::{HashMap, VecDeque};
+
+use anyhow::{anyhow, Result};
+use ndarray::Array1;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::agent::{ACTION_COUNT, OBSERVATION_SIZE};
+use crate::AIConfig;
+use dqn::{BufferStats, DQNConfig, Experience, DQN};
+use reward::{RewardBreakdown, RewardCalculator};
+
+/// Weight of the newest sample in the smoothed learning curves
+pub const LEARNING_CURVE_SMOOTHING: f64 = 0.1;
+
+/// Which network an agent's experiences train and its actions come from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkSharing {
+    /// Every agent uses the one shared network
+    #[default]
+    Shared,
+    /// One network per agent type, configured by `LearningEngine::dqn_config_for`
+    PerType,
+    /// One network per agent, configured for the agent's type
+    PerAgent,
+}
+
+/// Most recent reward breakdowns kept for debugging
+pub const REWARD_LOG_CAPACITY: usize = 1000;
+
+/// Scalar reward of a submitted experience with the components it came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardLogEntry {
+    /// Reward as submitted, the weighted sum of `breakdown`
+    pub reward: f64,
+    /// Reward as stored for training: `reward` standardized when `AIConfig::normalize_rewards` is set
+    pub stored_reward: f64,
+    pub breakdown: RewardBreakdown,
+}
+
+/// Exponential moving average of a noisy series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExponentialMovingAverage {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl ExponentialMovingAverage {
+    /// Average weighting each new sample by `alpha` (clamped to (0, 1])
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(f64::EPSILON, 1.0),
+            value: None,
+        }
+    }
+
+    /// Fold in a sample and return the new average; the first sample is taken as is
+    pub fn update(&mut self, sample: f64) -> f64 {
+        let value = match self.value {
+            Some(value) => value + self.alpha * (sample - value),
+            None => sample,
+        };
+        self.value = Some(value);
+        value
+    }
+
+    /// Current average, or None before the first sample
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Standard deviation below which rewards are only centered, not scaled
+pub const REWARD_NORMALIZATION_MIN_STD: f64 = 1e-8;
+
+/// Running mean and variance of a series, updated with Welford's algorithm
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunningStandardizer {
+    count: u64,
+    mean: f64,
+    /// Sum of squared deviations from the mean
+    m2: f64,
+}
+
+impl RunningStandardizer {
+    /// Fold in a sample
+    pub fn update(&mut self, sample: f64) {
+        self.count += 1;
+        let delta = sample - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (sample - self.mean);
+    }
+
+    /// Shift and scale a sample by the current mean and standard deviation
+    pub fn standardize(&self, sample: f64) -> f64 {
+        (sample - self.mean) / self.std_dev().max(REWARD_NORMALIZATION_MIN_STD)
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population variance of the samples so far, 0 before the second one
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        self.m2 / self.count as f64
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+}
+
+/// Aggregates of one finished episode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EpisodeSummary {
+    pub total_reward: f64,
+    /// Number of steps, including the terminal one
+    pub length: usize,
+    /// Agent energy at the terminal step
+    pub final_energy: f64,
+}
+
+/// Accumulates an agent's steps into per-episode summaries
+#[derive(Debug, Clone, Default)]
+pub struct EpisodeTracker {
+    reward: f64,
+    length: usize,
+    summaries: Vec<EpisodeSummary>,
+}
+
+impl EpisodeTracker {
+    /// Record one step, returning the episode's summary when `done` ends it
+    pub fn record_step(&mut self, reward: f64, energy: f64, done: bool) -> Option<EpisodeSummary> {
+        self.reward += reward;
+        self.length += 1;
+        if !done {
+            return None;
+        }
+
+        let summary = EpisodeSummary {
+            total_reward: self.reward,
+            length: self.length,
+            final_energy: energy,
+        };
+        self.reward = 0.0;
+        self.length = 0;
+        self.summaries.push(summary.clone());
+        Some(summary)
+    }
+
+    /// Summaries of the finished episodes, oldest first
+    pub fn summaries(&self) -> &[EpisodeSummary] {
+        &self.summaries
+    }
+
+    /// Steps taken in the episode in progress
+    pub fn current_length(&self) -> usize {
+        self.length
+    }
+}
+
+/// Experience queued by an agent, with the key of the network that owns it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentExperience {
+    pub agent_id: Uuid,
+    /// Key as in `LearningEngine::network_key`, or None for the shared network
+    pub network: Option<String>,
+    pub experience: Experience,
+}
+
+/// Metrics reported after each training step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainStepMetrics {
+    pub step: usize,
+    pub loss: f64,
+    /// Loss smoothed by an exponential moving average
+    pub smoothed_loss: f64,
+    pub epsilon: f64,
+    pub memory_size: usize,
+}
+
+/// Serializable snapshot of the learning engine state
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LearningState {
+    pub dqn: DQN,
+    pub train_steps: usize,
+    pub pending_experiences: Vec<Experience>,
+    /// Networks owned by an agent type or agent, keyed as in `LearningEngine::network_key`
+    #[serde(default)]
+    pub networks: HashMap<String, DQN>,
+    #[serde(default)]
+    pub pending_agent_experiences: Vec<AgentExperience>,
+    /// Reward statistics used when `AIConfig::normalize_rewards` is set
+    #[serde(default)]
+    pub reward_normalizer: RunningStandardizer,
+}
+
+/// Callback invoked with training progress
+pub type ProgressCallback = Box<dyn Fn(&TrainStepMetrics) + Send + Sync>;
+
+struct ProgressListener {
+    interval: usize,
+    callback: ProgressCallback,
+}
+
+/// Learning engine shared by all agents
+pub struct LearningEngine {
+    config: AIConfig,
+    rewards: RewardCalculator,
+    dqn: RwLock<DQN>,
+    pending_experiences: Mutex<Vec<Experience>>,
+    /// Networks of agent types or agents when `AIConfig::network_sharing` is not `Shared`
+    networks: RwLock<HashMap<String, DQN>>,
+    /// Experiences of known agents, whose n-step returns are folded per agent
+    pending_agent_experiences: Mutex<Vec<AgentExperience>>,
+    train_steps: RwLock<usize>,
+    progress_listeners: RwLock<Vec<ProgressListener>>,
+    loss_curve: RwLock<ExponentialMovingAverage>,
+    reward_curve: RwLock<ExponentialMovingAverage>,
+    reward_normalizer: Mutex<RunningStandardizer>,
+    episodes: Mutex<HashMap<Uuid, EpisodeTracker>>,
+    reward_log: Mutex<VecDeque<RewardLogEntry>>,
+}
+
+impl LearningEngine {
+    pub fn new(config: AIConfig) -> Self {
+        let dqn_config = Self::base_dqn_config(&config);
+
+        Self {
+            rewards: RewardCalculator::new(config.reward.clone()),
+            config,
+            dqn: RwLock::new(DQN::new(dqn_config)),
+            pending_experiences: Mutex::new(Vec::new()),
+            networks: RwLock::new(HashMap::new()),
+            pending_agent_experiences: Mutex::new(Vec::new()),
+            train_steps: RwLock::new(0),
+            progress_listeners: RwLock::new(Vec::new()),
+            loss_curve: RwLock::new(ExponentialMovingAverage::new(LEARNING_CURVE_SMOOTHING)),
+            reward_curve: RwLock::new(ExponentialMovingAverage::new(LEARNING_CURVE_SMOOTHING)),
+            reward_normalizer: Mutex::new(RunningStandardizer::default()),
+            episodes: Mutex::new(HashMap::new()),
+            reward_log: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn base_dqn_config(config: &AIConfig) -> DQNConfig {
+        DQNConfig {
+            learning_rate: config.learning_rate,
+            batch_size: config.batch_size,
+            memory_size: config.memory_size,
+            input_size: OBSERVATION_SIZE,
+            output_size: ACTION_COUNT,
+            seed: config.seed,
+            ..DQNConfig::default()
+        }
+    }
+
+    /// DQN configuration for a network dedicated to one agent type
+    ///
+    /// Uses the type's entry in `AIConfig::epsilon_end_by_type` as the exploration floor when present.
+    pub fn dqn_config_for(&self, agent_type: &str) -> DQNConfig {
+        let mut dqn_config = Self::base_dqn_config(&self.config);
+        if let Some(&epsilon_end) = self.config.epsilon_end_by_type.get(agent_type) {
+            dqn_config.epsilon_end = epsilon_end;
+        }
+        dqn_config
+    }
+
+    /// Create a fresh network for one agent type
+    pub fn create_network_for(&self, agent_type: &str) -> DQN {
+        DQN::new(self.dqn_config_for(agent_type))
+    }
+
+    /// Key of the network owning an agent's experiences, or None for the shared network
+    fn network_key(&self, agent_id: Uuid, agent_type: &str) -> Option<String> {
+        match self.config.network_sharing {
+            NetworkSharing::Shared => None,
+            NetworkSharing::PerType => Some(agent_type.to_string()),
+            NetworkSharing::PerAgent => Some(agent_id.to_string()),
+        }
+    }
+
+    /// Run `f` on the network owning an agent's experiences, creating it on first use
+    async fn with_agent_network<T>(&self, agent_id: Uuid, agent_type: &str, f: impl FnOnce(&mut DQN) -> T) -> T {
+        match self.network_key(agent_id, agent_type) {
+            None => f(&mut *self.dqn.write().await),
+            Some(key) => {
+                let mut networks = self.networks.write().await;
+                f(networks.entry(key).or_insert_with(|| self.create_network_for(agent_type)))
+            }
+        }
+    }
+
+    /// Initialize the learning engine
+    pub async fn initialize(&self) -> Result<()> {
+        info!(
+            "Learning engine initialized (batch_size={}, memory_size={})",
+            self.config.batch_size, self.config.memory_size
+        );
+        Ok(())
+    }
+
+    /// Track the raw reward and, with `AIConfig::normalize_rewards`, standardize it for storage
+    async fn prepare_experience(&self, mut experience: Experience) -> Experience {
+        self.reward_curve.write().await.update(experience.reward);
+        if self.config.normalize_rewards {
+            let mut normalizer = self.reward_normalizer.lock().await;
+            normalizer.update(experience.reward);
+            experience.reward = normalizer.standardize(experience.reward);
+        }
+        experience
+    }
+
+    /// Queue an experience to be stored on the next processing pass
+    ///
+    /// The smoothed reward curve follows the raw reward, even when rewards are normalized.
+    /// Fails without queuing anything if the state or next state does not fit the shared network,
+    /// so one bad experience cannot abort a later processing pass.
+    pub async fn submit_experience(&self, experience: Experience) -> Result<()> {
+        self.queue_experience(experience).await.map(|_| ())
+    }
+
+    /// Queue an experience as `submit_experience`, returning the reward as stored
+    async fn queue_experience(&self, experience: Experience) -> Result<f64> {
+        self.dqn.read().await.check_experience(&experience).map_err(|e| anyhow!(e))?;
+        let experience = self.prepare_experience(experience).await;
+        let stored_reward = experience.reward;
+        self.pending_experiences.lock().await.push(experience);
+        Ok(stored_reward)
+    }
+
+    /// Queue an agent's experience for the network that owns it under `AIConfig::network_sharing`
+    ///
+    /// The raw reward also counts toward the agent's current episode, which `experience.done`
+    /// ends with `energy` as its final energy. Fails without queuing or recording anything if
+    /// the experience does not fit the owning network.
+    pub async fn submit_agent_experience(
+        &self,
+        agent_id: Uuid,
+        agent_type: &str,
+        energy: f64,
+        experience: Experience,
+    ) -> Result<()> {
+        self.queue_agent_experience(agent_id, agent_type, energy, experience)
+            .await
+            .map(|_| ())
+    }
+
+    /// Queue an agent's experience as `submit_agent_experience`, returning the reward as stored
+    async fn queue_agent_experience(
+        &self,
+        agent_id: Uuid,
+        agent_type: &str,
+        energy: f64,
+        experience: Experience,
+    ) -> Result<f64> {
+        self.with_agent_network(agent_id, agent_type, |dqn| dqn.check_experience(&experience))
+            .await
+            .map_err(|e| anyhow!(e))?;
+        self.record_episode_step(agent_id, experience.reward, energy, experience.done).await;
+        let experience = self.prepare_experience(experience).await;
+        let stored_reward = experience.reward;
+        self.pending_agent_experiences.lock().await.push(AgentExperience {
+            agent_id,
+            network: self.network_key(agent_id, agent_type),
+            experience,
+        });
+        Ok(stored_reward)
+    }
+
+    /// Epsilon-greedy action from the network that owns an agent's experiences
+    pub async fn select_agent_action(&self, agent_id: Uuid, agent_type: &str, state: &Array1<f64>) -> Result<usize> {
+        self.with_agent_network(agent_id, agent_type, |dqn| dqn.select_action(state))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Q-values from the network that owns an agent's experiences
+    pub async fn get_agent_q_values(&self, agent_id: Uuid, agent_type: &str, state: &Array1<f64>) -> Result<Array1<f64>> {
+        self.with_agent_network(agent_id, agent_type, |dqn| dqn.get_q_values(state))
+            .await
+            .map_err(|e| anyhow!(e))
+    }
+
+    /// Queue an experience and log the components of its reward
+    ///
+    /// Only the last `REWARD_LOG_CAPACITY` breakdowns are kept. Rejected experiences are not logged.
+    pub async fn submit_experience_with_breakdown(&self, experience: Experience, breakdown: RewardBreakdown) -> Result<()> {
+        let reward = experience.reward;
+        let stored_reward = self.queue_experience(experience).await?;
+        self.log_reward(reward, stored_reward, breakdown).await;
+        Ok(())
+    }
+
+    /// Queue an agent's experience as `submit_agent_experience` and log the components of its reward
+    pub async fn submit_agent_experience_with_breakdown(
+        &self,
+        agent_id: Uuid,
+        agent_type: &str,
+        energy: f64,
+        experience: Experience,
+        breakdown: RewardBreakdown,
+    ) -> Result<()> {
+        let reward = experience.reward;
+        let stored_reward = self.queue_agent_experience(agent_id, agent_type, energy, experience).await?;
+        self.log_reward(reward, stored_reward, breakdown).await;
+        Ok(())
+    }
+
+    async fn log_reward(&self, reward: f64, stored_reward: f64, breakdown: RewardBreakdown) {
+        let mut reward_log = self.reward_log.lock().await;
+        if reward_log.len() >= REWARD_LOG_CAPACITY {
+            reward_log.pop_front();
+        }
+        reward_log.push_back(RewardLogEntry {
+            reward,
+            stored_reward,
+            breakdown,
+        });
+    }
+
+    /// Logged reward breakdowns, oldest first
+    pub async fn get_reward_log(&self) -> Vec<RewardLogEntry> {
+        self.reward_log.lock().await.iter().cloned().collect()
+    }
+
+    /// Move queued experiences into the replay buffers and train each network that got some once
+    pub async fn process_experiences(&self) -> Result<()> {
+        let pending: Vec<Experience> = self.pending_experiences.lock().await.drain(..).collect();
+        let mut shared = Vec::new();
+        let mut owned = Vec::new();
+        for pending in self.pending_agent_experiences.lock().await.drain(..) {
+            match pending.network {
+                None => shared.push((pending.agent_id, pending.experience)),
+                Some(key) => owned.push((key, pending.agent_id, pending.experience)),
+            }
+        }
+
+        if !pending.is_empty() || !shared.is_empty() {
+            {
+                let mut dqn = self.dqn.write().await;
+                for experience in pending {
+                    dqn.store_experience(experience).map_err(|e| anyhow!(e))?;
+                }
+                for (agent_id, experience) in shared {
+                    dqn.store_experience_from(agent_id, experience).map_err(|e| anyhow!(e))?;
+                }
+            }
+            self.train_step().await?;
+        }
+
+        if !owned.is_empty() {
+            self.train_owned_networks(owned).await?;
+        }
+        Ok(())
+    }
+
+    /// Store experiences only in the networks that own them, then train each of those once
+    async fn train_owned_networks(&self, experiences: Vec<(String, Uuid, Experience)>) -> Result<()> {
+        let mut networks = self.networks.write().await;
+        let mut touched: Vec<String> = Vec::new();
+        for (key, agent_id, experience) in experiences {
+            let network = networks
+                .get_mut(&key)
+                .ok_or_else(|| anyhow!("No network owns experiences of {}", key))?;
+            network.store_experience_from(agent_id, experience).map_err(|e| anyhow!(e))?;
+            if !touched.contains(&key) {
+                touched.push(key);
+            }
+        }
+
+        for key in touched {
+            let network = networks.get_mut(&key).unwrap();
+            let loss = network.train().map_err(|e| anyhow!(e))?;
+            let metrics = self.record_train_step(loss, network).await;
+            debug!("Training step {} of the {} network finished with loss {:.4}", metrics.step, key, loss);
+        }
+        Ok(())
+    }
+
+    /// Forget a removed agent: its network under `NetworkSharing::PerAgent`, its queued
+    /// experiences, its unfinished n-step windows and its episodes
+    pub async fn remove_agent(&self, agent_id: Uuid) {
+        self.episodes.lock().await.remove(&agent_id);
+        self.pending_agent_experiences
+            .lock()
+            .await
+            .retain(|pending| pending.agent_id != agent_id);
+        self.dqn.write().await.clear_n_step_window(agent_id);
+
+        let mut networks = self.networks.write().await;
+        if self.config.network_sharing == NetworkSharing::PerAgent {
+            networks.remove(&agent_id.to_string());
+        }
+        for network in networks.values_mut() {
+            network.clear_n_step_window(agent_id);
+        }
+    }
+
+    /// Run a single training step of the shared network and notify progress listeners
+    pub async fn train_step(&self) -> Result<TrainStepMetrics> {
+        let mut dqn = self.dqn.write().await;
+        let loss = dqn.train().map_err(|e| anyhow!(e))?;
+        let metrics = self.record_train_step(loss, &dqn).await;
+
+        debug!("Training step {} finished with loss {:.4}", metrics.step, metrics.loss);
+        Ok(metrics)
+    }
+
+    /// Count a training step of any network, update the loss curve and notify progress listeners
+    async fn record_train_step(&self, loss: f64, network: &DQN) -> TrainStepMetrics {
+        let metrics = {
+            let mut train_steps = self.train_steps.write().await;
+            *train_steps += 1;
+            TrainStepMetrics {
+                step: *train_steps,
+                loss,
+                smoothed_loss: self.loss_curve.write().await.update(loss),
+                epsilon: network.get_epsilon(),
+                memory_size: network.get_memory_size(),
+            }
+        };
+
+        for listener in self.progress_listeners.read().await.iter() {
+            if metrics.step % listener.interval == 0 {
+                (listener.callback)(&metrics);
+            }
+        }
+        metrics
+    }
+
+    /// Register a callback invoked every `interval` training steps
+    pub async fn register_progress_callback<F>(&self, interval: usize, callback: F)
+    where
+        F: Fn(&TrainStepMetrics) + Send + Sync + 'static,
+    {
+        self.progress_listeners.write().await.push(ProgressListener {
+            interval: interval.max(1),
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Export the network, replay buffer and counters for persistence
+    pub async fn export_state(&self) -> LearningState {
+        LearningState {
+            dqn: self.dqn.read().await.clone(),
+            train_steps: *self.train_steps.read().await,
+            pending_experiences: self.pending_experiences.lock().await.clone(),
+            networks: self.networks.read().await.clone(),
+            pending_agent_experiences: self.pending_agent_experiences.lock().await.clone(),
+            reward_normalizer: self.reward_normalizer.lock().await.clone(),
+        }
+    }
+
+    /// Restore a previously exported state
+    pub async fn import_state(&self, state: LearningState) {
+        *self.dqn.write().await = state.dqn;
+        *self.train_steps.write().await = state.train_steps;
+        *self.pending_experiences.lock().await = state.pending_experiences;
+        *self.networks.write().await = state.networks;
+        *self.pending_agent_experiences.lock().await = state.pending_agent_experiences;
+        *self.reward_normalizer.lock().await = state.reward_normalizer;
+    }
+
+    /// Reward calculator configured from `AIConfig::reward`
+    pub fn rewards(&self) -> &RewardCalculator {
+        &self.rewards
+    }
+
+    /// Get number of training steps executed so far
+    pub async fn get_train_steps(&self) -> usize {
+        *self.train_steps.read().await
+    }
+
+    /// Record one step of an agent's episode, returning the summary when `done` ends it
+    pub async fn record_episode_step(
+        &self,
+        agent_id: Uuid,
+        reward: f64,
+        energy: f64,
+        done: bool,
+    ) -> Option<EpisodeSummary> {
+        let summary = self
+            .episodes
+            .lock()
+            .await
+            .entry(agent_id)
+            .or_default()
+            .record_step(reward, energy, done);
+        if let Some(summary) = &summary {
+            debug!(
+                "Episode of agent {} finished after {} steps with reward {:.3}",
+                agent_id, summary.length, summary.total_reward
+            );
+        }
+        summary
+    }
+
+    /// Summaries of an agent's finished episodes, oldest first
+    pub async fn get_episode_summaries(&self, agent_id: Uuid) -> Vec<EpisodeSummary> {
+        self.episodes
+            .lock()
+            .await
+            .get(&agent_id)
+            .map(|tracker| tracker.summaries().to_vec())
+            .unwrap_or_default()
+    }
+
+    /// Moving average of the training loss, or None before the first training step
+    pub async fn get_smoothed_loss(&self) -> Option<f64> {
+        self.loss_curve.read().await.value()
+    }
+
+    /// Moving average of submitted rewards, or None before the first experience
+    pub async fn get_smoothed_reward(&self) -> Option<f64> {
+        self.reward_curve.read().await.value()
+    }
+
+    /// Statistics of the shared network's replay buffer
+    pub async fn get_buffer_stats(&self) -> BufferStats {
+        self.dqn.read().await.get_buffer_stats()
+    }
+
+    /// Sample `n` experiences from the shared network's replay buffer without training
+    pub async fn sample_experiences(&self, n: usize) -> Vec<Experience> {
+        self.dqn.write().await.sample_batch(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_progress_callback_interval() {
+        let engine = LearningEngine::new(AIConfig::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&calls);
+        engine
+            .register_progress_callback(10, move |_metrics| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            })
+            .await;
+
+        for _ in 0..100 {
+            engine.train_step().await.unwrap();
+        }
+
+        assert_eq!(engine.get_train_steps().await, 100);
+        assert_eq!(calls.load(Ordering::SeqCst), 10);
+    }
+
+    #[test]
+    fn test_per_type_epsilon_floor() {
+        let config = AIConfig {
+            batch_size: 4,
+            epsilon_end_by_type: HashMap::from([
+                ("government".to_string(), 0.02),
+                ("citizen".to_string(), 0.2),
+            ]),
+            ..AIConfig::default()
+        };
+        let engine = LearningEngine::new(config);
+        let fast_decay = |agent_type: &str| {
+            DQN::new(DQNConfig {
+                epsilon_decay: 0.9,
+                ..engine.dqn_config_for(agent_type)
+            })
+        };
+        let mut government = fast_decay("government");
+        let mut citizen = fast_decay("citizen");
+
+        for network in [&mut government, &mut citizen] {
+            for i in 0..8 {
+                network.store_experience(Experience {
+                    state: Array1::from_elem(20, i as f64 * 0.1),
+                    action: i % 10,
+                    reward: 1.0,
+                    next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                    done: false,
+                }).unwrap();
+            }
+            for _ in 0..100 {
+                network.train().unwrap();
+            }
+        }
+
+        assert_eq!(government.get_epsilon(), 0.02);
+        assert_eq!(citizen.get_epsilon(), 0.2);
+        assert_eq!(engine.dqn_config_for("business").epsilon_end, DQNConfig::default().epsilon_end);
+    }
+
+    #[test]
+    fn test_moving_average_smooths_noisy_loss() {
+        // Decaying loss with alternating noise
+        let raw: Vec<f64> = (0..200)
+            .map(|i| 2.0 * (-(i as f64) / 50.0).exp() + if i % 2 == 0 { 0.3 } else { -0.3 })
+            .collect();
+        let mut average = ExponentialMovingAverage::new(LEARNING_CURVE_SMOOTHING);
+        let smoothed: Vec<f64> = raw.iter().map(|&loss| average.update(loss)).collect();
+
+        let step_variance = |series: &[f64]| {
+            let steps: Vec<f64> = series.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            let mean = steps.iter().sum::<f64>() / steps.len() as f64;
+            steps.iter().map(|step| (step - mean).powi(2)).sum::<f64>() / steps.len() as f64
+        };
+        assert!(step_variance(&smoothed) < 0.1 * step_variance(&raw));
+
+        // Still follows the downward trend
+        let trend_end = 2.0 * (-199.0_f64 / 50.0).exp();
+        assert!(smoothed[199] < smoothed[20]);
+        assert!((smoothed[199] - trend_end).abs() < 0.1);
+    }
+
+    #[tokio::test]
+    async fn test_smoothed_curves_track_engine() {
+        let engine = LearningEngine::new(AIConfig::default());
+        assert_eq!(engine.get_smoothed_loss().await, None);
+        assert_eq!(engine.get_smoothed_reward().await, None);
+
+        for reward in [1.0, 3.0] {
+            engine
+                .submit_experience(Experience {
+                    state: Array1::zeros(20),
+                    action: 0,
+                    reward,
+                    next_state: Array1::zeros(20),
+                    done: false,
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(engine.get_smoothed_reward().await, Some(1.0 + LEARNING_CURVE_SMOOTHING * 2.0));
+
+        let metrics = engine.train_step().await.unwrap();
+        assert_eq!(engine.get_smoothed_loss().await, Some(metrics.smoothed_loss));
+    }
+
+    #[tokio::test]
+    async fn test_episode_summaries_per_agent() {
+        let engine = LearningEngine::new(AIConfig::default());
+        let agent_id = Uuid::new_v4();
+        let other_id = Uuid::new_v4();
+
+        for (length, energy) in [(3, 40.0), (5, 10.0)] {
+            for step in 0..length {
+                let done = step == length - 1;
+                let summary = engine.record_episode_step(agent_id, 0.5 * (step + 1) as f64, energy, done).await;
+                assert_eq!(summary.is_some(), done);
+            }
+        }
+        // Another agent's unfinished episode stays separate
+        engine.record_episode_step(other_id, 100.0, 1.0, false).await;
+
+        let summaries = engine.get_episode_summaries(agent_id).await;
+        assert_eq!(
+            summaries,
+            vec![
+                EpisodeSummary { total_reward: 3.0, length: 3, final_energy: 40.0 },
+                EpisodeSummary { total_reward: 7.5, length: 5, final_energy: 10.0 },
+            ]
+        );
+        assert!(engine.get_episode_summaries(other_id).await.is_empty());
+
+        // Submitted experiences feed the episodes, and a removed agent's episodes are dropped
+        for (step, reward) in [1.0, 2.0].into_iter().enumerate() {
+            let experience = Experience {
+                state: Array1::zeros(20),
+                action: 0,
+                reward,
+                next_state: Array1::zeros(20),
+                done: step == 1,
+            };
+            engine.submit_agent_experience(other_id, "citizen", 25.0, experience).await.unwrap();
+        }
+        assert_eq!(
+            engine.get_episode_summaries(other_id).await,
+            vec![EpisodeSummary { total_reward: 103.0, length: 3, final_energy: 25.0 }]
+        );
+        engine.remove_agent(agent_id).await;
+        assert!(engine.get_episode_summaries(agent_id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reward_log_components_sum_to_reward() {
+        let config = AIConfig {
+            reward: reward::RewardConfig {
+                congestion_weight: 0.5,
+                goal_weight: 2.0,
+                profit_weight: 0.1,
+                ..reward::RewardConfig::default()
+            },
+            ..AIConfig::default()
+        };
+        let engine = LearningEngine::new(config);
+        let mut environment = crate::environment::Environment::new();
+        let agent_id = Uuid::new_v4();
+        environment.register_agent(agent_id, (100.0, 100.0));
+        environment.register_agent(Uuid::new_v4(), (105.0, 100.0));
+
+        let breakdowns = [
+            engine.rewards().breakdown_with_goal(agent_id, &environment, (80.0, 100.0), (200.0, 100.0)),
+            engine.rewards().breakdown_with_profit(agent_id, &environment, 30.0, 12.0),
+        ];
+        for breakdown in breakdowns {
+            let experience = Experience {
+                state: Array1::zeros(20),
+                action: 0,
+                reward: breakdown.total(),
+                next_state: Array1::zeros(20),
+                done: false,
+            };
+            engine.submit_experience_with_breakdown(experience, breakdown).await.unwrap();
+        }
+        // Experiences submitted without a breakdown are not logged
+        engine
+            .submit_experience(Experience {
+                state: Array1::zeros(20),
+                action: 0,
+                reward: 9.0,
+                next_state: Array1::zeros(20),
+                done: false,
+            })
+            .await
+            .unwrap();
+
+        let log = engine.get_reward_log().await;
+        assert_eq!(log.len(), 2);
+        for entry in &log {
+            let weighted: f64 = entry.breakdown.components.iter().map(|c| c.weight * c.value).sum();
+            assert_eq!(weighted, entry.reward);
+        }
+
+        let congestion = log[0].breakdown.component(reward::CONGESTION_COMPONENT).unwrap();
+        assert_eq!((congestion.value, congestion.weight), (0.5, 0.5));
+        let goal = log[0].breakdown.component(reward::GOAL_COMPONENT).unwrap();
+        assert_eq!(goal.weight, 2.0);
+        assert!(goal.value > 0.0);
+        assert!(log[0].breakdown.component(reward::PROFIT_COMPONENT).is_none());
+        let profit = log[1].breakdown.component(reward::PROFIT_COMPONENT).unwrap();
+        assert_eq!((profit.value, profit.weight), (18.0, 0.1));
+        assert!((log[1].reward - (0.25 + 1.8)).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_per_type_networks_train_only_on_their_own_experiences() {
+        let config = AIConfig {
+            batch_size: 4,
+            network_sharing: NetworkSharing::PerType,
+            ..AIConfig::default()
+        };
+        let engine = LearningEngine::new(config);
+        let citizen = Uuid::new_v4();
+        let business = Uuid::new_v4();
+        let probe = Array1::from_elem(20, 0.5);
+
+        let business_before = engine.get_agent_q_values(business, "business", &probe).await.unwrap();
+        let citizen_before = engine.get_agent_q_values(citizen, "citizen", &probe).await.unwrap();
+
+        for i in 0..8 {
+            let experience = Experience {
+                state: Array1::from_elem(20, i as f64 * 0.1),
+                action: i % 4,
+                reward: 5.0,
+                next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                done: false,
+            };
+            engine.submit_agent_experience(citizen, "citizen", 50.0, experience).await.unwrap();
+        }
+        engine.process_experiences().await.unwrap();
+
+        // Another agent of the same type shares the citizen network
+        let citizen_after = engine.get_agent_q_values(Uuid::new_v4(), "citizen", &probe).await.unwrap();
+        assert_ne!(citizen_after, citizen_before);
+        assert_eq!(engine.get_agent_q_values(business, "business", &probe).await.unwrap(), business_before);
+        assert_eq!(engine.get_buffer_stats().await.count, 0);
+
+        let state = engine.export_state().await;
+        assert_eq!(state.networks["citizen"].get_memory_size(), 8);
+        assert_eq!(state.networks["business"].get_memory_size(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_normalized_rewards_become_standardized() {
+        let experience = |reward: f64| Experience {
+            state: Array1::zeros(20),
+            action: 0,
+            reward,
+            next_state: Array1::zeros(20),
+            done: false,
+        };
+        // Mostly small rewards with rare large payouts
+        let skewed = |i: usize| if i.is_multiple_of(10) { 5000.0 } else { 10.0 + (i % 7) as f64 };
+
+        let engine = LearningEngine::new(AIConfig {
+            normalize_rewards: true,
+            ..AIConfig::default()
+        });
+        for i in 0..4000 {
+            engine.submit_experience(experience(skewed(i))).await.unwrap();
+        }
+
+        let state = engine.export_state().await;
+        let recent: Vec<f64> = state.pending_experiences[2000..].iter().map(|e| e.reward).collect();
+        let mean = recent.iter().sum::<f64>() / recent.len() as f64;
+        let variance = recent.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / recent.len() as f64;
+        assert!(mean.abs() < 0.05, "mean {}", mean);
+        assert!((variance - 1.0).abs() < 0.05, "variance {}", variance);
+        assert_eq!(state.reward_normalizer.count(), 4000);
+        // The learning curve still follows raw rewards
+        assert!(engine.get_smoothed_reward().await.unwrap() > 100.0);
+
+        // Statistics survive an export and import
+        let restored = LearningEngine::new(AIConfig {
+            normalize_rewards: true,
+            ..AIConfig::default()
+        });
+        restored.import_state(state.clone()).await;
+        restored.submit_experience(experience(5000.0)).await.unwrap();
+        engine.submit_experience(experience(5000.0)).await.unwrap();
+        let last = |state: LearningState| state.pending_experiences.last().unwrap().reward;
+        assert_eq!(last(restored.export_state().await), last(engine.export_state().await));
+
+        // The reward log keeps the submitted reward next to the standardized one
+        let breakdown = RewardBreakdown::default();
+        engine.submit_experience_with_breakdown(experience(5000.0), breakdown.clone()).await.unwrap();
+        let entry = engine.get_reward_log().await.pop().unwrap();
+        assert_eq!(entry.reward, 5000.0);
+        assert_eq!(entry.stored_reward, last(engine.export_state().await));
+        assert!(entry.stored_reward < 10.0);
+
+        // Without the flag rewards are stored as is
+        let raw = LearningEngine::new(AIConfig::default());
+        raw.submit_experience(experience(5000.0)).await.unwrap();
+        assert_eq!(last(raw.export_state().await), 5000.0);
+        raw.submit_experience_with_breakdown(experience(5000.0), breakdown).await.unwrap();
+        let entry = raw.get_reward_log().await.pop().unwrap();
+        assert_eq!((entry.reward, entry.stored_reward), (5000.0, 5000.0));
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_experiences_are_rejected_on_submit() {
+        let config = AIConfig {
+            batch_size: 4,
+            ..AIConfig::default()
+        };
+        let engine = LearningEngine::new(config);
+        let experience = |size: usize| Experience {
+            state: Array1::zeros(size),
+            action: 0,
+            reward: 1.0,
+            next_state: Array1::zeros(size),
+            done: false,
+        };
+
+        for i in 0..6 {
+            let result = engine.submit_experience(experience(if i == 2 { 3 } else { 20 })).await;
+            assert_eq!(result.is_err(), i == 2);
+        }
+        assert!(engine.submit_agent_experience(Uuid::new_v4(), "citizen", 50.0, experience(3)).await.is_err());
+
+        // The valid experiences around the rejected one are all stored
+        engine.process_experiences().await.unwrap();
+        assert_eq!(engine.get_buffer_stats().await.count, 5);
+        assert_eq!(engine.get_train_steps().await, 1);
+    }
+}
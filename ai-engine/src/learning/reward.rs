@@ -0,0 +1,308 @@
+//! Reward shaping for smart city agents
+//! Each component scores one aspect of an agent's situation; the calculator
+//! combines them into the scalar reward stored in experiences
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::environment::Environment;
+
+/// Weights and parameters of the reward components
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewardConfig {
+    /// Weight of the congestion component (0 disables it)
+    pub congestion_weight: f64,
+    /// Radius around an agent used to measure local congestion
+    pub congestion_radius: f64,
+    /// Weight of the goal distance shaping component (0 disables it)
+    #[serde(default)]
+    pub goal_weight: f64,
+    /// Discount used by goal shaping; must match the learner's gamma to keep the optimal policy
+    #[serde(default = "default_shaping_discount")]
+    pub shaping_discount: f64,
+    /// Weight of the business profit component (0 disables it)
+    #[serde(default)]
+    pub profit_weight: f64,
+}
+
+fn default_shaping_discount() -> f64 {
+    0.95
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            congestion_weight: 1.0,
+            congestion_radius: 25.0,
+            goal_weight: 0.0,
+            shaping_discount: default_shaping_discount(),
+            profit_weight: 0.0,
+        }
+    }
+}
+
+/// Name of the congestion component in a `RewardBreakdown`
+pub const CONGESTION_COMPONENT: &str = "congestion";
+/// Name of the goal shaping component in a `RewardBreakdown`
+pub const GOAL_COMPONENT: &str = "goal";
+/// Name of the profit component in a `RewardBreakdown`
+pub const PROFIT_COMPONENT: &str = "profit";
+
+/// One reward component before weighting
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardComponent {
+    pub name: String,
+    pub value: f64,
+    pub weight: f64,
+}
+
+/// Components behind a scalar reward, for finding out which one dominates
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RewardBreakdown {
+    pub components: Vec<RewardComponent>,
+}
+
+impl RewardBreakdown {
+    fn push(&mut self, name: &str, value: f64, weight: f64) {
+        self.components.push(RewardComponent {
+            name: name.to_string(),
+            value,
+            weight,
+        });
+    }
+
+    /// Component with the given name, if it contributed to the reward
+    pub fn component(&self, name: &str) -> Option<&RewardComponent> {
+        self.components.iter().find(|component| component.name == name)
+    }
+
+    /// Weighted sum of the components: the scalar reward
+    pub fn total(&self) -> f64 {
+        self.components
+            .iter()
+            .map(|component| component.weight * component.value)
+            .sum()
+    }
+}
+
+/// Computes shaped rewards from the environment
+#[derive(Debug, Clone)]
+pub struct RewardCalculator {
+    config: RewardConfig,
+}
+
+impl RewardCalculator {
+    pub fn new(config: RewardConfig) -> Self {
+        Self { config }
+    }
+
+    /// Reward in (0, 1] that decreases with the number of agents around `agent_id`
+    ///
+    /// An isolated agent gets 1.0, encouraging agents to spread out.
+    pub fn congestion_reward(&self, agent_id: Uuid, environment: &Environment) -> f64 {
+        let Some(position) = environment.get_agent_position(agent_id) else {
+            return 0.0;
+        };
+
+        let neighbors = environment.count_agents_near(position, self.config.congestion_radius, Some(agent_id));
+        1.0 / (1.0 + neighbors as f64)
+    }
+
+    /// Potential of a position with respect to a goal: the negative distance to it
+    pub fn goal_potential(&self, position: (f64, f64), goal: (f64, f64)) -> f64 {
+        -((goal.0 - position.0).powi(2) + (goal.1 - position.1).powi(2)).sqrt()
+    }
+
+    /// Potential-based shaping reward for moving from `previous` to `current`
+    ///
+    /// Computed as `discount * potential(current) - potential(previous)`, positive when
+    /// the agent gets closer to the goal. Shaping of this form adds the same amount to
+    /// every policy's return from a given state, so it speeds up learning without
+    /// changing which policy is optimal (Ng et al., 1999).
+    pub fn goal_shaping_reward(&self, previous: (f64, f64), current: (f64, f64), goal: (f64, f64)) -> f64 {
+        self.config.shaping_discount * self.goal_potential(current, goal) - self.goal_potential(previous, goal)
+    }
+
+    /// Profit of a business over one step: revenue minus costs, negative when it loses money
+    pub fn profit_reward(&self, revenue: f64, costs: f64) -> f64 {
+        revenue - costs
+    }
+
+    /// Weighted sum of all reward components for an agent
+    pub fn compute(&self, agent_id: Uuid, environment: &Environment) -> f64 {
+        self.breakdown(agent_id, environment).total()
+    }
+
+    /// Components behind `compute`
+    pub fn breakdown(&self, agent_id: Uuid, environment: &Environment) -> RewardBreakdown {
+        let mut breakdown = RewardBreakdown::default();
+        breakdown.push(
+            CONGESTION_COMPONENT,
+            self.congestion_reward(agent_id, environment),
+            self.config.congestion_weight,
+        );
+        breakdown
+    }
+
+    /// Weighted sum of all reward components, including shaping toward `goal`
+    /// for an agent that moved from `previous` to its current position
+    pub fn compute_with_goal(
+        &self,
+        agent_id: Uuid,
+        environment: &Environment,
+        previous: (f64, f64),
+        goal: (f64, f64),
+    ) -> f64 {
+        self.breakdown_with_goal(agent_id, environment, previous, goal).total()
+    }
+
+    /// Components behind `compute_with_goal`
+    pub fn breakdown_with_goal(
+        &self,
+        agent_id: Uuid,
+        environment: &Environment,
+        previous: (f64, f64),
+        goal: (f64, f64),
+    ) -> RewardBreakdown {
+        let shaping = match environment.get_agent_position(agent_id) {
+            Some(current) => self.goal_shaping_reward(previous, current, goal),
+            None => 0.0,
+        };
+        let mut breakdown = self.breakdown(agent_id, environment);
+        breakdown.push(GOAL_COMPONENT, shaping, self.config.goal_weight);
+        breakdown
+    }
+
+    /// Weighted sum of all reward components, including the profit a business made this step
+    pub fn compute_with_profit(&self, agent_id: Uuid, environment: &Environment, revenue: f64, costs: f64) -> f64 {
+        self.breakdown_with_profit(agent_id, environment, revenue, costs).total()
+    }
+
+    /// Components behind `compute_with_profit`
+    pub fn breakdown_with_profit(
+        &self,
+        agent_id: Uuid,
+        environment: &Environment,
+        revenue: f64,
+        costs: f64,
+    ) -> RewardBreakdown {
+        let mut breakdown = self.breakdown(agent_id, environment);
+        breakdown.push(PROFIT_COMPONENT, self.profit_reward(revenue, costs), self.config.profit_weight);
+        breakdown
+    }
+
+    /// Components of an agent's reward for one simulation step: congestion, shaping toward
+    /// `goal` since `previous` and, for a business reporting `(revenue, costs)`, its profit
+    pub fn breakdown_step(
+        &self,
+        agent_id: Uuid,
+        environment: &Environment,
+        previous: (f64, f64),
+        goal: (f64, f64),
+        profit: Option<(f64, f64)>,
+    ) -> RewardBreakdown {
+        let mut breakdown = self.breakdown_with_goal(agent_id, environment, previous, goal);
+        if let Some((revenue, costs)) = profit {
+            breakdown.push(PROFIT_COMPONENT, self.profit_reward(revenue, costs), self.config.profit_weight);
+        }
+        breakdown
+    }
+
+    pub fn config(&self) -> &RewardConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::learning::dqn::Experience;
+    use ndarray::Array1;
+
+    #[test]
+    fn test_congestion_reward_prefers_empty_areas() {
+        let mut environment = Environment::new();
+        let isolated = Uuid::new_v4();
+        environment.register_agent(isolated, (900.0, 900.0));
+
+        let crowded = Uuid::new_v4();
+        environment.register_agent(crowded, (100.0, 100.0));
+        for i in 0..5 {
+            environment.register_agent(Uuid::new_v4(), (100.0 + 2.0 * i as f64, 105.0));
+        }
+
+        let calculator = RewardCalculator::new(RewardConfig::default());
+        let isolated_reward = calculator.congestion_reward(isolated, &environment);
+        let crowded_reward = calculator.congestion_reward(crowded, &environment);
+
+        assert_eq!(isolated_reward, 1.0);
+        assert!((crowded_reward - 1.0 / 6.0).abs() < 1e-12);
+        assert!(isolated_reward > crowded_reward);
+    }
+
+    #[test]
+    fn test_goal_shaping_increases_toward_goal() {
+        let calculator = RewardCalculator::new(RewardConfig::default());
+        let goal = (100.0, 50.0);
+        let path: Vec<(f64, f64)> = (0..=10).map(|i| (10.0 * i as f64, 5.0 * i as f64)).collect();
+
+        let potentials: Vec<f64> = path.iter().map(|&p| calculator.goal_potential(p, goal)).collect();
+        assert!(potentials.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(potentials[10], 0.0);
+
+        for step in path.windows(2) {
+            assert!(calculator.goal_shaping_reward(step[0], step[1], goal) > 0.0);
+            assert!(calculator.goal_shaping_reward(step[1], step[0], goal) < 0.0);
+        }
+
+        // Undiscounted shaping telescopes, so every path between two points earns the same total
+        let undiscounted = RewardCalculator::new(RewardConfig {
+            shaping_discount: 1.0,
+            ..RewardConfig::default()
+        });
+        let detour = [(0.0, 0.0), (0.0, 80.0), (60.0, 90.0), (100.0, 50.0)];
+        let total = |points: &[(f64, f64)]| -> f64 {
+            points.windows(2).map(|w| undiscounted.goal_shaping_reward(w[0], w[1], goal)).sum()
+        };
+        assert!((total(&path) - total(&detour)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_profit_reward_follows_profit_sign() {
+        let mut environment = Environment::new();
+        let (profitable, losing) = (Uuid::new_v4(), Uuid::new_v4());
+        environment.register_agent(profitable, (100.0, 100.0));
+        environment.register_agent(losing, (800.0, 800.0));
+
+        let calculator = RewardCalculator::new(RewardConfig {
+            congestion_weight: 0.0,
+            profit_weight: 0.5,
+            ..RewardConfig::default()
+        });
+        // (revenue, costs) per step
+        let step = |agent_id: Uuid, (revenue, costs): (f64, f64)| Experience {
+            state: Array1::zeros(4),
+            action: 0,
+            reward: calculator.compute_with_profit(agent_id, &environment, revenue, costs),
+            next_state: Array1::zeros(4),
+            done: false,
+        };
+
+        let profitable_steps: Vec<Experience> =
+            [(120.0, 80.0), (90.0, 60.0), (50.0, 49.0)].into_iter().map(|s| step(profitable, s)).collect();
+        let losing_steps: Vec<Experience> =
+            [(40.0, 80.0), (0.0, 10.0), (60.0, 61.0)].into_iter().map(|s| step(losing, s)).collect();
+
+        assert!(profitable_steps.iter().all(|e| e.reward > 0.0));
+        assert!(losing_steps.iter().all(|e| e.reward < 0.0));
+        assert_eq!(profitable_steps[0].reward, 20.0);
+        assert_eq!(losing_steps[0].reward, -20.0);
+
+        // Disabled by default
+        let default_calculator = RewardCalculator::new(RewardConfig::default());
+        assert_eq!(
+            default_calculator.compute_with_profit(losing, &environment, 0.0, 1000.0),
+            default_calculator.compute(losing, &environment)
+        );
+    }
+}
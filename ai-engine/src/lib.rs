@@ -2,6 +2,7 @@
 //! Versão 1.1 - Algoritmos de alta performance
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
@@ -15,12 +16,20 @@ pub mod environment;
 pub mod learning;
 pub mod optimization;
 pub mod communication;
+pub mod observer;
+pub mod stats;
+#[cfg(feature = "profiling")]
+pub mod profiling;
 
 use agent::Agent;
 use environment::Environment;
-use learning::LearningEngine;
+use learning::{LearningEngine, LearningState, NetworkSharing};
+use learning::dqn::DQNConfig;
+use learning::reward::RewardConfig;
 use optimization::OptimizationEngine;
-use communication::CommunicationHub;
+use communication::{CommunicationHub, Message};
+use observer::SimulationObserver;
+use stats::Distribution;
 
 /// Configuração principal do sistema de IA
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +41,20 @@ pub struct AIConfig {
     pub batch_size: usize,
     pub update_frequency: u64,
     pub optimization_threshold: f64,
+    #[serde(default)]
+    pub reward: RewardConfig,
+    /// Piso de exploração (`epsilon_end`) por tipo de agente, ex.: governos exploram menos
+    #[serde(default)]
+    pub epsilon_end_by_type: HashMap<String, f64>,
+    /// Semente do gerador usado nas decisões dos agentes; `None` usa entropia do sistema
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Se os agentes compartilham uma rede, uma por tipo ou uma por agente
+    #[serde(default)]
+    pub network_sharing: NetworkSharing,
+    /// Padroniza as recompensas (média zero, variância unitária) antes de armazená-las
+    #[serde(default)]
+    pub normalize_rewards: bool,
 }
 
 impl Default for AIConfig {
@@ -44,10 +67,134 @@ impl Default for AIConfig {
             batch_size: 32,
             update_frequency: 100,
             optimization_threshold: 0.8,
+            reward: RewardConfig::default(),
+            epsilon_end_by_type: HashMap::new(),
+            seed: None,
+            network_sharing: NetworkSharing::default(),
+            normalize_rewards: false,
         }
     }
 }
 
+impl AIConfig {
+    /// Inicia um builder a partir da configuração padrão
+    pub fn builder() -> AIConfigBuilder {
+        AIConfigBuilder::default()
+    }
+
+    /// Verifica se os tamanhos são positivos, as taxas estão em [0, 1] e os pisos por tipo em [0, epsilon_start]
+    pub fn validate(&self) -> Result<()> {
+        let sizes = [
+            ("max_agents", self.max_agents as u64),
+            ("memory_size", self.memory_size as u64),
+            ("batch_size", self.batch_size as u64),
+            ("update_frequency", self.update_frequency),
+        ];
+        for (name, size) in sizes {
+            anyhow::ensure!(size > 0, "{} deve ser positivo", name);
+        }
+
+        let rates = [
+            ("learning_rate", self.learning_rate),
+            ("exploration_rate", self.exploration_rate),
+            ("optimization_threshold", self.optimization_threshold),
+        ];
+        for (name, rate) in rates {
+            anyhow::ensure!((0.0..=1.0).contains(&rate), "{} deve estar em [0, 1], recebido {}", name, rate);
+        }
+        anyhow::ensure!(self.learning_rate > 0.0, "learning_rate deve ser positivo");
+        anyhow::ensure!(
+            self.batch_size <= self.memory_size,
+            "batch_size ({}) não pode exceder memory_size ({})",
+            self.batch_size,
+            self.memory_size
+        );
+        // Cada piso passa pela mesma validação do `epsilon_end` global das redes,
+        // que o limita a [0, epsilon_start]
+        for (agent_type, &epsilon_end) in &self.epsilon_end_by_type {
+            DQNConfig { epsilon_end, ..DQNConfig::default() }
+                .validate()
+                .map_err(|e| anyhow::anyhow!("epsilon_end de {} inválido: {}", agent_type, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Builder de `AIConfig` que valida o resultado
+#[derive(Debug, Clone, Default)]
+pub struct AIConfigBuilder {
+    config: AIConfig,
+}
+
+impl AIConfigBuilder {
+    pub fn max_agents(mut self, max_agents: usize) -> Self {
+        self.config.max_agents = max_agents;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: f64) -> Self {
+        self.config.learning_rate = learning_rate;
+        self
+    }
+
+    pub fn exploration_rate(mut self, exploration_rate: f64) -> Self {
+        self.config.exploration_rate = exploration_rate;
+        self
+    }
+
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.config.memory_size = memory_size;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.config.batch_size = batch_size;
+        self
+    }
+
+    pub fn update_frequency(mut self, update_frequency: u64) -> Self {
+        self.config.update_frequency = update_frequency;
+        self
+    }
+
+    pub fn optimization_threshold(mut self, threshold: f64) -> Self {
+        self.config.optimization_threshold = threshold;
+        self
+    }
+
+    pub fn reward(mut self, reward: RewardConfig) -> Self {
+        self.config.reward = reward;
+        self
+    }
+
+    /// Piso de exploração de um tipo de agente
+    pub fn epsilon_end_for(mut self, agent_type: impl Into<String>, epsilon_end: f64) -> Self {
+        self.config.epsilon_end_by_type.insert(agent_type.into(), epsilon_end);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn network_sharing(mut self, network_sharing: NetworkSharing) -> Self {
+        self.config.network_sharing = network_sharing;
+        self
+    }
+
+    pub fn normalize_rewards(mut self, normalize_rewards: bool) -> Self {
+        self.config.normalize_rewards = normalize_rewards;
+        self
+    }
+
+    /// Valida e retorna a configuração
+    pub fn build(self) -> Result<AIConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
 /// Estado de um agente
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AgentState {
@@ -102,6 +249,7 @@ pub struct AISystem {
     learning_engine: Arc<LearningEngine>,
     optimization_engine: Arc<OptimizationEngine>,
     communication_hub: Arc<CommunicationHub>,
+    observers: RwLock<Vec<Arc<dyn SimulationObserver + Send + Sync>>>,
     running: Arc<RwLock<bool>>,
 }
 
@@ -122,6 +270,7 @@ impl AISystem {
             learning_engine,
             optimization_engine,
             communication_hub,
+            observers: RwLock::new(Vec::new()),
             running,
         }
     }
@@ -144,10 +293,13 @@ impl AISystem {
 
     /// Adiciona um novo agente ao sistema
     pub async fn add_agent(&self, agent_type: String, initial_state: AgentState) -> Result<Uuid> {
+        let position = initial_state.position;
         let agent = Agent::new(agent_type, initial_state, self.config.clone());
         let agent_id = agent.get_id();
         
         self.agents.write().await.insert(agent_id, agent);
+        self.environment.write().await.register_agent(agent_id, position);
+        self.communication_hub.register_agent(agent_id).await;
         
         info!("Agente {} adicionado ao sistema", agent_id);
         Ok(agent_id)
@@ -156,35 +308,110 @@ impl AISystem {
     /// Remove um agente do sistema
     pub async fn remove_agent(&self, agent_id: Uuid) -> Result<()> {
         if self.agents.write().await.remove(&agent_id).is_some() {
+            self.environment.write().await.unregister_agent(agent_id);
+            self.communication_hub.unregister_agent(agent_id).await;
+            self.learning_engine.remove_agent(agent_id).await;
             info!("Agente {} removido do sistema", agent_id);
         }
         Ok(())
     }
 
+    /// Registra um observador notificado em cada ciclo de simulação
+    pub async fn register_observer(&self, observer: Arc<dyn SimulationObserver + Send + Sync>) {
+        self.observers.write().await.push(observer);
+    }
+
     /// Executa um ciclo de simulação
+    ///
+    /// Os agentes são processados em ordem crescente de id, então ciclos com a mesma
+    /// semente (`AIConfig::seed`) produzem os mesmos resultados. Observadores registrados
+    /// são notificados no início do ciclo, a cada ação e ao final.
     pub async fn run_simulation_cycle(&self) -> Result<()> {
+        let observers = self.observers.read().await.clone();
+        if observers.is_empty() {
+            return self.execute_cycle(&observers).await;
+        }
+
+        let cycle = self.environment.read().await.tick;
+        let stats = self.get_system_stats().await?;
+        for observer in &observers {
+            observer.on_cycle_start(cycle, &stats);
+        }
+
+        self.execute_cycle(&observers).await?;
+
+        let stats = self.get_system_stats().await?;
+        for observer in &observers {
+            observer.on_cycle_end(cycle, &stats);
+        }
+        Ok(())
+    }
+
+    /// Decide e executa as ações dos agentes, avança o ambiente e treina as redes
+    ///
+    /// Cada agente observa o ambiente e age pela rede que o `AIConfig::network_sharing`
+    /// lhe atribui; a transição resultante volta para essa mesma rede como experiência.
+    /// A recompensa soma os componentes de `AIConfig::reward`: congestionamento, aproximação
+    /// do centro da cidade e, para empresas, o lucro do passo.
+    async fn execute_cycle(&self, observers: &[Arc<dyn SimulationObserver + Send + Sync>]) -> Result<()> {
         let agents = self.agents.read().await;
         let mut environment = self.environment.write().await;
         
+        let mut agent_ids: Vec<Uuid> = agents.keys().copied().collect();
+        agent_ids.sort_unstable();
+        
         // Coletar ações de todos os agentes
-        let mut actions = Vec::new();
-        for (agent_id, agent) in agents.iter() {
-            if let Ok(action) = agent.decide_action(&environment).await {
-                actions.push((*agent_id, action));
-            }
+        let mut decisions = Vec::new();
+        for agent_id in agent_ids {
+            let agent = &agents[&agent_id];
+            let state = agent.observe(&environment);
+            let index = self.learning_engine.select_agent_action(agent_id, agent.get_type(), &state).await?;
+            let position = environment.get_agent_position(agent_id).unwrap_or(agent.get_state().position);
+            decisions.push((agent_id, state, index, position, agent.action_for(index, &environment)));
         }
         
         // Executar ações no ambiente
-        for (agent_id, action) in actions {
+        let mut transitions = Vec::new();
+        for (agent_id, state, index, position, action) in decisions {
+            for observer in observers {
+                observer.on_action(agent_id, &action);
+            }
+
+            // Mensagens diretas são entregues pelo hub de comunicação
+            if let Action::Communicate { target_id, message } = &action {
+                let payload = serde_json::Value::String(message.clone());
+                self.communication_hub.send(*target_id, Message::new(agent_id, payload)).await;
+            }
+
             if let Err(e) = environment.execute_action(agent_id, action).await {
                 error!("Erro ao executar ação do agente {}: {}", agent_id, e);
             }
+            transitions.push((agent_id, state, index, position));
         }
         
         // Atualizar estado do ambiente
         environment.update().await?;
         
-        // Processar aprendizado
+        // Devolver as transições às redes que escolheram as ações e processar aprendizado
+        let goal = environment.get_center();
+        for (agent_id, state, action, previous) in transitions {
+            let agent = &agents[&agent_id];
+            let breakdown = self
+                .learning_engine
+                .rewards()
+                .breakdown_step(agent_id, &environment, previous, goal, agent.profit());
+            let experience = learning::dqn::Experience {
+                state,
+                action,
+                reward: breakdown.total(),
+                next_state: agent.observe(&environment),
+                done: false,
+            };
+            let energy = agent.get_state().energy;
+            self.learning_engine
+                .submit_agent_experience_with_breakdown(agent_id, agent.get_type(), energy, experience, breakdown)
+                .await?;
+        }
         self.learning_engine.process_experiences().await?;
         
         // Otimizar sistema se necessário
@@ -195,7 +422,18 @@ impl AISystem {
         Ok(())
     }
 
-    /// Inicia o loop principal de simulação
+    /// Executa exatamente `n` ciclos sem pausas, interrompendo no primeiro erro
+    ///
+    /// Ao contrário de `start_simulation`, não depende do relógio, o que torna execuções
+    /// com a mesma semente reproduzíveis.
+    pub async fn run_n_cycles(&self, n: usize) -> Result<()> {
+        for _ in 0..n {
+            self.run_simulation_cycle().await?;
+        }
+        Ok(())
+    }
+
+    /// Inicia o loop principal de simulação em tempo real, para uso interativo
     pub async fn start_simulation(&self) -> Result<()> {
         *self.running.write().await = true;
         info!("Iniciando simulação de IA...");
@@ -252,27 +490,89 @@ impl AISystem {
         let agents = self.agents.read().await;
         let environment = self.environment.read().await;
         
-        let total_agents = agents.len();
-        let total_reward: f64 = agents.values()
-            .map(|agent| agent.get_performance_metrics().total_reward)
-            .sum();
+        let all_metrics: Vec<&PerformanceMetrics> = agents.values()
+            .map(|agent| agent.get_performance_metrics())
+            .collect();
+        let overall = AgentGroupStats::from_metrics(&all_metrics);
         
-        let average_efficiency: f64 = if total_agents > 0 {
-            agents.values()
-                .map(|agent| agent.get_performance_metrics().efficiency)
-                .sum::<f64>() / total_agents as f64
-        } else {
-            0.0
-        };
+        let mut metrics_by_type: HashMap<String, Vec<&PerformanceMetrics>> = HashMap::new();
+        for agent in agents.values() {
+            metrics_by_type
+                .entry(agent.get_type().to_string())
+                .or_default()
+                .push(agent.get_performance_metrics());
+        }
+        let by_type = metrics_by_type
+            .into_iter()
+            .map(|(agent_type, metrics)| (agent_type, AgentGroupStats::from_metrics(&metrics)))
+            .collect();
         
         Ok(SystemStats {
-            total_agents,
-            total_reward,
-            average_efficiency,
+            total_agents: overall.agents,
+            total_reward: overall.total_reward,
+            average_efficiency: overall.average_efficiency,
+            reward_distribution: overall.reward_distribution,
+            efficiency_distribution: overall.efficiency_distribution,
+            by_type,
             environment_state: environment.get_state().await?,
             running: *self.running.read().await,
         })
     }
+
+    /// Salva agentes, ambiente e estado de aprendizado (replay e epsilon) em um único arquivo
+    pub async fn save_checkpoint(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let snapshot = SystemSnapshot {
+            agents: self.agents.read().await.values().cloned().collect(),
+            environment: self.environment.read().await.clone(),
+            learning: self.learning_engine.export_state().await,
+        };
+
+        std::fs::write(path, serde_json::to_string(&snapshot)?)?;
+        info!("Checkpoint do sistema de IA salvo em {}", path.display());
+        Ok(())
+    }
+
+    /// Restaura um sistema salvo com `save_checkpoint`
+    ///
+    /// O sistema restaurado começa parado; a simulação continua com `start_simulation`.
+    pub async fn load_checkpoint(path: impl AsRef<Path>, config: AIConfig) -> Result<Self> {
+        let path = path.as_ref();
+        let snapshot: SystemSnapshot = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let system = Self::new(config);
+
+        for agent in &snapshot.agents {
+            system.communication_hub.register_agent(agent.get_id()).await;
+        }
+        *system.agents.write().await = snapshot
+            .agents
+            .into_iter()
+            .map(|agent| (agent.get_id(), agent))
+            .collect();
+        *system.environment.write().await = snapshot.environment;
+        system.learning_engine.import_state(snapshot.learning).await;
+
+        info!("Checkpoint do sistema de IA carregado de {}", path.display());
+        Ok(system)
+    }
+
+    /// Salva agentes, ambiente e estado de aprendizado em arquivo; o mesmo que `save_checkpoint`
+    pub async fn save(&self, path: &str) -> Result<()> {
+        self.save_checkpoint(path).await
+    }
+
+    /// Carrega um sistema salvo com `save`; o mesmo que `load_checkpoint`
+    pub async fn load(path: &str, config: AIConfig) -> Result<Self> {
+        Self::load_checkpoint(path, config).await
+    }
+}
+
+/// Estado persistido do sistema de IA
+#[derive(Serialize, Deserialize)]
+struct SystemSnapshot {
+    agents: Vec<Agent>,
+    environment: Environment,
+    learning: LearningState,
 }
 
 /// Estatísticas do sistema
@@ -281,13 +581,75 @@ pub struct SystemStats {
     pub total_agents: usize,
     pub total_reward: f64,
     pub average_efficiency: f64,
+    /// Distribuição de `total_reward` entre os agentes
+    #[serde(default)]
+    pub reward_distribution: Distribution,
+    /// Distribuição de `efficiency` entre os agentes
+    #[serde(default)]
+    pub efficiency_distribution: Distribution,
+    /// As mesmas estatísticas separadas por tipo de agente
+    #[serde(default)]
+    pub by_type: HashMap<String, AgentGroupStats>,
     pub environment_state: serde_json::Value,
     pub running: bool,
 }
 
+/// Estatísticas de desempenho de um grupo de agentes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentGroupStats {
+    pub agents: usize,
+    pub total_reward: f64,
+    pub average_efficiency: f64,
+    pub reward_distribution: Distribution,
+    pub efficiency_distribution: Distribution,
+}
+
+impl AgentGroupStats {
+    /// Resume as métricas dos agentes do grupo
+    pub fn from_metrics(metrics: &[&PerformanceMetrics]) -> Self {
+        let agents = metrics.len();
+        let total_reward = metrics.iter().map(|m| m.total_reward).sum();
+        let average_efficiency = if agents > 0 {
+            metrics.iter().map(|m| m.efficiency).sum::<f64>() / agents as f64
+        } else {
+            0.0
+        };
+
+        Self {
+            agents,
+            total_reward,
+            average_efficiency,
+            reward_distribution: Distribution::from_values(metrics.iter().map(|m| m.total_reward)),
+            efficiency_distribution: Distribution::from_values(metrics.iter().map(|m| m.efficiency)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    #[test]
+    fn test_config_builder_rejects_invalid_values() {
+        let config = AIConfig::builder()
+            .max_agents(50)
+            .exploration_rate(0.2)
+            .epsilon_end_for("government", 0.02)
+            .seed(7)
+            .build()
+            .unwrap();
+        assert_eq!(config.max_agents, 50);
+        assert_eq!(config.epsilon_end_by_type["government"], 0.02);
+        assert_eq!(config.seed, Some(7));
+
+        assert!(AIConfig::builder().exploration_rate(1.2).build().is_err());
+        assert!(AIConfig::builder().max_agents(0).build().is_err());
+        assert!(AIConfig::builder().epsilon_end_for("citizen", -0.1).build().is_err());
+        assert!(AIConfig::builder().epsilon_end_for("citizen", 1.5).build().is_err());
+        assert!(AIConfig::builder().epsilon_end_for("citizen", f64::NAN).build().is_err());
+        assert!(AIConfig::builder().batch_size(64).memory_size(10).build().is_err());
+    }
 
     #[tokio::test]
     async fn test_ai_system_creation() {
@@ -323,4 +685,316 @@ mod tests {
         let agent_id = ai_system.add_agent("citizen".to_string(), initial_state).await.unwrap();
         assert!(ai_system.agents.read().await.contains_key(&agent_id));
     }
+
+    fn sample_state(position: (f64, f64), energy: f64) -> AgentState {
+        AgentState {
+            id: Uuid::new_v4(),
+            agent_type: "citizen".to_string(),
+            position,
+            energy,
+            resources: HashMap::new(),
+            goals: vec!["survive".to_string()],
+            memory: Vec::new(),
+            performance_metrics: PerformanceMetrics {
+                total_reward: 0.0,
+                average_reward: 0.0,
+                success_rate: 0.0,
+                efficiency: 0.0,
+                collaboration_score: 0.0,
+                energy_efficiency: 0.0,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load() {
+        let config = AIConfig::default();
+        let ai_system = AISystem::new(config.clone());
+        ai_system.initialize().await.unwrap();
+
+        let mut agent_ids = Vec::new();
+        for i in 0..3 {
+            let state = sample_state((100.0 * i as f64, 50.0), 20.0 + 30.0 * i as f64);
+            agent_ids.push(ai_system.add_agent("citizen".to_string(), state).await.unwrap());
+        }
+        for _ in 0..5 {
+            ai_system.run_simulation_cycle().await.unwrap();
+        }
+
+        let path = std::env::temp_dir().join(format!("ai_system_{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        ai_system.save(path).await.unwrap();
+        let restored = AISystem::load(path, config).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(restored.agents.read().await.len(), 3);
+
+        let sampled = agent_ids[1];
+        let original_agents = ai_system.agents.read().await;
+        let restored_agents = restored.agents.read().await;
+        let original = original_agents[&sampled].get_state();
+        let loaded = restored_agents[&sampled].get_state();
+        assert_eq!(original.position, loaded.position);
+        assert_eq!(original.energy, loaded.energy);
+        assert_eq!(
+            ai_system.environment.read().await.get_agent_position(sampled),
+            restored.environment.read().await.get_agent_position(sampled)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip() {
+        let config = AIConfig::default();
+        let ai_system = AISystem::new(config.clone());
+        ai_system.initialize().await.unwrap();
+
+        let mut agent_ids = Vec::new();
+        for i in 0..3 {
+            let mut state = sample_state((100.0 * i as f64, 50.0), 20.0 + 30.0 * i as f64);
+            state.performance_metrics.total_reward = 1.5 * i as f64;
+            agent_ids.push(ai_system.add_agent("citizen".to_string(), state).await.unwrap());
+        }
+        let input_size = learning::dqn::DQNConfig::default().input_size;
+        for i in 0..40 {
+            ai_system.learning_engine.submit_experience(learning::dqn::Experience {
+                state: ndarray::Array1::from_elem(input_size, i as f64 / 40.0),
+                action: i % 4,
+                reward: 1.0,
+                next_state: ndarray::Array1::from_elem(input_size, (i + 1) as f64 / 40.0),
+                done: false,
+            }).await.unwrap();
+        }
+        for _ in 0..5 {
+            ai_system.run_simulation_cycle().await.unwrap();
+        }
+        *ai_system.running.write().await = true;
+
+        let path = std::env::temp_dir().join(format!("ai_system_{}.json", Uuid::new_v4()));
+        ai_system.save_checkpoint(&path).await.unwrap();
+        let restored = AISystem::load_checkpoint(&path, config).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!*restored.running.read().await);
+        assert_eq!(restored.agents.read().await.len(), 3);
+
+        let original_stats = ai_system.get_system_stats().await.unwrap();
+        let restored_stats = restored.get_system_stats().await.unwrap();
+        assert_eq!(original_stats.total_reward, restored_stats.total_reward);
+        assert_eq!(original_stats.environment_state, restored_stats.environment_state);
+
+        let original_learning = ai_system.learning_engine.export_state().await;
+        let restored_learning = restored.learning_engine.export_state().await;
+        assert_eq!(original_learning.train_steps, restored_learning.train_steps);
+        // 40 submitted experiences plus one per agent and cycle
+        assert_eq!(original_learning.dqn.get_memory_size(), 55);
+        assert_eq!(original_learning.dqn.get_memory_size(), restored_learning.dqn.get_memory_size());
+        assert_eq!(original_learning.dqn.get_epsilon(), restored_learning.dqn.get_epsilon());
+
+        let sampled = agent_ids[1];
+        let original_agents = ai_system.agents.read().await;
+        let restored_agents = restored.agents.read().await;
+        let original = original_agents[&sampled].get_state();
+        let loaded = restored_agents[&sampled].get_state();
+        assert_eq!(original.position, loaded.position);
+        assert_eq!(original.energy, loaded.energy);
+        assert_eq!(
+            ai_system.environment.read().await.get_agent_position(sampled),
+            restored.environment.read().await.get_agent_position(sampled)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_n_cycles_is_deterministic() {
+        async fn seeded_run(seed: u64) -> (SystemStats, Vec<Option<(f64, f64)>>) {
+            let config = AIConfig {
+                exploration_rate: 0.5,
+                seed: Some(seed),
+                ..AIConfig::default()
+            };
+            let ai_system = AISystem::new(config);
+            ai_system.initialize().await.unwrap();
+
+            let mut agent_ids = Vec::new();
+            for i in 0..10 {
+                let mut state = sample_state((90.0 * i as f64, 40.0 * i as f64), 10.0 * i as f64);
+                state.id = Uuid::from_u128(i as u128 + 1);
+                agent_ids.push(ai_system.add_agent("citizen".to_string(), state).await.unwrap());
+            }
+            ai_system.run_n_cycles(100).await.unwrap();
+
+            let environment = ai_system.environment.read().await;
+            let positions = agent_ids.iter().map(|id| environment.get_agent_position(*id)).collect();
+            drop(environment);
+            (ai_system.get_system_stats().await.unwrap(), positions)
+        }
+
+        let (first_stats, first_positions) = seeded_run(7).await;
+        let (second_stats, second_positions) = seeded_run(7).await;
+
+        assert_eq!(first_stats.total_agents, second_stats.total_agents);
+        assert_eq!(first_stats.total_reward, second_stats.total_reward);
+        assert_eq!(first_stats.average_efficiency, second_stats.average_efficiency);
+        assert_eq!(first_stats.environment_state, second_stats.environment_state);
+        assert_eq!(first_stats.environment_state["tick"], 100);
+        assert_eq!(first_positions, second_positions);
+
+        let (_, other_positions) = seeded_run(8).await;
+        assert_ne!(first_positions, other_positions);
+    }
+
+    #[derive(Default)]
+    struct CountingObserver {
+        starts: AtomicUsize,
+        actions: AtomicUsize,
+        ends: AtomicUsize,
+        last_cycle: AtomicU64,
+    }
+
+    impl SimulationObserver for CountingObserver {
+        fn on_cycle_start(&self, cycle: u64, stats: &SystemStats) {
+            assert_eq!(stats.environment_state["tick"], cycle);
+            self.starts.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_action(&self, _agent_id: Uuid, _action: &Action) {
+            self.actions.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_cycle_end(&self, cycle: u64, stats: &SystemStats) {
+            assert_eq!(stats.environment_state["tick"], cycle + 1);
+            self.ends.fetch_add(1, Ordering::SeqCst);
+            self.last_cycle.store(cycle, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_observers_follow_simulation_cycle() {
+        let ai_system = AISystem::new(AIConfig::default());
+        ai_system.initialize().await.unwrap();
+        for i in 0..4 {
+            let state = sample_state((50.0 * i as f64, 50.0), 60.0);
+            ai_system.add_agent("citizen".to_string(), state).await.unwrap();
+        }
+
+        let observer = Arc::new(CountingObserver::default());
+        ai_system.register_observer(observer.clone()).await;
+        ai_system.run_n_cycles(10).await.unwrap();
+
+        assert_eq!(observer.starts.load(Ordering::SeqCst), 10);
+        assert_eq!(observer.ends.load(Ordering::SeqCst), 10);
+        assert_eq!(observer.actions.load(Ordering::SeqCst), 40);
+        assert_eq!(observer.last_cycle.load(Ordering::SeqCst), 9);
+    }
+
+    #[tokio::test]
+    async fn test_system_stats_report_reward_percentiles() {
+        let ai_system = AISystem::new(AIConfig::default());
+        let agents = [
+            ("citizen", 1.0, 0.2),
+            ("citizen", 3.0, 0.4),
+            ("citizen", 5.0, 0.6),
+            ("citizen", 7.0, 0.8),
+            ("business", 10.0, 0.5),
+            ("business", 20.0, 0.9),
+        ];
+        for (agent_type, total_reward, efficiency) in agents {
+            let mut state = sample_state((0.0, 0.0), 100.0);
+            state.agent_type = agent_type.to_string();
+            state.performance_metrics.total_reward = total_reward;
+            state.performance_metrics.efficiency = efficiency;
+            ai_system.add_agent(agent_type.to_string(), state).await.unwrap();
+        }
+
+        let stats = ai_system.get_system_stats().await.unwrap();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        // Rewards sorted: 1, 3, 5, 7, 10, 20
+        assert_eq!(stats.total_agents, 6);
+        assert!(close(stats.total_reward, 46.0));
+        assert_eq!(stats.reward_distribution.min, 1.0);
+        assert_eq!(stats.reward_distribution.max, 20.0);
+        // Median sits halfway between the 3rd and 4th values
+        assert!(close(stats.reward_distribution.median, 6.0));
+        // p90 is at rank 0.9 * 5 = 4.5, between 10 and 20
+        assert!(close(stats.reward_distribution.p90, 15.0));
+        // Efficiencies sorted: 0.2, 0.4, 0.5, 0.6, 0.8, 0.9
+        assert!(close(stats.efficiency_distribution.median, 0.55));
+        assert!(close(stats.efficiency_distribution.p90, 0.85));
+        assert!(close(stats.average_efficiency, 3.4 / 6.0));
+
+        let citizens = &stats.by_type["citizen"];
+        assert_eq!(citizens.agents, 4);
+        assert!(close(citizens.total_reward, 16.0));
+        assert!(close(citizens.reward_distribution.median, 4.0));
+        // Rank 0.9 * 3 = 2.7, between 5 and 7
+        assert!(close(citizens.reward_distribution.p90, 6.4));
+        let businesses = &stats.by_type["business"];
+        assert_eq!(businesses.agents, 2);
+        assert!(close(businesses.efficiency_distribution.min, 0.5));
+        assert!(close(businesses.reward_distribution.p90, 19.0));
+    }
+
+    #[tokio::test]
+    async fn test_cycles_train_the_network_owning_each_agent() {
+        let config = AIConfig {
+            batch_size: 4,
+            network_sharing: NetworkSharing::PerAgent,
+            seed: Some(3),
+            ..AIConfig::default()
+        };
+        let ai_system = AISystem::new(config);
+        ai_system.initialize().await.unwrap();
+        let mut agent_ids = Vec::new();
+        for i in 0..3 {
+            let state = sample_state((200.0 * i as f64, 100.0), 50.0);
+            agent_ids.push(ai_system.add_agent("citizen".to_string(), state).await.unwrap());
+        }
+        ai_system.run_n_cycles(6).await.unwrap();
+
+        let learning = ai_system.learning_engine.export_state().await;
+        assert_eq!(learning.dqn.get_memory_size(), 0);
+        assert_eq!(learning.networks.len(), 3);
+        for agent_id in &agent_ids {
+            assert_eq!(learning.networks[&agent_id.to_string()].get_memory_size(), 6);
+        }
+        // Every owned network's training step is counted
+        assert_eq!(learning.train_steps, 18);
+
+        ai_system.remove_agent(agent_ids[0]).await.unwrap();
+        let networks = ai_system.learning_engine.export_state().await.networks;
+        assert_eq!(networks.len(), 2);
+        assert!(!networks.contains_key(&agent_ids[0].to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cycle_rewards_follow_reward_config() {
+        let config = AIConfig {
+            reward: RewardConfig {
+                congestion_weight: 0.0,
+                profit_weight: 1.0,
+                ..RewardConfig::default()
+            },
+            seed: Some(5),
+            ..AIConfig::default()
+        };
+        let ai_system = AISystem::new(config);
+        ai_system.initialize().await.unwrap();
+        for (i, (revenue, costs)) in [(30.0, 10.0), (4.0, 9.0)].into_iter().enumerate() {
+            let mut state = sample_state((100.0 * i as f64, 100.0), 50.0);
+            state.id = Uuid::from_u128(i as u128 + 1);
+            state.agent_type = "business".to_string();
+            state.resources = HashMap::from([("revenue".to_string(), revenue), ("costs".to_string(), costs)]);
+            ai_system.add_agent("business".to_string(), state).await.unwrap();
+        }
+        ai_system.run_n_cycles(3).await.unwrap();
+
+        // Agents act in id order, so entries alternate between the two businesses
+        let log = ai_system.learning_engine.get_reward_log().await;
+        assert_eq!(log.len(), 6);
+        for (i, entry) in log.iter().enumerate() {
+            let expected = if i % 2 == 0 { 20.0 } else { -5.0 };
+            assert_eq!(entry.reward, expected);
+            assert_eq!(entry.breakdown.component(learning::reward::PROFIT_COMPONENT).unwrap().value, expected);
+        }
+    }
 }
@@ -0,0 +1,20 @@
+//! Observadores do ciclo de simulação
+
+use uuid::Uuid;
+
+use crate::{Action, SystemStats};
+
+/// Recebe notificações do ciclo de simulação do `AISystem`
+///
+/// Todos os métodos têm implementação vazia, então um observador implementa apenas
+/// os eventos que lhe interessam. As chamadas acontecem dentro do ciclo e devem ser rápidas.
+pub trait SimulationObserver {
+    /// Chamado antes de os agentes decidirem suas ações, com as estatísticas atuais
+    fn on_cycle_start(&self, _cycle: u64, _stats: &SystemStats) {}
+
+    /// Chamado para cada ação, antes de ser executada no ambiente
+    fn on_action(&self, _agent_id: Uuid, _action: &Action) {}
+
+    /// Chamado ao final do ciclo, com as estatísticas resultantes
+    fn on_cycle_end(&self, _cycle: u64, _stats: &SystemStats) {}
+}
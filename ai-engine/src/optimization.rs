@@ -0,0 +1,51 @@
+//! Otimização global do sistema de agentes
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::agent::Agent;
+use crate::environment::Environment;
+use crate::AIConfig;
+
+/// Engine de otimização do sistema
+pub struct OptimizationEngine {
+    config: AIConfig,
+}
+
+impl OptimizationEngine {
+    pub fn new(config: AIConfig) -> Self {
+        Self { config }
+    }
+
+    /// Inicializa a engine de otimização
+    pub async fn initialize(&self) -> Result<()> {
+        info!(
+            "Engine de otimização inicializada (limiar={:.2})",
+            self.config.optimization_threshold
+        );
+        Ok(())
+    }
+
+    /// Analisa a eficiência dos agentes no ambiente atual
+    pub async fn optimize_system(
+        &self,
+        agents: &HashMap<Uuid, Agent>,
+        environment: &Environment,
+    ) -> Result<()> {
+        let below_threshold = agents
+            .values()
+            .filter(|agent| agent.get_performance_metrics().efficiency < self.config.optimization_threshold)
+            .count();
+
+        debug!(
+            "Tick {}: {} de {} agentes abaixo do limiar de eficiência",
+            environment.tick,
+            below_threshold,
+            agents.len()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Medição de spans para investigações de desempenho (feature `profiling`)
+//!
+//! Com a feature ativa, os caminhos críticos (como `DQN::train`) abrem spans `tracing`.
+//! Um `Profile` acumula quanto tempo cada span ficou ativo.
+//!
+//! O engine de simulação (`src/rust_engine`, módulo `utils::profiling`) tem uma cópia
+//! idêntica deste módulo; mudanças em um devem ser feitas também no outro.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::span::Id;
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Amostras de tempo de um span
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SpanStats {
+    pub samples: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl SpanStats {
+    /// Tempo médio por amostra
+    pub fn mean(&self) -> Duration {
+        if self.samples == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.samples as u32
+        }
+    }
+}
+
+/// Tabela compartilhada de tempos por span, preenchida pelas camadas que ela cria
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    spans: Arc<Mutex<HashMap<&'static str, SpanStats>>>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Camada que registra neste perfil, para compor um subscriber `tracing`
+    pub fn layer(&self) -> ProfileLayer {
+        ProfileLayer { profile: self.clone() }
+    }
+
+    /// Instala a camada deste perfil como subscriber global
+    ///
+    /// Retorna false se já havia um subscriber global.
+    pub fn install(&self) -> bool {
+        let subscriber = tracing_subscriber::registry().with(self.layer());
+        tracing::subscriber::set_global_default(subscriber).is_ok()
+    }
+
+    /// Tempos de um span, se ele foi aberto desde o último `reset`
+    pub fn stats(&self, name: &str) -> Option<SpanStats> {
+        self.spans.lock().unwrap().get(name).copied()
+    }
+
+    /// Tempos de todos os spans abertos desde o último `reset`
+    pub fn snapshot(&self) -> HashMap<String, SpanStats> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, stats)| (name.to_string(), *stats))
+            .collect()
+    }
+
+    /// Descarta os tempos registrados
+    pub fn reset(&self) {
+        self.spans.lock().unwrap().clear();
+    }
+
+    fn record(&self, name: &'static str, elapsed: Duration) {
+        let mut spans = self.spans.lock().unwrap();
+        let stats = spans.entry(name).or_default();
+        stats.samples += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+}
+
+/// Instante em que o span foi aberto pela última vez
+struct Entered(Instant);
+
+/// Camada `tracing` que mede cada entrada em um span
+pub struct ProfileLayer {
+    profile: Profile,
+}
+
+impl<S> Layer<S> for ProfileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().replace(Entered(Instant::now()));
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() {
+                self.profile.record(span.name(), start.elapsed());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::learning::dqn::{DQNConfig, Experience, DQN};
+    use ndarray::Array1;
+
+    #[test]
+    fn test_train_span_is_recorded() {
+        let profile = Profile::new();
+        let subscriber = tracing_subscriber::registry().with(profile.layer());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut dqn = DQN::new(DQNConfig {
+                batch_size: 2,
+                ..DQNConfig::default()
+            });
+            for i in 0..4 {
+                dqn.store_experience(Experience {
+                    state: Array1::from_elem(20, i as f64 * 0.1),
+                    action: i % 10,
+                    reward: 1.0,
+                    next_state: Array1::from_elem(20, (i + 1) as f64 * 0.1),
+                    done: false,
+                }).unwrap();
+            }
+            for _ in 0..3 {
+                dqn.train().unwrap();
+            }
+        });
+
+        let stats = profile.stats("train").expect("no samples for train");
+        assert_eq!(stats.samples, 3);
+        assert!(stats.max <= stats.total);
+        assert!(stats.mean() <= stats.max);
+    }
+}
@@ -0,0 +1,64 @@
+//! Estatísticas descritivas para os relatórios do sistema
+
+use serde::{Deserialize, Serialize};
+
+/// Quantil `q` (entre 0 e 1) de valores já ordenados, interpolando entre os vizinhos
+///
+/// Segue a definição usada pelo NumPy: a posição é `q * (n - 1)`. Diferente de
+/// `utils::math::percentile` do engine de simulação, não ordena a entrada e recebe
+/// a fração, não o percentil em [0, 100].
+pub fn quantile_sorted(sorted: &[f64], q: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let fraction = rank - lower as f64;
+    Some(sorted[lower] + (sorted[upper] - sorted[lower]) * fraction)
+}
+
+/// Resumo da distribuição de uma métrica entre os agentes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Distribution {
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub p90: f64,
+}
+
+impl Distribution {
+    /// Resume os valores; sem valores, todos os campos ficam em zero
+    pub fn from_values(values: impl IntoIterator<Item = f64>) -> Self {
+        let mut sorted: Vec<f64> = values.into_iter().collect();
+        sorted.sort_by(f64::total_cmp);
+
+        let at = |q: f64| quantile_sorted(&sorted, q).unwrap_or(0.0);
+        Self {
+            min: at(0.0),
+            max: at(1.0),
+            median: at(0.5),
+            p90: at(0.9),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantile_interpolates_between_ranks() {
+        assert_eq!(quantile_sorted(&[], 0.5), None);
+        assert_eq!(quantile_sorted(&[3.0], 0.9), Some(3.0));
+        assert_eq!(quantile_sorted(&[1.0, 2.0, 3.0, 4.0], 0.5), Some(2.5));
+        assert!((quantile_sorted(&[0.0, 10.0], 0.9).unwrap() - 9.0).abs() < 1e-12);
+
+        let distribution = Distribution::from_values([4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(distribution.min, 1.0);
+        assert_eq!(distribution.max, 4.0);
+        assert_eq!(distribution.median, 2.5);
+        assert_eq!(Distribution::from_values(Vec::new()), Distribution::default());
+    }
+}
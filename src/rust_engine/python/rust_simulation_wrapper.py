@@ -145,6 +145,7 @@ class RustSimulationWrapper:
                 'cpu_usage_percent': metrics.cpu_usage_percent,
                 'total_updates': metrics.total_updates,
                 'avg_update_time_ms': metrics.avg_update_time_ms,
+                'is_estimated': metrics.is_estimated,
             }
         else:
             return self.fallback_engine.get_performance_metrics()
@@ -5,57 +5,714 @@
 //! - Businesses with economic behavior
 //! - Government with policy enforcement
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
 use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use ndarray::{Array1, Array2};
+use crate::utils::math::histogram;
+use crate::utils::random::{agent_rng, agent_stream_seed};
+use crate::utils::data_structures::{CircularBuffer, KdTree2D};
+use crate::utils::serialization::{sorted_map, Identified};
+
 /// Agent types in the simulation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AgentType {
     Citizen,
     Business,
     Government,
 }
 
+impl AgentType {
+    /// Numeric code used in array exports
+    pub fn code(&self) -> f64 {
+        match self {
+            AgentType::Citizen => 0.0,
+            AgentType::Business => 1.0,
+            AgentType::Government => 2.0,
+        }
+    }
+}
+
+impl FromStr for AgentType {
+    type Err = String;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "citizen" => Ok(AgentType::Citizen),
+            "business" => Ok(AgentType::Business),
+            "government" => Ok(AgentType::Government),
+            _ => Err(format!("unknown agent type: {}", name)),
+        }
+    }
+}
+
 /// Citizen agent with personality and behavior
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Citizen {
     pub id: u32,
     pub position: Vector2<f64>,
     pub velocity: Vector2<f64>,
+    pub radius: f64,
     pub energy: f64,
+    pub money: f64,
+    #[serde(serialize_with = "sorted_map")]
     pub personality: HashMap<String, f64>,
+    #[serde(serialize_with = "sorted_map")]
     pub needs: HashMap<String, f64>,
-    pub decisions: Vec<String>,
-    pub learning_data: Vec<f64>,
+    /// Most recent decisions, oldest first
+    pub decisions: CircularBuffer<String>,
+    /// Most recent learning samples, oldest first
+    pub learning_data: CircularBuffer<f64>,
+    /// Cycles lived so far
+    #[serde(default)]
+    pub age: u64,
 }
 
 /// Business agent with economic behavior
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Business {
     pub id: u32,
     pub position: Vector2<f64>,
     pub velocity: Vector2<f64>,
+    pub radius: f64,
     pub energy: f64,
     pub business_type: String,
     pub revenue: f64,
     pub customers: u32,
+    /// Units on hand per product
+    #[serde(serialize_with = "sorted_map")]
     pub products: HashMap<String, f64>,
+    /// Most units of a product the business holds
+    #[serde(default = "default_stock_capacity")]
+    pub stock_capacity: f64,
+    /// Units of each product made per unit of time
+    #[serde(default = "default_production_rate")]
+    pub production_rate: f64,
+    /// Cycles in business so far
+    #[serde(default)]
+    pub age: u64,
+    /// Revenue earned since the last time a government taxed it
+    #[serde(default)]
+    pub untaxed_revenue: f64,
+}
+
+/// Product businesses sell to citizens in need of "goods"
+pub const GOODS_PRODUCT: &str = "goods";
+
+/// Default number of units a business can hold per product
+pub const DEFAULT_STOCK_CAPACITY: f64 = 50.0;
+
+/// Default units a business produces per product and unit of time
+pub const DEFAULT_PRODUCTION_RATE: f64 = 1.0;
+
+fn default_stock_capacity() -> f64 {
+    DEFAULT_STOCK_CAPACITY
+}
+
+fn default_production_rate() -> f64 {
+    DEFAULT_PRODUCTION_RATE
+}
+
+impl Business {
+    /// Units of a product on hand
+    pub fn stock_of(&self, product: &str) -> f64 {
+        self.products.get(product).copied().unwrap_or(0.0)
+    }
+    
+    /// Whether the business has no whole unit of goods left to sell
+    ///
+    /// A business in stockout earns no revenue until production restocks it.
+    pub fn in_stockout(&self) -> bool {
+        self.stock_of(GOODS_PRODUCT) < 1.0
+    }
+    
+    /// Produce goods for `delta_time`, up to `stock_capacity`
+    fn restock(&mut self, delta_time: f64) {
+        let produced = self.production_rate * delta_time;
+        let capacity = self.stock_capacity;
+        let goods = self.products.entry(GOODS_PRODUCT.to_string()).or_insert(0.0);
+        *goods = (*goods + produced).min(capacity);
+    }
+    
+    /// Credit revenue, leaving it open to taxation
+    fn earn(&mut self, amount: f64) {
+        self.revenue += amount;
+        self.untaxed_revenue += amount;
+    }
 }
 
 /// Government agent with policy enforcement
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Government {
     pub id: u32,
     pub position: Vector2<f64>,
     pub velocity: Vector2<f64>,
+    pub radius: f64,
     pub energy: f64,
+    #[serde(serialize_with = "sorted_map")]
     pub policies: HashMap<String, f64>,
     pub budget: f64,
     pub approval_rating: f64,
 }
 
+impl Identified for Citizen {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Identified for Business {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl Identified for Government {
+    fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+/// Number of discrete movement actions a citizen chooses from, one per heading sector
+pub const MOVEMENT_ACTIONS: usize = 8;
+
+/// One recorded step of a trajectory: the observation and the action chosen from it
+pub type TrajectoryStep = (Array1<f64>, usize);
+
+impl Citizen {
+    /// Observation vector: position, velocity, energy, money and goods need
+    pub fn observation(&self) -> Array1<f64> {
+        let goods_need = self.needs.get("goods").copied().unwrap_or(0.0);
+        Array1::from(vec![
+            self.position.x,
+            self.position.y,
+            self.velocity.x,
+            self.velocity.y,
+            self.energy,
+            self.money,
+            goods_need,
+        ])
+    }
+    
+    /// Movement action: the heading of the velocity quantized into `MOVEMENT_ACTIONS`
+    /// sectors, counterclockwise from +x (0 when standing still)
+    pub fn movement_action(&self) -> usize {
+        if self.velocity.norm_squared() == 0.0 {
+            return 0;
+        }
+        let sector = std::f64::consts::TAU / MOVEMENT_ACTIONS as f64;
+        let heading = self.velocity.y.atan2(self.velocity.x).rem_euclid(std::f64::consts::TAU);
+        ((heading / sector).round() as usize) % MOVEMENT_ACTIONS
+    }
+}
+
+/// Speed at which a citizen heads for what it needs when the need is at its maximum
+pub const CITIZEN_SEEK_SPEED: f64 = 1.0;
+
+/// Distance within which a citizen looks for other citizens to join when it needs company
+pub const SOCIAL_SENSING_RADIUS: f64 = 50.0;
+
+/// Default collision radius of a citizen
+pub const CITIZEN_RADIUS: f64 = 3.0;
+/// Default collision radius of a business
+pub const BUSINESS_RADIUS: f64 = 8.0;
+/// Default collision radius of a government agent
+pub const GOVERNMENT_RADIUS: f64 = 5.0;
+
+/// Category of interaction, each with its own range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InteractionType {
+    /// Conversation between two citizens
+    Social,
+    /// Trade and influence between a citizen and a business
+    Economic,
+    /// Any two agents near each other, counted in the interaction matrix
+    Proximity,
+}
+
+impl FromStr for InteractionType {
+    type Err = String;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "social" => Ok(InteractionType::Social),
+            "economic" => Ok(InteractionType::Economic),
+            "proximity" => Ok(InteractionType::Proximity),
+            _ => Err(format!("unknown interaction type: {}", name)),
+        }
+    }
+}
+
+/// Order in which a citizen tries the businesses in its economic range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CustomerMatching {
+    /// Closest business first, ties broken by lower id
+    #[default]
+    NearestFirst,
+    /// Lowest business id first, regardless of distance
+    IdOrder,
+}
+
+impl FromStr for CustomerMatching {
+    type Err = String;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "nearest" => Ok(CustomerMatching::NearestFirst),
+            "id" => Ok(CustomerMatching::IdOrder),
+            _ => Err(format!("unknown customer matching rule: {}", name)),
+        }
+    }
+}
+
+/// Side of a purchase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InteractionRole {
+    /// The citizen who buys
+    Customer,
+    /// The business that sells
+    Seller,
+}
+
+impl FromStr for InteractionRole {
+    type Err = String;
+    
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "customer" => Ok(InteractionRole::Customer),
+            "seller" => Ok(InteractionRole::Seller),
+            _ => Err(format!("unknown interaction role: {}", name)),
+        }
+    }
+}
+
+/// What one side gains from each purchase; negative values are costs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InteractionEffect {
+    pub reward: f64,
+    pub energy: f64,
+}
+
+fn default_customer_effect() -> InteractionEffect {
+    InteractionEffect { reward: 0.5, energy: 0.0 }
+}
+
+fn default_seller_effect() -> InteractionEffect {
+    InteractionEffect { reward: 1.0, energy: 0.0 }
+}
+
+/// Tunable parameters of agent interactions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InteractionConfig {
+    /// Maximum distance for a citizen and a business to interact (economic range)
+    pub radius: f64,
+    /// Maximum distance for two citizens to interact socially
+    pub social_radius: f64,
+    /// Money a citizen spends per purchase
+    pub purchase_price: f64,
+    /// Minimum "goods" need for a citizen to buy
+    pub need_threshold: f64,
+    /// How much a purchase reduces the "goods" need
+    pub need_satisfaction: f64,
+    /// Revenue a business earns from each citizen in range, whether or not they buy (off by default)
+    pub revenue_per_interaction: f64,
+    /// Order in which a citizen is matched with the businesses in range
+    #[serde(default)]
+    pub matching: CustomerMatching,
+    /// Maximum distance for two agents of any type to count in the interaction matrix
+    #[serde(default = "default_interaction_radius")]
+    pub interaction_radius: f64,
+    /// Effect of a purchase on the citizen who buys
+    #[serde(default = "default_customer_effect")]
+    pub customer_effect: InteractionEffect,
+    /// Effect of a purchase on the business that sells
+    #[serde(default = "default_seller_effect")]
+    pub seller_effect: InteractionEffect,
+}
+
+/// Default range of the interaction matrix
+pub const DEFAULT_INTERACTION_RADIUS: f64 = 20.0;
+
+fn default_interaction_radius() -> f64 {
+    DEFAULT_INTERACTION_RADIUS
+}
+
+impl InteractionConfig {
+    /// Distance threshold of an interaction type
+    pub fn radius_for(&self, interaction_type: InteractionType) -> f64 {
+        match interaction_type {
+            InteractionType::Social => self.social_radius,
+            InteractionType::Economic => self.radius,
+            InteractionType::Proximity => self.interaction_radius,
+        }
+    }
+    
+    /// Set the distance threshold of an interaction type
+    pub fn set_radius(&mut self, interaction_type: InteractionType, radius: f64) {
+        match interaction_type {
+            InteractionType::Social => self.social_radius = radius,
+            InteractionType::Economic => self.radius = radius,
+            InteractionType::Proximity => self.interaction_radius = radius,
+        }
+    }
+    
+    /// Effect of a purchase on one side
+    pub fn effect_for(&self, role: InteractionRole) -> InteractionEffect {
+        match role {
+            InteractionRole::Customer => self.customer_effect,
+            InteractionRole::Seller => self.seller_effect,
+        }
+    }
+    
+    /// Set the effect of a purchase on one side
+    pub fn set_effect(&mut self, role: InteractionRole, effect: InteractionEffect) {
+        match role {
+            InteractionRole::Customer => self.customer_effect = effect,
+            InteractionRole::Seller => self.seller_effect = effect,
+        }
+    }
+    
+    /// Whether agents `distance` apart are close enough for an interaction of this type
+    pub fn in_range(&self, interaction_type: InteractionType, distance: f64) -> bool {
+        distance < self.radius_for(interaction_type)
+    }
+}
+
+impl Default for InteractionConfig {
+    fn default() -> Self {
+        Self {
+            radius: 20.0,
+            social_radius: 5.0,
+            purchase_price: 1.0,
+            need_threshold: 0.2,
+            need_satisfaction: 0.1,
+            revenue_per_interaction: 0.0,
+            matching: CustomerMatching::default(),
+            interaction_radius: DEFAULT_INTERACTION_RADIUS,
+            customer_effect: default_customer_effect(),
+            seller_effect: default_seller_effect(),
+        }
+    }
+}
+
+/// Pairs of agents within the proximity range, counted per pair of agent types
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InteractionMatrix {
+    pub citizen_citizen: u32,
+    pub citizen_business: u32,
+    pub citizen_government: u32,
+    pub business_business: u32,
+    pub business_government: u32,
+    pub government_government: u32,
+}
+
+impl InteractionMatrix {
+    fn slot_mut(&mut self, first: &AgentType, second: &AgentType) -> &mut u32 {
+        match (first, second) {
+            (AgentType::Citizen, AgentType::Citizen) => &mut self.citizen_citizen,
+            (AgentType::Citizen, AgentType::Business) | (AgentType::Business, AgentType::Citizen) => &mut self.citizen_business,
+            (AgentType::Citizen, AgentType::Government) | (AgentType::Government, AgentType::Citizen) => &mut self.citizen_government,
+            (AgentType::Business, AgentType::Business) => &mut self.business_business,
+            (AgentType::Business, AgentType::Government) | (AgentType::Government, AgentType::Business) => &mut self.business_government,
+            (AgentType::Government, AgentType::Government) => &mut self.government_government,
+        }
+    }
+    
+    /// Number of pairs between two agent types, in either order
+    pub fn get(&self, first: &AgentType, second: &AgentType) -> u32 {
+        let mut counts = *self;
+        *counts.slot_mut(first, second)
+    }
+    
+    /// Total number of pairs of any type
+    pub fn total(&self) -> u32 {
+        self.citizen_citizen + self.citizen_business + self.citizen_government
+            + self.business_business + self.business_government + self.government_government
+    }
+    
+    /// Counts keyed by pair type, such as "citizen-business"
+    pub fn to_map(&self) -> HashMap<String, u32> {
+        HashMap::from([
+            ("citizen-citizen".to_string(), self.citizen_citizen),
+            ("citizen-business".to_string(), self.citizen_business),
+            ("citizen-government".to_string(), self.citizen_government),
+            ("business-business".to_string(), self.business_business),
+            ("business-government".to_string(), self.business_government),
+            ("government-government".to_string(), self.government_government),
+        ])
+    }
+}
+
+/// Cached citizen-business pairs for incremental interaction counting
+///
+/// Only agents that moved more than `move_threshold` since they were last evaluated
+/// have their pairs re-checked; smaller drifts reuse the previous result.
+#[derive(Debug, Clone, Default)]
+pub struct InteractionCache {
+    pub move_threshold: f64,
+    radius: f64,
+    anchors: HashMap<u32, Vector2<f64>>,
+    pairs: HashSet<(u32, u32)>,
+}
+
+impl InteractionCache {
+    pub fn new(move_threshold: f64) -> Self {
+        Self {
+            move_threshold,
+            ..Self::default()
+        }
+    }
+}
+
+/// Recognized citizen personality traits and their validation
+pub mod personality {
+    use std::collections::HashMap;
+    
+    /// Willingness to move far and take chances
+    pub const RISK_TOLERANCE: &str = "risk_tolerance";
+    /// Tendency to move toward other agents
+    pub const SOCIAL_PREFERENCE: &str = "social_preference";
+    /// Openness to new products and ideas
+    pub const INNOVATION_LEVEL: &str = "innovation_level";
+    /// Traits the engine understands
+    pub const RECOGNIZED_TRAITS: [&str; 3] = [RISK_TOLERANCE, SOCIAL_PREFERENCE, INNOVATION_LEVEL];
+    /// Value of a recognized trait that was not given
+    pub const DEFAULT_TRAIT_VALUE: f64 = 0.5;
+    
+    /// Check recognized traits lie in `0.0..=1.0` and fill the missing ones with the default
+    ///
+    /// Unknown keys are kept with a warning, since they usually are typos.
+    pub fn normalize(mut personality: HashMap<String, f64>) -> Result<HashMap<String, f64>, String> {
+        let mut unknown: Vec<&String> = personality.keys()
+            .filter(|key| !RECOGNIZED_TRAITS.contains(&key.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            tracing::warn!("Unknown personality traits {:?}; recognized traits are {:?}", unknown, RECOGNIZED_TRAITS);
+        }
+        
+        for name in RECOGNIZED_TRAITS {
+            let value = *personality.entry(name.to_string()).or_insert(DEFAULT_TRAIT_VALUE);
+            if !(0.0..=1.0).contains(&value) {
+                return Err(format!("Personality trait {} must be within 0.0..=1.0, got {}", name, value));
+            }
+        }
+        Ok(personality)
+    }
+}
+
+/// Government policy keys and their defaults
+pub mod policy {
+    /// Fraction of the revenue nearby businesses earn that is collected as tax
+    pub const TAX_RATE: &str = "tax_rate";
+    pub const DEFAULT_TAX_RATE: f64 = 0.1;
+    /// Range within which the government taxes businesses and supports citizens
+    pub const JURISDICTION_RADIUS: &str = "jurisdiction_radius";
+    pub const DEFAULT_JURISDICTION_RADIUS: f64 = 200.0;
+    /// Citizens below this energy are topped up from the budget
+    pub const WELFARE_THRESHOLD: &str = "welfare_threshold";
+    pub const DEFAULT_WELFARE_THRESHOLD: f64 = 30.0;
+}
+
+/// Default number of cycles an agent may stay at zero energy before it is removed
+pub const DEFAULT_DEATH_GRACE_CYCLES: u32 = 10;
+
+/// Lifecycle of citizens and businesses
+///
+/// The default disables aging: energy decays at the base rate and nobody retires.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgingConfig {
+    /// Extra energy decay per cycle of age, as a fraction of the base decay rate
+    pub decay_growth: f64,
+    /// Age in cycles past which agents retire and are removed (None for no limit)
+    pub max_age: Option<u64>,
+}
+
+impl AgingConfig {
+    /// Factor applied to the base energy decay of an agent of `age` cycles
+    pub fn decay_multiplier(&self, age: u64) -> f64 {
+        1.0 + self.decay_growth.max(0.0) * age as f64
+    }
+    
+    /// Whether an agent of `age` cycles is past the maximum age
+    pub fn is_retired(&self, age: u64) -> bool {
+        self.max_age.is_some_and(|max_age| age > max_age)
+    }
+}
+
+/// Default agent count from which collisions use the spatial grid instead of brute force
+pub const DEFAULT_COLLISION_GRID_THRESHOLD: usize = 64;
+
+/// Default number of decisions and learning samples each citizen retains
+pub const DEFAULT_CITIZEN_HISTORY: usize = 100;
+
+/// Energy agents spawn with unless a spawn distribution is configured
+pub const DEFAULT_SPAWN_ENERGY: f64 = 100.0;
+
+/// Algorithm used by a collision pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollisionStrategy {
+    BruteForce,
+    Grid,
+}
+
+/// Macroeconomic cycle that modulates business activity over time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EconomicCycle {
+    pub period: f64,
+    pub amplitude: f64,
+    pub phase: f64,
+}
+
+impl EconomicCycle {
+    /// Create a sinusoidal cycle with the given period (in ticks) and amplitude
+    pub fn new(period: f64, amplitude: f64) -> Self {
+        Self {
+            period: period.max(1.0),
+            amplitude: amplitude.clamp(0.0, 1.0),
+            phase: 0.0,
+        }
+    }
+    
+    /// Activity multiplier at a given tick: above 1.0 in a boom, below 1.0 in a bust
+    pub fn multiplier(&self, tick: u64) -> f64 {
+        let angle = 2.0 * std::f64::consts::PI * tick as f64 / self.period + self.phase;
+        1.0 + self.amplitude * angle.sin()
+    }
+}
+
+/// Read-only view over an agent of any type
+#[derive(Debug, Clone, Copy)]
+pub enum AgentView<'a> {
+    Citizen(&'a Citizen),
+    Business(&'a Business),
+    Government(&'a Government),
+}
+
+impl<'a> AgentView<'a> {
+    pub fn id(&self) -> u32 {
+        match self {
+            AgentView::Citizen(citizen) => citizen.id,
+            AgentView::Business(business) => business.id,
+            AgentView::Government(government) => government.id,
+        }
+    }
+    
+    pub fn agent_type(&self) -> AgentType {
+        match self {
+            AgentView::Citizen(_) => AgentType::Citizen,
+            AgentView::Business(_) => AgentType::Business,
+            AgentView::Government(_) => AgentType::Government,
+        }
+    }
+    
+    pub fn position(&self) -> Vector2<f64> {
+        match self {
+            AgentView::Citizen(citizen) => citizen.position,
+            AgentView::Business(business) => business.position,
+            AgentView::Government(government) => government.position,
+        }
+    }
+    
+    pub fn velocity(&self) -> Vector2<f64> {
+        match self {
+            AgentView::Citizen(citizen) => citizen.velocity,
+            AgentView::Business(business) => business.velocity,
+            AgentView::Government(government) => government.velocity,
+        }
+    }
+    
+    pub fn energy(&self) -> f64 {
+        match self {
+            AgentView::Citizen(citizen) => citizen.energy,
+            AgentView::Business(business) => business.energy,
+            AgentView::Government(government) => government.energy,
+        }
+    }
+    
+    pub fn radius(&self) -> f64 {
+        match self {
+            AgentView::Citizen(citizen) => citizen.radius,
+            AgentView::Business(business) => business.radius,
+            AgentView::Government(government) => government.radius,
+        }
+    }
+}
+
+/// Runs an agent type's logic every `interval` cycles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateSchedule {
+    pub interval: u32,
+    pub elapsed_cycles: u32,
+    pub elapsed_time: f64,
+}
+
+impl UpdateSchedule {
+    /// Schedule that fires every `interval` cycles
+    pub fn every(interval: u32) -> Self {
+        Self {
+            interval: interval.max(1),
+            elapsed_cycles: 0,
+            elapsed_time: 0.0,
+        }
+    }
+    
+    /// Advance by one cycle, returning the time accumulated since the last run when due
+    pub fn advance(&mut self, delta_time: f64) -> Option<f64> {
+        self.elapsed_cycles += 1;
+        self.elapsed_time += delta_time;
+        
+        if self.elapsed_cycles >= self.interval {
+            let elapsed_time = self.elapsed_time;
+            self.elapsed_cycles = 0;
+            self.elapsed_time = 0.0;
+            Some(elapsed_time)
+        } else {
+            None
+        }
+    }
+}
+
+/// Energy new agents of one type spawn with, drawn from a normal distribution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpawnEnergy {
+    pub mean: f64,
+    pub std_dev: f64,
+}
+
+impl SpawnEnergy {
+    /// Every agent spawns with exactly `energy`
+    pub fn fixed(energy: f64) -> Self {
+        Self {
+            mean: energy,
+            std_dev: 0.0,
+        }
+    }
+    
+    /// Draw a spawn energy, never below zero
+    ///
+    /// A fixed distribution consumes no randomness.
+    pub fn sample(&self, rng: &mut StdRng) -> f64 {
+        if self.std_dev <= 0.0 {
+            return self.mean.max(0.0);
+        }
+        
+        // Box-Muller transform; 1 - u keeps the logarithm finite
+        let u1: f64 = 1.0 - rng.gen::<f64>();
+        let u2: f64 = rng.gen();
+        let standard = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        
+        (self.mean + self.std_dev * standard).max(0.0)
+    }
+}
+
 /// Main agent engine that manages all agents
 #[derive(Clone)]
 pub struct AgentEngine {
@@ -64,34 +721,238 @@ pub struct AgentEngine {
     pub government: HashMap<u32, Government>,
     pub next_id: u32,
     pub interaction_count: u32,
+    pub social_interaction_count: u32,
+    pub interaction_matrix: InteractionMatrix,
+    pub tick: u64,
+    pub economic_cycle: Option<EconomicCycle>,
+    pub rng: StdRng,
+    pub behavior_seed: u64,
+    pub agent_seeds: HashMap<u32, u64>,
+    pub citizens_enabled: bool,
+    pub businesses_enabled: bool,
+    pub government_enabled: bool,
+    /// Whether collisions may push agents of each type; immovable agents stay put
+    pub citizens_movable: bool,
+    pub businesses_movable: bool,
+    pub government_movable: bool,
+    pub citizen_schedule: UpdateSchedule,
+    pub business_schedule: UpdateSchedule,
+    pub government_schedule: UpdateSchedule,
+    pub citizen_spawn_energy: SpawnEnergy,
+    pub business_spawn_energy: SpawnEnergy,
+    pub government_spawn_energy: SpawnEnergy,
+    pub collision_checks: u64,
+    pub collision_grid_threshold: usize,
+    /// Fraction of approach speed kept after a collision; `None` only separates agents
+    pub collision_restitution: Option<f64>,
+    /// Factor applied to every agent's radius when resolving collisions
+    pub collision_radius_scale: f64,
+    pub last_collision_strategy: CollisionStrategy,
+    pub death_grace_cycles: u32,
+    pub aging: AgingConfig,
+    pub depleted_cycles: HashMap<u32, u32>,
+    pub interaction_config: InteractionConfig,
+    pub transaction_volume: f64,
+    /// (citizen, business) pairs of the purchases made in the last cycle, in order
+    pub customer_matches: Vec<(u32, u32)>,
+    /// Reward each agent earned from purchases in the last cycle
+    pub interaction_rewards: HashMap<u32, f64>,
+    pub interaction_cache: Option<InteractionCache>,
+    pub interaction_checks: u64,
+    pub parallel_processing: bool,
+    /// Threads used by the parallel paths; None uses rayon's global pool
+    pub thread_count: Option<usize>,
+    thread_pool: Option<Arc<rayon::ThreadPool>>,
+    pub recordings: HashMap<u32, Vec<TrajectoryStep>>,
+    pub citizen_history: usize,
+    /// Full angle in radians of the cone agents perceive, centered on their heading; None sees all around
+    pub field_of_view: Option<f64>,
 }
 
 impl AgentEngine {
     /// Create new agent engine
     pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+    
+    /// Create agent engine with a fixed seed for reproducible behavior
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+    
+    fn with_rng(mut rng: StdRng) -> Self {
+        let behavior_seed = rng.gen();
         Self {
             citizens: HashMap::new(),
             businesses: HashMap::new(),
             government: HashMap::new(),
             next_id: 1,
             interaction_count: 0,
+            social_interaction_count: 0,
+            interaction_matrix: InteractionMatrix::default(),
+            tick: 0,
+            economic_cycle: None,
+            rng,
+            behavior_seed,
+            agent_seeds: HashMap::new(),
+            citizens_enabled: true,
+            businesses_enabled: true,
+            government_enabled: true,
+            citizens_movable: true,
+            businesses_movable: true,
+            government_movable: true,
+            citizen_schedule: UpdateSchedule::every(1),
+            business_schedule: UpdateSchedule::every(1),
+            government_schedule: UpdateSchedule::every(1),
+            citizen_spawn_energy: SpawnEnergy::fixed(DEFAULT_SPAWN_ENERGY),
+            business_spawn_energy: SpawnEnergy::fixed(DEFAULT_SPAWN_ENERGY),
+            government_spawn_energy: SpawnEnergy::fixed(DEFAULT_SPAWN_ENERGY),
+            collision_checks: 0,
+            collision_grid_threshold: DEFAULT_COLLISION_GRID_THRESHOLD,
+            collision_restitution: None,
+            collision_radius_scale: 1.0,
+            last_collision_strategy: CollisionStrategy::BruteForce,
+            death_grace_cycles: DEFAULT_DEATH_GRACE_CYCLES,
+            aging: AgingConfig::default(),
+            depleted_cycles: HashMap::new(),
+            interaction_config: InteractionConfig::default(),
+            transaction_volume: 0.0,
+            customer_matches: Vec::new(),
+            interaction_rewards: HashMap::new(),
+            interaction_cache: None,
+            interaction_checks: 0,
+            parallel_processing: true,
+            thread_count: None,
+            thread_pool: None,
+            recordings: HashMap::new(),
+            citizen_history: DEFAULT_CITIZEN_HISTORY,
+            field_of_view: None,
+        }
+    }
+    
+    /// Enable or disable behavior processing for one agent type
+    pub fn set_processing_enabled(&mut self, agent_type: AgentType, enabled: bool) {
+        match agent_type {
+            AgentType::Citizen => self.citizens_enabled = enabled,
+            AgentType::Business => self.businesses_enabled = enabled,
+            AgentType::Government => self.government_enabled = enabled,
+        }
+    }
+    
+    /// Let collisions push agents of one type or not (all types are movable by default)
+    ///
+    /// When an immovable agent collides with a movable one, the movable agent takes
+    /// the whole separation and bounces off; two immovable agents may overlap.
+    pub fn set_movable(&mut self, agent_type: AgentType, movable: bool) {
+        match agent_type {
+            AgentType::Citizen => self.citizens_movable = movable,
+            AgentType::Business => self.businesses_movable = movable,
+            AgentType::Government => self.government_movable = movable,
+        }
+    }
+    
+    /// Whether collisions may push an agent
+    fn is_movable(&self, id: u32) -> bool {
+        if self.citizens.contains_key(&id) {
+            self.citizens_movable
+        } else if self.businesses.contains_key(&id) {
+            self.businesses_movable
+        } else {
+            self.government_movable
+        }
+    }
+    
+    /// Process citizens and businesses across threads (enabled by default)
+    ///
+    /// Both paths give identical results for the same seed.
+    pub fn set_parallel_processing(&mut self, enabled: bool) {
+        self.parallel_processing = enabled;
+    }
+    
+    /// Run the parallel paths on a dedicated pool of `threads` threads (None for rayon's global pool)
+    ///
+    /// Results do not depend on the thread count; this only pins how the work is scheduled.
+    pub fn set_thread_count(&mut self, threads: Option<usize>) -> Result<(), rayon::ThreadPoolBuildError> {
+        self.thread_pool = match threads {
+            Some(threads) => {
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(threads.max(1)).build()?;
+                Some(Arc::new(pool))
+            }
+            None => None,
+        };
+        self.thread_count = threads.map(|threads| threads.max(1));
+        Ok(())
+    }
+    
+    /// Run `op` inside the configured thread pool, or the global one
+    fn install<R: Send>(pool: Option<&rayon::ThreadPool>, op: impl FnOnce() -> R + Send) -> R {
+        match pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+    
+    /// Run one agent type's logic only every `interval` cycles
+    ///
+    /// The time steps of skipped cycles are accumulated and passed on the next run.
+    pub fn set_update_interval(&mut self, agent_type: AgentType, interval: u32) {
+        let schedule = UpdateSchedule::every(interval);
+        match agent_type {
+            AgentType::Citizen => self.citizen_schedule = schedule,
+            AgentType::Business => self.business_schedule = schedule,
+            AgentType::Government => self.government_schedule = schedule,
+        }
+    }
+    
+    /// Draw the initial energy of one agent type from a normal distribution
+    ///
+    /// Draws come from the engine's seeded RNG, so seeded runs spawn identical populations.
+    pub fn set_spawn_energy(&mut self, agent_type: AgentType, mean: f64, std_dev: f64) {
+        let spawn_energy = SpawnEnergy {
+            mean,
+            std_dev: std_dev.max(0.0),
+        };
+        match agent_type {
+            AgentType::Citizen => self.citizen_spawn_energy = spawn_energy,
+            AgentType::Business => self.business_spawn_energy = spawn_energy,
+            AgentType::Government => self.government_spawn_energy = spawn_energy,
+        }
+    }
+    
+    /// Set how many decisions and learning samples each citizen retains
+    ///
+    /// Existing citizens keep their most recent entries that still fit.
+    pub fn set_citizen_history(&mut self, capacity: usize) {
+        self.citizen_history = capacity;
+        for citizen in self.citizens.values_mut() {
+            citizen.decisions.resize(capacity);
+            citizen.learning_data.resize(capacity);
         }
     }
     
+    /// Up to `n` most recent decisions of a citizen, oldest first
+    pub fn recent_decisions(&self, id: u32, n: usize) -> Option<Vec<String>> {
+        self.citizens.get(&id).map(|citizen| citizen.decisions.recent(n))
+    }
+    
     /// Add a citizen agent
     pub fn add_citizen(&mut self, x: f64, y: f64, personality: HashMap<String, f64>) -> u32 {
         let id = self.next_id;
         self.next_id += 1;
+        let energy = self.citizen_spawn_energy.sample(&mut self.rng);
         
         let citizen = Citizen {
             id,
             position: Vector2::new(x, y),
             velocity: Vector2::new(0.0, 0.0),
-            energy: 100.0,
+            radius: CITIZEN_RADIUS,
+            energy,
+            money: 100.0,
             personality,
-            needs: HashMap::new(),
-            decisions: Vec::new(),
-            learning_data: Vec::new(),
+            needs: HashMap::from([("goods".to_string(), 0.5)]),
+            decisions: CircularBuffer::new(self.citizen_history),
+            learning_data: CircularBuffer::new(self.citizen_history),
+            age: 0,
         };
         
         self.citizens.insert(id, citizen);
@@ -102,16 +963,22 @@ impl AgentEngine {
     pub fn add_business(&mut self, x: f64, y: f64, business_type: String) -> u32 {
         let id = self.next_id;
         self.next_id += 1;
+        let energy = self.business_spawn_energy.sample(&mut self.rng);
         
         let business = Business {
             id,
             position: Vector2::new(x, y),
             velocity: Vector2::new(0.0, 0.0),
-            energy: 100.0,
+            radius: BUSINESS_RADIUS,
+            energy,
             business_type,
             revenue: 0.0,
             customers: 0,
-            products: HashMap::new(),
+            products: HashMap::from([(GOODS_PRODUCT.to_string(), DEFAULT_STOCK_CAPACITY)]),
+            stock_capacity: DEFAULT_STOCK_CAPACITY,
+            production_rate: DEFAULT_PRODUCTION_RATE,
+            age: 0,
+            untaxed_revenue: 0.0,
         };
         
         self.businesses.insert(id, business);
@@ -122,12 +989,14 @@ impl AgentEngine {
     pub fn add_government(&mut self, x: f64, y: f64, policies: HashMap<String, f64>) -> u32 {
         let id = self.next_id;
         self.next_id += 1;
+        let energy = self.government_spawn_energy.sample(&mut self.rng);
         
         let government = Government {
             id,
             position: Vector2::new(x, y),
             velocity: Vector2::new(0.0, 0.0),
-            energy: 100.0,
+            radius: GOVERNMENT_RADIUS,
+            energy,
             policies,
             budget: 10000.0,
             approval_rating: 0.5,
@@ -137,52 +1006,308 @@ impl AgentEngine {
         id
     }
     
-    /// Process one cycle of agent behavior
-    pub fn process_cycle(&mut self, delta_time: f64) {
-        // Process citizens
-        for citizen in self.citizens.values_mut() {
-            self.process_citizen(citizen, delta_time);
-        }
-        
-        // Process businesses
-        for business in self.businesses.values_mut() {
-            self.process_business(business, delta_time);
-        }
+    /// Remove an agent of any type, returning whether it existed
+    pub fn remove_agent(&mut self, id: u32) -> bool {
+        let removed = self.forget_agent(id);
         
-        // Process government
-        for government in self.government.values_mut() {
-            self.process_government(government, delta_time);
+        if removed {
+            // Keep interaction count consistent with the remaining population
+            self.calculate_interactions();
         }
         
-        // Calculate interactions
-        self.calculate_interactions();
+        removed
     }
     
-    /// Process citizen behavior
-    fn process_citizen(&mut self, citizen: &mut Citizen, delta_time: f64) {
-        // Update energy
-        citizen.energy = (citizen.energy - 0.1 * delta_time).max(0.0);
-        
-        // Simple movement based on personality
-        let risk_tolerance = citizen.personality.get("risk_tolerance").unwrap_or(&0.5);
-        let social_preference = citizen.personality.get("social_preference").unwrap_or(&0.5);
+    /// Drop an agent and everything kept about it, returning whether it existed
+    fn forget_agent(&mut self, id: u32) -> bool {
+        let removed = self.citizens.remove(&id).is_some()
+            || self.businesses.remove(&id).is_some()
+            || self.government.remove(&id).is_some();
         
-        // Random movement influenced by personality
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+        self.depleted_cycles.remove(&id);
+        self.agent_seeds.remove(&id);
+        self.recordings.remove(&id);
         
-        let move_x = (rng.gen::<f64>() - 0.5) * 2.0 * risk_tolerance;
-        let move_y = (rng.gen::<f64>() - 0.5) * 2.0 * social_preference;
+        removed
+    }
+    
+    /// Process one cycle of agent behavior, returning the ids of agents removed for lack of energy
+    #[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
+    pub fn process_cycle(&mut self, delta_time: f64) -> Vec<u32> {
+        let economic_activity = self.get_economic_activity();
         
-        citizen.velocity = Vector2::new(move_x, move_y);
+        // Each agent draws from its own stream, reseeded every tick, so random draws
+        // are reproducible whatever the processing order or thread
+        let tick = self.tick;
+        let agent_seeds = &self.agent_seeds;
+        let behavior_seed = self.behavior_seed;
+        let rng_for = |id: u32| agent_rng(Self::stream_seed(agent_seeds, behavior_seed, id), tick);
+        let aging = self.aging;
         
-        // Make decisions based on personality
-        if rng.gen::<f64>() < 0.1 {
-            let decision = format!("Decision based on risk_tolerance: {:.2}", risk_tolerance);
-            citizen.decisions.push(decision);
+        // Process citizens
+        if let Some(elapsed) = Self::due(self.citizens_enabled, &mut self.citizen_schedule, delta_time) {
+            let observations: Vec<(u32, Array1<f64>)> = self.recordings.keys()
+                .filter_map(|id| self.citizens.get(id).map(|citizen| (*id, citizen.observation())))
+                .collect();
+            
+            let goals = self.citizen_goals();
+            let process = |citizen: &mut Citizen| {
+                let goal = goals.get(&citizen.id).copied().unwrap_or_else(Vector2::zeros);
+                Self::process_citizen(citizen, goal, elapsed, &aging, &mut rng_for(citizen.id));
+            };
+            if self.parallel_processing {
+                Self::install(self.thread_pool.as_deref(), || {
+                    self.citizens.par_iter_mut().for_each(|(_, citizen)| process(citizen));
+                });
+            } else {
+                self.citizens.values_mut().for_each(process);
+            }
+            
+            for (id, observation) in observations {
+                let action = self.citizens[&id].movement_action();
+                self.recordings.entry(id).or_default().push((observation, action));
+            }
         }
         
-        // Learn from experience
+        // Process businesses
+        if let Some(elapsed) = Self::due(self.businesses_enabled, &mut self.business_schedule, delta_time) {
+            let process = |business: &mut Business| {
+                Self::process_business(business, elapsed, economic_activity, &aging, &mut rng_for(business.id));
+            };
+            if self.parallel_processing {
+                Self::install(self.thread_pool.as_deref(), || {
+                    self.businesses.par_iter_mut().for_each(|(_, business)| process(business));
+                });
+            } else {
+                self.businesses.values_mut().for_each(process);
+            }
+        }
+        
+        // Process government
+        if let Some(elapsed) = Self::due(self.government_enabled, &mut self.government_schedule, delta_time) {
+            for id in Self::sorted_ids(&self.government) {
+                if let Some(government) = self.government.get_mut(&id) {
+                    Self::process_government(government, elapsed, &mut self.businesses, &mut self.citizens);
+                }
+            }
+        }
+        
+        // Citizens buy from nearby businesses
+        self.process_transactions();
+        
+        // Citizens and businesses grow one cycle older
+        for citizen in self.citizens.values_mut() {
+            citizen.age += 1;
+        }
+        for business in self.businesses.values_mut() {
+            business.age += 1;
+        }
+        
+        // Remove agents that ran out of energy or retired
+        let removed = self.remove_depleted_agents();
+        
+        // Calculate interactions
+        self.calculate_interactions();
+        
+        self.tick += 1;
+        removed
+    }
+    
+    /// Seed of an agent's random stream, or None if the agent does not exist
+    ///
+    /// Unless set with `set_agent_seed`, it is derived from the engine seed and the agent id.
+    pub fn agent_seed(&self, id: u32) -> Option<u64> {
+        self.contains_agent(id).then(|| Self::stream_seed(&self.agent_seeds, self.behavior_seed, id))
+    }
+    
+    /// Pin an agent's random stream to a seed, returning whether the agent exists
+    ///
+    /// The agent's draws then depend only on this seed and the tick, not on the
+    /// engine seed or on other agents.
+    pub fn set_agent_seed(&mut self, id: u32, seed: u64) -> bool {
+        if !self.contains_agent(id) {
+            return false;
+        }
+        self.agent_seeds.insert(id, seed);
+        true
+    }
+    
+    /// Start recording a citizen's observations and chosen actions, returning whether it exists
+    ///
+    /// One step is recorded each time citizens are processed. Recording an agent that
+    /// is already recorded keeps its trajectory so far.
+    pub fn start_recording(&mut self, id: u32) -> bool {
+        if !self.citizens.contains_key(&id) {
+            return false;
+        }
+        self.recordings.entry(id).or_default();
+        true
+    }
+    
+    /// Stop recording an agent and return its trajectory (empty if it was not recorded)
+    pub fn take_trajectory(&mut self, id: u32) -> Vec<TrajectoryStep> {
+        self.recordings.remove(&id).unwrap_or_default()
+    }
+    
+    fn contains_agent(&self, id: u32) -> bool {
+        self.citizens.contains_key(&id) || self.businesses.contains_key(&id) || self.government.contains_key(&id)
+    }
+    
+    fn stream_seed(agent_seeds: &HashMap<u32, u64>, behavior_seed: u64, id: u32) -> u64 {
+        agent_seeds.get(&id).copied().unwrap_or_else(|| agent_stream_seed(behavior_seed, id))
+    }
+    
+    /// Time step to process an agent type with this cycle, if it is enabled and due
+    fn due(enabled: bool, schedule: &mut UpdateSchedule, delta_time: f64) -> Option<f64> {
+        if enabled {
+            schedule.advance(delta_time)
+        } else {
+            None
+        }
+    }
+    
+    /// Remove citizens and businesses that stayed at zero energy longer than the grace period
+    /// or outlived the maximum age
+    ///
+    /// Government agents are exempt. Agents that regain energy start counting again.
+    fn remove_depleted_agents(&mut self) -> Vec<u32> {
+        let depleted: HashSet<u32> = self.citizens.values()
+            .filter(|citizen| citizen.energy <= 0.0)
+            .map(|citizen| citizen.id)
+            .chain(self.businesses.values()
+                .filter(|business| business.energy <= 0.0)
+                .map(|business| business.id))
+            .collect();
+        
+        self.depleted_cycles.retain(|id, _| depleted.contains(id));
+        
+        let mut removed = HashSet::new();
+        for id in depleted {
+            let cycles = self.depleted_cycles.entry(id).or_insert(0);
+            *cycles += 1;
+            if *cycles > self.death_grace_cycles {
+                removed.insert(id);
+            }
+        }
+        
+        let aging = self.aging;
+        removed.extend(self.citizens.values()
+            .filter(|citizen| aging.is_retired(citizen.age))
+            .map(|citizen| citizen.id)
+            .chain(self.businesses.values()
+                .filter(|business| aging.is_retired(business.age))
+                .map(|business| business.id)));
+        
+        let mut removed: Vec<u32> = removed.into_iter().collect();
+        removed.sort_unstable();
+        
+        for &id in &removed {
+            self.forget_agent(id);
+        }
+        
+        removed
+    }
+    
+    /// Ids of a map in ascending order
+    fn sorted_ids<T>(agents: &HashMap<u32, T>) -> Vec<u32> {
+        let mut ids: Vec<u32> = agents.keys().copied().collect();
+        ids.sort_unstable();
+        ids
+    }
+    
+    /// Current economic activity multiplier (1.0 when no cycle is configured)
+    pub fn get_economic_activity(&self) -> f64 {
+        self.economic_cycle
+            .as_ref()
+            .map_or(1.0, |cycle| cycle.multiplier(self.tick))
+    }
+    
+    /// Direction each needy citizen heads in, weighted by how strong its needs are
+    ///
+    /// A need for energy pulls toward the nearest business and a need for company
+    /// toward the centroid of the perceived citizens within `SOCIAL_SENSING_RADIUS`,
+    /// weighted by the citizen's social preference relative to the default trait value.
+    /// Citizens without either need are left out. Both lookups go through k-d trees, and
+    /// neighbors are summed in id order so the result does not depend on map iteration
+    /// order or on whether citizens are processed in parallel.
+    fn citizen_goals(&self) -> HashMap<u32, Vector2<f64>> {
+        let businesses: Vec<(u32, Vector2<f64>)> = self.businesses.values()
+            .map(|business| (business.id, business.position))
+            .collect();
+        let citizens: Vec<(u32, Vector2<f64>)> = self.citizens.values()
+            .map(|citizen| (citizen.id, citizen.position))
+            .collect();
+        let business_tree = KdTree2D::build(&businesses);
+        let citizen_tree = KdTree2D::build(&citizens);
+        
+        let heading = |from: Vector2<f64>, to: Vector2<f64>| {
+            (to - from).try_normalize(f64::EPSILON).unwrap_or_else(Vector2::zeros)
+        };
+        
+        let goal_for = |citizen: &Citizen| {
+            let need = |key: &str| citizen.needs.get(key).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+            let (energy_need, social_need) = (need("energy"), need("social"));
+            if energy_need == 0.0 && social_need == 0.0 {
+                return None;
+            }
+            
+            let position = citizen.position;
+            let mut goal = Vector2::zeros();
+            
+            if let Some(business_id) = business_tree.nearest(position) {
+                goal += heading(position, self.businesses[&business_id].position) * energy_need;
+            }
+            
+            if social_need > 0.0 {
+                let (sum, count) = citizen_tree.within(position, SOCIAL_SENSING_RADIUS).into_iter()
+                    .filter(|(other_id, other)| *other_id != citizen.id && self.in_field_of_view(position, citizen.velocity, *other))
+                    .fold((Vector2::zeros(), 0), |(sum, count), (_, other)| (sum + other, count + 1));
+                if count > 0 {
+                    let social_preference = citizen.personality.get(personality::SOCIAL_PREFERENCE)
+                        .unwrap_or(&personality::DEFAULT_TRAIT_VALUE);
+                    let weight = social_need * social_preference / personality::DEFAULT_TRAIT_VALUE;
+                    goal += heading(position, sum / count as f64) * weight;
+                }
+            }
+            
+            Some((citizen.id, goal))
+        };
+        
+        if self.parallel_processing {
+            Self::install(self.thread_pool.as_deref(), || {
+                self.citizens.par_iter().filter_map(|(_, citizen)| goal_for(citizen)).collect()
+            })
+        } else {
+            self.citizens.values().filter_map(goal_for).collect()
+        }
+    }
+    
+    /// Process citizen behavior
+    ///
+    /// `goal` is the needs-weighted direction from `citizen_goals`.
+    fn process_citizen(citizen: &mut Citizen, goal: Vector2<f64>, delta_time: f64, aging: &AgingConfig, rng: &mut StdRng) {
+        // Update energy, decaying faster with age
+        let decay = 0.1 * aging.decay_multiplier(citizen.age);
+        citizen.energy = (citizen.energy - decay * delta_time).max(0.0);
+        
+        // Demand for goods grows until satisfied by purchases
+        let goods_need = citizen.needs.entry("goods".to_string()).or_insert(0.0);
+        *goods_need = (*goods_need + 0.01 * delta_time).min(1.0);
+        
+        // Head for what the citizen needs, wandering more the more risk-tolerant it is
+        let risk_tolerance = citizen.personality.get(personality::RISK_TOLERANCE).unwrap_or(&personality::DEFAULT_TRAIT_VALUE);
+        let wander_x = (rng.gen::<f64>() - 0.5) * 2.0 * risk_tolerance;
+        let wander_y = (rng.gen::<f64>() - 0.5) * 2.0 * risk_tolerance;
+        
+        citizen.velocity = goal * CITIZEN_SEEK_SPEED + Vector2::new(wander_x, wander_y);
+        
+        // Make decisions based on personality
+        if rng.gen::<f64>() < 0.1 {
+            let decision = format!("Decision based on risk_tolerance: {:.2}", risk_tolerance);
+            citizen.decisions.push(decision);
+        }
+        
+        // Learn from experience
         if rng.gen::<f64>() < 0.05 {
             let learning = rng.gen::<f64>();
             citizen.learning_data.push(learning);
@@ -190,18 +1315,20 @@ impl AgentEngine {
     }
     
     /// Process business behavior
-    fn process_business(&mut self, business: &mut Business, delta_time: f64) {
-        // Update energy
-        business.energy = (business.energy - 0.05 * delta_time).max(0.0);
+    fn process_business(business: &mut Business, delta_time: f64, economic_activity: f64, aging: &AgingConfig, rng: &mut StdRng) {
+        // Update energy, decaying faster with age
+        let decay = 0.05 * aging.decay_multiplier(business.age);
+        business.energy = (business.energy - decay * delta_time).max(0.0);
         
-        // Economic behavior
-        business.revenue += 1.0 * delta_time;
-        business.customers = (business.customers as f64 + 0.1 * delta_time) as u32;
+        business.restock(delta_time);
         
-        // Simple movement
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+        // Economic behavior, scaled by the macroeconomic cycle; nothing is earned in a stockout
+        if !business.in_stockout() {
+            business.earn(1.0 * delta_time * economic_activity);
+        }
+        business.customers = (business.customers as f64 + 0.1 * delta_time * economic_activity) as u32;
         
+        // Simple movement
         let move_x = (rng.gen::<f64>() - 0.5) * 0.5;
         let move_y = (rng.gen::<f64>() - 0.5) * 0.5;
         
@@ -209,7 +1336,12 @@ impl AgentEngine {
     }
     
     /// Process government behavior
-    fn process_government(&mut self, government: &mut Government, delta_time: f64) {
+    fn process_government(
+        government: &mut Government,
+        delta_time: f64,
+        businesses: &mut HashMap<u32, Business>,
+        citizens: &mut HashMap<u32, Citizen>,
+    ) {
         // Update energy
         government.energy = (government.energy - 0.02 * delta_time).max(0.0);
         
@@ -217,6 +1349,35 @@ impl AgentEngine {
         government.budget += 10.0 * delta_time;
         government.approval_rating = (government.approval_rating + 0.001 * delta_time).min(1.0);
         
+        let policy_value = |key: &str, default: f64| government.policies.get(key).copied().unwrap_or(default);
+        let tax_rate = policy_value(policy::TAX_RATE, policy::DEFAULT_TAX_RATE).clamp(0.0, 1.0);
+        let radius = policy_value(policy::JURISDICTION_RADIUS, policy::DEFAULT_JURISDICTION_RADIUS);
+        let welfare_threshold = policy_value(policy::WELFARE_THRESHOLD, policy::DEFAULT_WELFARE_THRESHOLD);
+        
+        // Taxation: collect a fraction of the revenue nearby businesses earned since
+        // they were last taxed, so accumulated revenue is never taxed twice
+        for id in Self::sorted_ids(businesses) {
+            let business = businesses.get_mut(&id).unwrap();
+            if (business.position - government.position).magnitude() <= radius {
+                let tax = (business.untaxed_revenue * tax_rate).clamp(0.0, business.revenue.max(0.0));
+                business.revenue -= tax;
+                business.untaxed_revenue = 0.0;
+                government.budget += tax;
+            }
+        }
+        
+        // Redistribution: top up low-energy citizens from the budget
+        for id in Self::sorted_ids(citizens) {
+            let citizen = citizens.get_mut(&id).unwrap();
+            if citizen.energy < welfare_threshold
+                && (citizen.position - government.position).magnitude() <= radius
+            {
+                let support = (welfare_threshold - citizen.energy).min(government.budget.max(0.0));
+                citizen.energy += support;
+                government.budget -= support;
+            }
+        }
+        
         // Minimal movement
         government.velocity = Vector2::new(0.0, 0.0);
     }
@@ -239,93 +1400,432 @@ impl AgentEngine {
         }
     }
     
+    /// Accelerate every agent by the force the field exerts at its position
+    pub fn apply_force_field(&mut self, field: impl Fn(Vector2<f64>) -> Vector2<f64>, delta_time: f64) {
+        for citizen in self.citizens.values_mut() {
+            citizen.velocity += field(citizen.position) * delta_time;
+        }
+        for business in self.businesses.values_mut() {
+            business.velocity += field(business.position) * delta_time;
+        }
+        for government in self.government.values_mut() {
+            government.velocity += field(government.position) * delta_time;
+        }
+    }
+    
+    /// Replace every agent's position with the constrained one
+    pub fn constrain_positions(&mut self, constrain: impl Fn(Vector2<f64>) -> Vector2<f64>) {
+        for citizen in self.citizens.values_mut() {
+            citizen.position = constrain(citizen.position);
+        }
+        for business in self.businesses.values_mut() {
+            business.position = constrain(business.position);
+        }
+        for government in self.government.values_mut() {
+            government.position = constrain(government.position);
+        }
+    }
+    
+    /// Let `constrain` adjust every agent's position and velocity in place
+    pub fn constrain_motion(&mut self, constrain: impl Fn(&mut Vector2<f64>, &mut Vector2<f64>)) {
+        for citizen in self.citizens.values_mut() {
+            constrain(&mut citizen.position, &mut citizen.velocity);
+        }
+        for business in self.businesses.values_mut() {
+            constrain(&mut business.position, &mut business.velocity);
+        }
+        for government in self.government.values_mut() {
+            constrain(&mut government.position, &mut government.velocity);
+        }
+    }
+    
     /// Apply boundary constraints
-    pub fn apply_boundary_constraints(&mut self, width: f64, height: f64) {
+    ///
+    /// Agents touching a wall lose `wall_friction` (0 to 1) of the velocity component
+    /// pointing into it; the component along the wall is kept.
+    pub fn apply_boundary_constraints(&mut self, width: f64, height: f64, wall_friction: f64) {
+        let retained = 1.0 - wall_friction.clamp(0.0, 1.0);
+        
         // Constrain citizens
         for citizen in self.citizens.values_mut() {
-            citizen.position.x = citizen.position.x.max(0.0).min(width);
-            citizen.position.y = citizen.position.y.max(0.0).min(height);
+            clamp_to_walls(&mut citizen.position, &mut citizen.velocity, width, height, retained);
         }
         
         // Constrain businesses
         for business in self.businesses.values_mut() {
-            business.position.x = business.position.x.max(0.0).min(width);
-            business.position.y = business.position.y.max(0.0).min(height);
+            clamp_to_walls(&mut business.position, &mut business.velocity, width, height, retained);
         }
         
         // Constrain government
         for government in self.government.values_mut() {
-            government.position.x = government.position.x.max(0.0).min(width);
-            government.position.y = government.position.y.max(0.0).min(height);
+            clamp_to_walls(&mut government.position, &mut government.velocity, width, height, retained);
         }
     }
     
     /// Handle collisions between agents
-    pub fn handle_collisions(&mut self, collision_radius: f64) {
-        // Simple collision handling - just separate overlapping agents
-        let mut positions: Vec<(u32, Vector2<f64>)> = Vec::new();
-        
-        // Collect all positions
-        for citizen in self.citizens.values() {
-            positions.push((citizen.id, citizen.position));
+    ///
+    /// Small populations are scanned by brute force, which avoids the grid overhead;
+    /// from `collision_grid_threshold` agents on the spatial grid is used. Both give
+    /// identical results.
+    pub fn handle_collisions(&mut self) {
+        if self.get_agent_count() as usize >= self.collision_grid_threshold {
+            self.handle_collisions_grid();
+        } else {
+            self.handle_collisions_brute_force();
         }
-        for business in self.businesses.values() {
-            positions.push((business.id, business.position));
+    }
+    
+    /// Handle collisions using a spatial grid
+    ///
+    /// Agents are bucketed into a grid of cells twice the largest radius, so each
+    /// agent is only checked against its own and adjacent cells. Pairs are
+    /// resolved in the same order as the brute-force scan.
+    pub fn handle_collisions_grid(&mut self) {
+        // Bodies are sorted by id so separation is independent of HashMap iteration order
+        let positions = self.collision_bodies();
+        let max_radius = positions.iter().map(|body| body.2).fold(0.0, f64::max);
+        let cell_size = (max_radius * 2.0).max(f64::EPSILON);
+        self.collision_checks = 0;
+        self.last_collision_strategy = CollisionStrategy::Grid;
+        
+        let cell_of = |position: &Vector2<f64>| {
+            ((position.x / cell_size).floor() as i64, (position.y / cell_size).floor() as i64)
+        };
+        
+        let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (index, (_, position, _)) in positions.iter().enumerate() {
+            grid.entry(cell_of(position)).or_default().push(index);
         }
-        for government in self.government.values() {
-            positions.push((government.id, government.position));
+        
+        let mut candidates = Vec::new();
+        for i in 0..positions.len() {
+            let (cell_x, cell_y) = cell_of(&positions[i].1);
+            
+            candidates.clear();
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    if let Some(indices) = grid.get(&(cell_x + dx, cell_y + dy)) {
+                        candidates.extend(indices.iter().copied().filter(|&j| j > i));
+                    }
+                }
+            }
+            candidates.sort_unstable();
+            
+            for &j in &candidates {
+                self.resolve_collision(positions[i], positions[j]);
+            }
         }
+    }
+    
+    /// Handle collisions by checking every pair of agents (O(n²))
+    pub fn handle_collisions_brute_force(&mut self) {
+        let positions = self.collision_bodies();
+        self.collision_checks = 0;
+        self.last_collision_strategy = CollisionStrategy::BruteForce;
         
-        // Check for collisions and separate
         for i in 0..positions.len() {
             for j in i+1..positions.len() {
-                let (id1, pos1) = positions[i];
-                let (id2, pos2) = positions[j];
-                
-                let distance = (pos2 - pos1).magnitude();
-                if distance < collision_radius * 2.0 {
-                    // Separate agents
-                    let separation = (collision_radius * 2.0 - distance) / 2.0;
-                    let direction = (pos2 - pos1).normalize();
-                    
-                    // Apply separation to both agents
-                    if let Some(citizen) = self.citizens.get_mut(&id1) {
-                        citizen.position -= direction * separation;
-                    }
-                    if let Some(business) = self.businesses.get_mut(&id1) {
-                        business.position -= direction * separation;
-                    }
-                    if let Some(government) = self.government.get_mut(&id1) {
-                        government.position -= direction * separation;
-                    }
-                    
-                    if let Some(citizen) = self.citizens.get_mut(&id2) {
-                        citizen.position += direction * separation;
-                    }
-                    if let Some(business) = self.businesses.get_mut(&id2) {
-                        business.position += direction * separation;
-                    }
-                    if let Some(government) = self.government.get_mut(&id2) {
-                        government.position += direction * separation;
-                    }
+                self.resolve_collision(positions[i], positions[j]);
+            }
+        }
+    }
+    
+    /// Bodies in id order with radii scaled by `collision_radius_scale`
+    fn collision_bodies(&self) -> Vec<(u32, Vector2<f64>, f64)> {
+        let scale = self.collision_radius_scale;
+        self.get_all_bodies()
+            .into_iter()
+            .map(|(id, position, radius)| (id, position, radius * scale))
+            .collect()
+    }
+    
+    /// Set the fraction (0 to 1) of approach speed agents keep after colliding
+    ///
+    /// 1 gives elastic collisions; 0 makes colliding agents move together along the
+    /// contact normal. `None`, the default, turns the exchange off: colliding agents
+    /// are only pushed apart and keep their velocities.
+    pub fn set_collision_restitution(&mut self, restitution: Option<f64>) {
+        self.collision_restitution = restitution.map(|restitution| restitution.clamp(0.0, 1.0));
+    }
+    
+    /// Separate two agents if they are closer than the sum of their radii and, with a
+    /// collision restitution set, exchange momentum along the contact normal, treating
+    /// agents as equal masses
+    fn resolve_collision(&mut self, first: (u32, Vector2<f64>, f64), second: (u32, Vector2<f64>, f64)) {
+        let (id1, pos1, radius1) = first;
+        let (id2, pos2, radius2) = second;
+        self.collision_checks += 1;
+        
+        let min_distance = radius1 + radius2;
+        let distance = (pos2 - pos1).magnitude();
+        if distance < min_distance {
+            // Movable agents share the separation; one facing an immovable agent takes all of it
+            let share = match (self.is_movable(id1), self.is_movable(id2)) {
+                (true, true) => (0.5, 0.5),
+                (true, false) => (1.0, 0.0),
+                (false, true) => (0.0, 1.0),
+                (false, false) => return,
+            };
+            let overlap = min_distance - distance;
+            let direction = (pos2 - pos1).normalize();
+            
+            // Apply separation
+            self.displace_agent(id1, -direction * overlap * share.0);
+            self.displace_agent(id2, direction * overlap * share.1);
+            
+            // Only agents moving toward each other exchange momentum
+            let Some(restitution) = self.collision_restitution else {
+                return;
+            };
+            if let (Some(velocity1), Some(velocity2)) = (self.velocity_of(id1), self.velocity_of(id2)) {
+                let approach_speed = (velocity2 - velocity1).dot(&direction);
+                if approach_speed < 0.0 {
+                    let impulse = direction * (-(1.0 + restitution) * approach_speed);
+                    self.set_velocity(id1, velocity1 - impulse * share.0);
+                    self.set_velocity(id2, velocity2 + impulse * share.1);
                 }
             }
         }
     }
     
+    fn velocity_of(&self, id: u32) -> Option<Vector2<f64>> {
+        self.citizens.get(&id).map(|citizen| citizen.velocity)
+            .or_else(|| self.businesses.get(&id).map(|business| business.velocity))
+            .or_else(|| self.government.get(&id).map(|government| government.velocity))
+    }
+    
+    fn set_velocity(&mut self, id: u32, velocity: Vector2<f64>) {
+        if let Some(citizen) = self.citizens.get_mut(&id) {
+            citizen.velocity = velocity;
+        } else if let Some(business) = self.businesses.get_mut(&id) {
+            business.velocity = velocity;
+        } else if let Some(government) = self.government.get_mut(&id) {
+            government.velocity = velocity;
+        }
+    }
+    
+    /// Move an agent of any type by the given offset
+    fn displace_agent(&mut self, id: u32, offset: Vector2<f64>) {
+        if let Some(citizen) = self.citizens.get_mut(&id) {
+            citizen.position += offset;
+        } else if let Some(business) = self.businesses.get_mut(&id) {
+            business.position += offset;
+        } else if let Some(government) = self.government.get_mut(&id) {
+            government.position += offset;
+        }
+    }
+    
+    /// Number of pairwise distance checks made by the last collision pass
+    pub fn get_collision_checks(&self) -> u64 {
+        self.collision_checks
+    }
+    
+    /// Only re-check interactions of agents that moved more than `move_threshold`
+    ///
+    /// Passing `None` returns to recomputing every pair each cycle.
+    pub fn set_incremental_interactions(&mut self, move_threshold: Option<f64>) {
+        self.interaction_cache = move_threshold.map(InteractionCache::new);
+        self.calculate_interactions();
+    }
+    
+    /// Number of citizen-business distance checks made by the last interaction pass
+    pub fn get_interaction_checks(&self) -> u64 {
+        self.interaction_checks
+    }
+    
     /// Calculate interactions between agents
+    ///
+    /// Each interaction type uses its own radius from `interaction_config`. The
+    /// incremental cache, when enabled, only applies to economic interactions.
+    #[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
     fn calculate_interactions(&mut self) {
-        self.interaction_count = 0;
+        if let Some(mut cache) = self.interaction_cache.take() {
+            self.update_interactions_incremental(&mut cache);
+            self.interaction_cache = Some(cache);
+        } else {
+            self.interaction_count = 0;
+            self.interaction_checks = 0;
+            
+            // Count interactions between citizens and businesses
+            for citizen in self.citizens.values() {
+                for business in self.businesses.values() {
+                    self.interaction_checks += 1;
+                    let distance = (business.position - citizen.position).magnitude();
+                    if self.interaction_config.in_range(InteractionType::Economic, distance) {
+                        self.interaction_count += 1;
+                    }
+                }
+            }
+        }
+        
+        self.calculate_interaction_matrix();
+    }
+    
+    /// Count pairs of agents within `interaction_radius`, per pair of agent types, and
+    /// pairs of citizens within the social radius
+    ///
+    /// Agents are bucketed into a grid of cells as wide as the larger of the two radii,
+    /// so each agent is only checked against its own and adjacent cells.
+    fn calculate_interaction_matrix(&mut self) {
+        let radius = self.interaction_config.radius_for(InteractionType::Proximity)
+            .max(self.interaction_config.radius_for(InteractionType::Social));
+        let mut agents: Vec<(u32, Vector2<f64>, AgentType)> = self.agent_views()
+            .map(|view| (view.id(), view.position(), view.agent_type()))
+            .collect();
+        agents.sort_by_key(|(id, _, _)| *id);
+        self.interaction_matrix = InteractionMatrix::default();
+        self.social_interaction_count = 0;
+        if radius <= 0.0 {
+            return;
+        }
+        
+        let cell_of = |position: &Vector2<f64>| {
+            ((position.x / radius).floor() as i64, (position.y / radius).floor() as i64)
+        };
+        let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (index, (_, position, _)) in agents.iter().enumerate() {
+            grid.entry(cell_of(position)).or_default().push(index);
+        }
+        
+        for (i, (_, position, agent_type)) in agents.iter().enumerate() {
+            let (cell_x, cell_y) = cell_of(position);
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let Some(indices) = grid.get(&(cell_x + dx, cell_y + dy)) else {
+                        continue;
+                    };
+                    for &j in indices.iter().filter(|&&j| j > i) {
+                        let (_, other_position, other_type) = &agents[j];
+                        let distance = (other_position - position).magnitude();
+                        if self.interaction_config.in_range(InteractionType::Proximity, distance) {
+                            *self.interaction_matrix.slot_mut(agent_type, other_type) += 1;
+                        }
+                        if *agent_type == AgentType::Citizen
+                            && *other_type == AgentType::Citizen
+                            && self.interaction_config.in_range(InteractionType::Social, distance)
+                        {
+                            self.social_interaction_count += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    
+    /// Re-check only the pairs involving agents that moved beyond the cache threshold
+    fn update_interactions_incremental(&mut self, cache: &mut InteractionCache) {
+        let radius = self.interaction_config.radius_for(InteractionType::Economic);
+        if cache.radius != radius {
+            cache.radius = radius;
+            cache.anchors.clear();
+            cache.pairs.clear();
+        }
+        
+        // Forget agents that left the simulation
+        cache.anchors.retain(|id, _| self.citizens.contains_key(id) || self.businesses.contains_key(id));
+        cache.pairs.retain(|(citizen_id, business_id)| {
+            self.citizens.contains_key(citizen_id) && self.businesses.contains_key(business_id)
+        });
+        
+        // New agents have no anchor and always count as moved
+        let mut moved = HashSet::new();
+        let positions = self.citizens.values().map(|c| (c.id, c.position))
+            .chain(self.businesses.values().map(|b| (b.id, b.position)));
+        for (id, position) in positions {
+            let stale = match cache.anchors.get(&id) {
+                Some(anchor) => (position - anchor).magnitude() > cache.move_threshold,
+                None => true,
+            };
+            if stale {
+                cache.anchors.insert(id, position);
+                moved.insert(id);
+            }
+        }
         
-        // Count interactions between citizens and businesses
+        self.interaction_checks = 0;
         for citizen in self.citizens.values() {
+            let citizen_moved = moved.contains(&citizen.id);
             for business in self.businesses.values() {
-                let distance = (business.position - citizen.position).magnitude();
-                if distance < 20.0 { // Interaction radius
-                    self.interaction_count += 1;
+                if !citizen_moved && !moved.contains(&business.id) {
+                    continue;
+                }
+                
+                self.interaction_checks += 1;
+                let pair = (citizen.id, business.id);
+                if self.interaction_config.in_range(InteractionType::Economic, (business.position - citizen.position).magnitude()) {
+                    cache.pairs.insert(pair);
+                } else {
+                    cache.pairs.remove(&pair);
                 }
             }
         }
+        
+        self.interaction_count = cache.pairs.len() as u32;
+    }
+    
+    /// Apply the economic effect of citizen-business interactions
+    ///
+    /// Every pair in range earns the business `revenue_per_interaction`, and citizens
+    /// with unmet "goods" need also buy, moving `purchase_price` from their money to
+    /// the business revenue. Pairs are visited in id order so results are reproducible.
+    fn process_transactions(&mut self) {
+        let config = self.interaction_config.clone();
+        let business_ids = Self::sorted_ids(&self.businesses);
+        self.transaction_volume = 0.0;
+        self.customer_matches.clear();
+        self.interaction_rewards.clear();
+        
+        for citizen_id in Self::sorted_ids(&self.citizens) {
+            let Some(citizen) = self.citizens.get_mut(&citizen_id) else {
+                continue;
+            };
+            
+            // Businesses in range, in the order the matching rule tries them
+            let mut candidates: Vec<(u32, f64)> = business_ids.iter()
+                .map(|id| (*id, (self.businesses[id].position - citizen.position).magnitude()))
+                .filter(|(_, distance)| config.in_range(InteractionType::Economic, *distance))
+                .collect();
+            if config.matching == CustomerMatching::NearestFirst {
+                candidates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            }
+            
+            for (business_id, _) in candidates {
+                let business = self.businesses.get_mut(&business_id).unwrap();
+                if business.in_stockout() {
+                    continue;
+                }
+                business.earn(config.revenue_per_interaction);
+                
+                let goods_need = citizen.needs.get("goods").copied().unwrap_or(0.0);
+                if goods_need >= config.need_threshold && citizen.money >= config.purchase_price {
+                    *business.products.entry(GOODS_PRODUCT.to_string()).or_insert(0.0) -= 1.0;
+                    citizen.money -= config.purchase_price;
+                    citizen.needs.insert("goods".to_string(), (goods_need - config.need_satisfaction).max(0.0));
+                    business.earn(config.purchase_price);
+                    business.customers += 1;
+                    self.transaction_volume += config.purchase_price;
+                    self.customer_matches.push((citizen_id, business_id));
+                    
+                    // Each side gains its own reward and energy from the purchase
+                    let customer = config.effect_for(InteractionRole::Customer);
+                    let seller = config.effect_for(InteractionRole::Seller);
+                    citizen.energy = (citizen.energy + customer.energy).max(0.0);
+                    business.energy = (business.energy + seller.energy).max(0.0);
+                    *self.interaction_rewards.entry(citizen_id).or_insert(0.0) += customer.reward;
+                    *self.interaction_rewards.entry(business_id).or_insert(0.0) += seller.reward;
+                }
+            }
+        }
+    }
+    
+    /// Reward an agent earned from purchases in the last cycle
+    pub fn get_interaction_reward(&self, id: u32) -> f64 {
+        self.interaction_rewards.get(&id).copied().unwrap_or(0.0)
+    }
+    
+    /// Total money exchanged in the last cycle
+    pub fn get_transaction_volume(&self) -> f64 {
+        self.transaction_volume
     }
     
     /// Get total number of agents
@@ -348,34 +1848,54 @@ impl AgentEngine {
         self.government.len() as u32
     }
     
-    /// Get interaction count
+    /// Get the number of citizen-business (economic) interactions
     pub fn get_interaction_count(&self) -> u32 {
         self.interaction_count
     }
     
+    /// Pairs of agents in proximity range from the last cycle, per pair of agent types
+    pub fn get_interaction_matrix(&self) -> InteractionMatrix {
+        self.interaction_matrix
+    }
+    
+    /// Get the number of citizen-citizen (social) interactions
+    pub fn get_social_interaction_count(&self) -> u32 {
+        self.social_interaction_count
+    }
+    
+    /// Histogram of energy across all agents over [0, 100]
+    pub fn energy_histogram(&self, bins: usize) -> Vec<usize> {
+        let energies = self.citizens.values().map(|citizen| citizen.energy)
+            .chain(self.businesses.values().map(|business| business.energy))
+            .chain(self.government.values().map(|government| government.energy));
+        histogram(energies, bins, 0.0, 100.0)
+    }
+    
+    /// Histogram of business revenue over [0, highest revenue]
+    pub fn revenue_histogram(&self, bins: usize) -> Vec<usize> {
+        let max_revenue = self.businesses.values().map(|business| business.revenue).fold(0.0, f64::max);
+        histogram(self.businesses.values().map(|business| business.revenue), bins, 0.0, max_revenue)
+    }
+    
+    /// Histogram of government approval rating over [0, 1]
+    pub fn approval_histogram(&self, bins: usize) -> Vec<usize> {
+        histogram(self.government.values().map(|government| government.approval_rating), bins, 0.0, 1.0)
+    }
+    
     /// Get average energy of all agents
+    ///
+    /// Energies are summed in id order, so the result does not depend on map iteration order.
     pub fn get_average_energy(&self) -> f64 {
-        let mut total_energy = 0.0;
-        let mut count = 0;
-        
-        for citizen in self.citizens.values() {
-            total_energy += citizen.energy;
-            count += 1;
-        }
-        for business in self.businesses.values() {
-            total_energy += business.energy;
-            count += 1;
-        }
-        for government in self.government.values() {
-            total_energy += government.energy;
-            count += 1;
+        let mut energies: Vec<(u32, f64)> = self.agent_views()
+            .map(|view| (view.id(), view.energy()))
+            .collect();
+        if energies.is_empty() {
+            return 0.0;
         }
         
-        if count > 0 {
-            total_energy / count as f64
-        } else {
-            0.0
-        }
+        energies.sort_unstable_by_key(|(id, _)| *id);
+        let total_energy: f64 = energies.iter().map(|(_, energy)| energy).sum();
+        total_energy / energies.len() as f64
     }
     
     /// Get all agent positions
@@ -392,9 +1912,161 @@ impl AgentEngine {
             positions.push((government.id, government.position));
         }
         
+        // Stable order keeps downstream float accumulations reproducible
+        positions.sort_by_key(|(id, _)| *id);
         positions
     }
     
+    /// Get id, position and collision radius of all agents, sorted by id
+    pub fn get_all_bodies(&self) -> Vec<(u32, Vector2<f64>, f64)> {
+        let mut bodies: Vec<(u32, Vector2<f64>, f64)> = self.citizens.values()
+            .map(|citizen| (citizen.id, citizen.position, citizen.radius))
+            .chain(self.businesses.values().map(|business| (business.id, business.position, business.radius)))
+            .chain(self.government.values().map(|government| (government.id, government.position, government.radius)))
+            .collect();
+        
+        bodies.sort_by_key(|(id, _, _)| *id);
+        bodies
+    }
+    
+    /// Iterate over all agents through a unified read-only view
+    pub fn agent_views(&self) -> impl Iterator<Item = AgentView<'_>> {
+        self.citizens.values().map(AgentView::Citizen)
+            .chain(self.businesses.values().map(AgentView::Business))
+            .chain(self.government.values().map(AgentView::Government))
+    }
+    
+    /// Ids of all agents matching a predicate, sorted ascending
+    pub fn find_agents(&self, predicate: impl Fn(&AgentView) -> bool) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.agent_views()
+            .filter(|view| predicate(view))
+            .map(|view| view.id())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+    
+    /// Get the position of an agent of any type
+    pub fn get_agent_position(&self, id: u32) -> Option<Vector2<f64>> {
+        self.citizens.get(&id).map(|citizen| citizen.position)
+            .or_else(|| self.businesses.get(&id).map(|business| business.position))
+            .or_else(|| self.government.get(&id).map(|government| government.position))
+    }
+    
+    /// Get agents within `radius` of an agent, sorted by distance then by id
+    ///
+    /// The agent itself is excluded; an unknown id yields no neighbors.
+    pub fn sorted_neighbors(&self, id: u32, radius: f64) -> Vec<(u32, f64)> {
+        let Some(center) = self.get_agent_position(id) else {
+            return Vec::new();
+        };
+        
+        let mut neighbors: Vec<(u32, f64)> = self.get_all_positions()
+            .into_iter()
+            .filter(|(other_id, _)| *other_id != id)
+            .map(|(other_id, position)| (other_id, (position - center).magnitude()))
+            .filter(|(_, distance)| *distance <= radius)
+            .collect();
+        
+        neighbors.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        neighbors
+    }
+    
+    /// Limit perception to a cone of `angle` radians around each agent's heading
+    ///
+    /// The angle is clamped to [0, 2π]; None restores omnidirectional perception.
+    pub fn set_field_of_view(&mut self, angle: Option<f64>) {
+        self.field_of_view = angle.map(|angle| angle.clamp(0.0, std::f64::consts::TAU));
+    }
+    
+    /// Whether an agent at `position` moving with `velocity` perceives `target`
+    ///
+    /// Stationary agents have no heading and perceive all around.
+    pub fn in_field_of_view(&self, position: Vector2<f64>, velocity: Vector2<f64>, target: Vector2<f64>) -> bool {
+        let Some(angle) = self.field_of_view else {
+            return true;
+        };
+        let (Some(heading), Some(direction)) = (
+            velocity.try_normalize(f64::EPSILON),
+            (target - position).try_normalize(f64::EPSILON),
+        ) else {
+            return true;
+        };
+        heading.dot(&direction) >= (angle / 2.0).cos()
+    }
+    
+    /// Agents within `radius` of an agent that it perceives, sorted by distance then by id
+    ///
+    /// Like `sorted_neighbors`, restricted to the agent's field of view.
+    pub fn perceived_neighbors(&self, id: u32, radius: f64) -> Vec<(u32, f64)> {
+        let (Some(position), Some(velocity)) = (self.get_agent_position(id), self.velocity_of(id)) else {
+            return Vec::new();
+        };
+        
+        self.sorted_neighbors(id, radius)
+            .into_iter()
+            .filter(|(other_id, _)| {
+                self.get_agent_position(*other_id)
+                    .is_some_and(|other| self.in_field_of_view(position, velocity, other))
+            })
+            .collect()
+    }
+    
+    /// Get the full state of a single agent for Python
+    pub fn get_agent_detail(&self, id: u32) -> Option<crate::AgentDetail> {
+        if let Some(citizen) = self.citizens.get(&id) {
+            return Some(crate::AgentDetail {
+                id,
+                agent_type: "citizen".to_string(),
+                x: citizen.position.x,
+                y: citizen.position.y,
+                velocity_x: citizen.velocity.x,
+                velocity_y: citizen.velocity.y,
+                energy: citizen.energy,
+                personality: Some(citizen.personality.clone()),
+                revenue: None,
+                customers: None,
+                stock: None,
+                budget: None,
+                approval_rating: None,
+            });
+        }
+        
+        if let Some(business) = self.businesses.get(&id) {
+            return Some(crate::AgentDetail {
+                id,
+                agent_type: "business".to_string(),
+                x: business.position.x,
+                y: business.position.y,
+                velocity_x: business.velocity.x,
+                velocity_y: business.velocity.y,
+                energy: business.energy,
+                personality: None,
+                revenue: Some(business.revenue),
+                customers: Some(business.customers),
+                stock: Some(business.products.clone()),
+                budget: None,
+                approval_rating: None,
+            });
+        }
+        
+        self.government.get(&id).map(|government| crate::AgentDetail {
+            id,
+            agent_type: "government".to_string(),
+            x: government.position.x,
+            y: government.position.y,
+            velocity_x: government.velocity.x,
+            velocity_y: government.velocity.y,
+            energy: government.energy,
+            personality: None,
+            revenue: None,
+            customers: None,
+            stock: None,
+            budget: Some(government.budget),
+            approval_rating: Some(government.approval_rating),
+        })
+    }
+    
     /// Get agent positions for Python
     pub fn get_positions(&self) -> Vec<crate::AgentPosition> {
         let mut positions = Vec::new();
@@ -437,4 +2109,991 @@ impl AgentEngine {
         
         positions
     }
+    
+    /// Agent state as an `[n, 4]` matrix of (x, y, energy, type code) plus the row ids
+    ///
+    /// Rows follow the same order as `get_positions`.
+    pub fn position_matrix(&self) -> (Array2<f64>, Vec<u32>) {
+        let views: Vec<AgentView> = self.agent_views().collect();
+        let mut matrix = Array2::zeros((views.len(), 4));
+        let mut ids = Vec::with_capacity(views.len());
+        
+        for (row, view) in views.iter().enumerate() {
+            let position = view.position();
+            matrix[[row, 0]] = position.x;
+            matrix[[row, 1]] = position.y;
+            matrix[[row, 2]] = view.energy();
+            matrix[[row, 3]] = view.agent_type().code();
+            ids.push(view.id());
+        }
+        
+        (matrix, ids)
+    }
+}
+
+/// Clamp a position to the city and scale the velocity component into each touched wall by `retained`
+fn clamp_to_walls(position: &mut Vector2<f64>, velocity: &mut Vector2<f64>, width: f64, height: f64, retained: f64) {
+    for (axis, limit) in [width, height].into_iter().enumerate() {
+        if position[axis] <= 0.0 {
+            position[axis] = 0.0;
+            if velocity[axis] < 0.0 {
+                velocity[axis] *= retained;
+            }
+        } else if position[axis] >= limit {
+            position[axis] = limit;
+            if velocity[axis] > 0.0 {
+                velocity[axis] *= retained;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::math::{mean, std_dev};
+
+    fn cluster_engine() -> AgentEngine {
+        let mut engine = AgentEngine::new();
+        engine.add_citizen(50.0, 50.0, HashMap::new());
+        engine.add_business(53.0, 51.0, "shop".to_string());
+        engine.add_government(51.0, 54.0, HashMap::new());
+        engine
+    }
+
+    #[test]
+    fn test_collision_resolution_is_deterministic() {
+        let mut first = cluster_engine();
+        let mut second = cluster_engine();
+
+        first.handle_collisions();
+        second.handle_collisions();
+
+        let mut first_positions = first.get_all_positions();
+        let mut second_positions = second.get_all_positions();
+        first_positions.sort_by_key(|(id, _)| *id);
+        second_positions.sort_by_key(|(id, _)| *id);
+
+        assert_eq!(first_positions, second_positions);
+    }
+    
+    #[test]
+    fn test_economic_cycle_boom_outpaces_bust() {
+        let mut engine = AgentEngine::new();
+        let business_id = engine.add_business(10.0, 10.0, "shop".to_string());
+        engine.economic_cycle = Some(EconomicCycle::new(20.0, 0.5));
+        
+        let mut boom_growth = Vec::new();
+        let mut bust_growth = Vec::new();
+        for _ in 0..40 {
+            let activity = engine.get_economic_activity();
+            let before = engine.businesses[&business_id].revenue;
+            engine.process_cycle(1.0);
+            let growth = engine.businesses[&business_id].revenue - before;
+            
+            if activity > 1.0 {
+                boom_growth.push(growth);
+            } else if activity < 1.0 {
+                bust_growth.push(growth);
+            }
+        }
+        
+        let boom_avg = boom_growth.iter().sum::<f64>() / boom_growth.len() as f64;
+        let bust_avg = bust_growth.iter().sum::<f64>() / bust_growth.len() as f64;
+        assert!(boom_avg > bust_avg);
+    }
+    
+    #[test]
+    fn test_remove_agent() {
+        let mut engine = AgentEngine::new();
+        let citizen_id = engine.add_citizen(10.0, 10.0, HashMap::new());
+        let business_id = engine.add_business(15.0, 10.0, "shop".to_string());
+        engine.add_government(100.0, 100.0, HashMap::new());
+        engine.process_cycle(0.0);
+        assert_eq!(engine.get_interaction_count(), 1);
+        
+        assert!(engine.remove_agent(business_id));
+        assert!(!engine.remove_agent(business_id));
+        
+        assert_eq!(engine.get_agent_count(), 2);
+        assert_eq!(engine.get_interaction_count(), 0);
+        assert!(engine.get_positions().iter().all(|p| p.id != business_id));
+        assert!(engine.get_positions().iter().any(|p| p.id == citizen_id));
+    }
+    
+    #[test]
+    fn test_disabled_type_is_not_processed() {
+        let mut engine = AgentEngine::new();
+        let citizen_id = engine.add_citizen(10.0, 10.0, HashMap::new());
+        let business_id = engine.add_business(200.0, 200.0, "shop".to_string());
+        engine.set_processing_enabled(AgentType::Business, false);
+        
+        let business_before = engine.businesses[&business_id].clone();
+        engine.process_cycle(1.0);
+        let business_after = &engine.businesses[&business_id];
+        
+        assert_eq!(business_after.energy, business_before.energy);
+        assert_eq!(business_after.revenue, business_before.revenue);
+        assert_eq!(business_after.velocity, business_before.velocity);
+        assert!(engine.citizens[&citizen_id].energy < 100.0);
+    }
+    
+    #[test]
+    fn test_grid_collisions_match_brute_force() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut grid_engine = AgentEngine::with_seed(7);
+        for _ in 0..5000 {
+            let x = rng.gen_range(0.0..1000.0);
+            let y = rng.gen_range(0.0..1000.0);
+            grid_engine.add_citizen(x, y, HashMap::new());
+        }
+        let mut brute_engine = grid_engine.clone();
+        
+        grid_engine.handle_collisions_grid();
+        brute_engine.handle_collisions_brute_force();
+        
+        assert_eq!(grid_engine.get_all_positions(), brute_engine.get_all_positions());
+        assert!(grid_engine.get_collision_checks() * 100 < brute_engine.get_collision_checks());
+    }
+    
+    #[test]
+    fn test_depleted_agents_removed_after_grace_period() {
+        let mut engine = AgentEngine::new();
+        engine.death_grace_cycles = 3;
+        let citizen_id = engine.add_citizen(10.0, 10.0, HashMap::new());
+        let government_id = engine.add_government(200.0, 200.0, HashMap::new());
+        engine.government.get_mut(&government_id).unwrap().energy = 0.0;
+        assert!(engine.set_agent_seed(citizen_id, 7));
+        assert!(engine.start_recording(citizen_id));
+        
+        // A single long cycle drains the citizen completely
+        assert!(engine.process_cycle(1000.0).is_empty());
+        assert_eq!(engine.citizens[&citizen_id].energy, 0.0);
+        
+        for _ in 0..2 {
+            assert!(engine.process_cycle(1.0).is_empty());
+        }
+        assert_eq!(engine.process_cycle(1.0), vec![citizen_id]);
+        
+        assert!(!engine.citizens.contains_key(&citizen_id));
+        assert!(engine.government.contains_key(&government_id));
+        assert!(engine.depleted_cycles.is_empty());
+        assert!(engine.agent_seeds.is_empty());
+        assert!(engine.recordings.is_empty());
+    }
+    
+    #[test]
+    fn test_sorted_neighbors_breaks_ties_by_id() {
+        let mut engine = AgentEngine::new();
+        let center = engine.add_citizen(100.0, 100.0, HashMap::new());
+        let far = engine.add_business(100.0, 130.0, "shop".to_string());
+        let east = engine.add_citizen(110.0, 100.0, HashMap::new());
+        let west = engine.add_citizen(90.0, 100.0, HashMap::new());
+        let near = engine.add_government(103.0, 104.0, HashMap::new());
+        engine.add_citizen(300.0, 300.0, HashMap::new());
+        
+        let neighbors = engine.sorted_neighbors(center, 40.0);
+        let ids: Vec<u32> = neighbors.iter().map(|(id, _)| *id).collect();
+        
+        assert_eq!(ids, vec![near, east, west, far]);
+        assert_eq!(neighbors[0].1, 5.0);
+        assert_eq!(neighbors[1].1, neighbors[2].1);
+        assert!(engine.sorted_neighbors(999, 40.0).is_empty());
+    }
+    
+    #[test]
+    fn test_citizen_buys_from_nearby_business() {
+        let mut engine = AgentEngine::new();
+        let citizen_id = engine.add_citizen(100.0, 100.0, HashMap::new());
+        let business_id = engine.add_business(105.0, 100.0, "shop".to_string());
+        engine.set_processing_enabled(AgentType::Business, false);
+        
+        engine.process_cycle(0.0);
+        
+        let citizen = &engine.citizens[&citizen_id];
+        let business = &engine.businesses[&business_id];
+        assert_eq!(citizen.money, 99.0);
+        assert_eq!(business.revenue, 1.0);
+        assert_eq!(business.customers, 1);
+        assert_eq!(engine.get_transaction_volume(), 1.0);
+        assert!((citizen.needs["goods"] - 0.4).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_government_taxes_and_redistributes() {
+        let mut engine = AgentEngine::new();
+        let government_id = engine.add_government(100.0, 100.0, HashMap::from([
+            (policy::TAX_RATE.to_string(), 0.2),
+        ]));
+        engine.set_processing_enabled(AgentType::Business, false);
+        engine.set_processing_enabled(AgentType::Citizen, false);
+        let business_id = engine.add_business(150.0, 100.0, "shop".to_string());
+        let citizen_id = engine.add_citizen(100.0, 160.0, HashMap::new());
+        let business = engine.businesses.get_mut(&business_id).unwrap();
+        business.revenue = 1000.0;
+        business.untaxed_revenue = 50.0;
+        engine.citizens.get_mut(&citizen_id).unwrap().energy = 5.0;
+        let budget_before = engine.government[&government_id].budget;
+        
+        engine.process_cycle(1.0);
+        
+        // Only the revenue earned since the last collection is taxed
+        let government = &engine.government[&government_id];
+        let business = &engine.businesses[&business_id];
+        let citizen = &engine.citizens[&citizen_id];
+        let support = policy::DEFAULT_WELFARE_THRESHOLD - 5.0;
+        assert_eq!(business.revenue, 990.0);
+        assert_eq!(business.untaxed_revenue, 0.0);
+        assert!((government.budget - (budget_before + 10.0 + 10.0 - support)).abs() < 1e-9);
+        assert_eq!(citizen.energy, policy::DEFAULT_WELFARE_THRESHOLD);
+        
+        // Revenue already taxed is left alone on the next collection
+        engine.process_cycle(1.0);
+        assert_eq!(engine.businesses[&business_id].revenue, 990.0);
+    }
+    
+    #[test]
+    fn test_attribute_histograms() {
+        let mut engine = AgentEngine::new();
+        assert_eq!(engine.energy_histogram(4), vec![0, 0, 0, 0]);
+        assert_eq!(engine.revenue_histogram(3), vec![0, 0, 0]);
+        
+        for energy in [5.0, 10.0, 30.0, 55.0, 60.0, 99.0, 100.0] {
+            let id = engine.add_citizen(0.0, 0.0, HashMap::new());
+            engine.citizens.get_mut(&id).unwrap().energy = energy;
+        }
+        assert_eq!(engine.energy_histogram(4), vec![2, 1, 2, 2]);
+        assert_eq!(engine.energy_histogram(0), Vec::<usize>::new());
+        
+        for revenue in [0.0, 10.0, 40.0] {
+            let id = engine.add_business(0.0, 0.0, "shop".to_string());
+            engine.businesses.get_mut(&id).unwrap().revenue = revenue;
+        }
+        assert_eq!(engine.revenue_histogram(2), vec![2, 1]);
+        
+        engine.add_government(0.0, 0.0, HashMap::new());
+        assert_eq!(engine.approval_histogram(2), vec![0, 1]);
+    }
+    
+    #[test]
+    fn test_revenue_proportional_to_interactions() {
+        let mut engine = AgentEngine::new();
+        engine.interaction_config.revenue_per_interaction = 2.5;
+        engine.interaction_config.need_threshold = f64::INFINITY;
+        engine.set_processing_enabled(AgentType::Business, false);
+        let business_id = engine.add_business(100.0, 100.0, "shop".to_string());
+        for i in 0..6 {
+            engine.add_citizen(95.0 + 2.0 * i as f64, 105.0, HashMap::new());
+        }
+        engine.add_citizen(300.0, 300.0, HashMap::new());
+        
+        engine.process_cycle(0.0);
+        
+        assert_eq!(engine.get_interaction_count(), 6);
+        assert_eq!(engine.businesses[&business_id].revenue, 2.5 * 6.0);
+        assert_eq!(engine.get_transaction_volume(), 0.0);
+    }
+    
+    #[test]
+    fn test_collision_separates_to_sum_of_radii() {
+        let mut engine = AgentEngine::new();
+        let business_id = engine.add_business(50.0, 50.0, "mall".to_string());
+        let citizen_id = engine.add_citizen(53.0, 50.0, HashMap::new());
+        
+        engine.handle_collisions();
+        
+        let business = &engine.businesses[&business_id];
+        let citizen = &engine.citizens[&citizen_id];
+        let distance = (citizen.position - business.position).magnitude();
+        assert!((distance - (BUSINESS_RADIUS + CITIZEN_RADIUS)).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_government_updates_every_tenth_cycle() {
+        let mut engine = AgentEngine::new();
+        engine.set_update_interval(AgentType::Government, 10);
+        let citizen_id = engine.add_citizen(10.0, 10.0, HashMap::new());
+        let government_id = engine.add_government(500.0, 500.0, HashMap::new());
+        
+        for cycle in 1..=30 {
+            let budget_before = engine.government[&government_id].budget;
+            let energy_before = engine.citizens[&citizen_id].energy;
+            engine.process_cycle(1.0);
+            
+            let budget_after = engine.government[&government_id].budget;
+            assert!(engine.citizens[&citizen_id].energy < energy_before);
+            if cycle % 10 == 0 {
+                // Skipped cycles are accumulated into the next run
+                assert_eq!(budget_after - budget_before, 10.0 * 10.0);
+            } else {
+                assert_eq!(budget_after, budget_before);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_find_agents_by_energy_across_types() {
+        let mut engine = AgentEngine::new();
+        let tired_citizen = engine.add_citizen(0.0, 0.0, HashMap::new());
+        engine.add_citizen(10.0, 0.0, HashMap::new());
+        let tired_business = engine.add_business(20.0, 0.0, "shop".to_string());
+        engine.add_business(30.0, 0.0, "shop".to_string());
+        let tired_government = engine.add_government(40.0, 0.0, HashMap::new());
+        
+        engine.citizens.get_mut(&tired_citizen).unwrap().energy = 10.0;
+        engine.businesses.get_mut(&tired_business).unwrap().energy = 25.0;
+        engine.government.get_mut(&tired_government).unwrap().energy = 5.0;
+        
+        let tired = engine.find_agents(|agent| agent.energy() < 30.0);
+        assert_eq!(tired, vec![tired_citizen, tired_business, tired_government]);
+        
+        let tired_businesses = engine.find_agents(|agent| {
+            agent.energy() < 30.0 && matches!(agent.agent_type(), AgentType::Business)
+        });
+        assert_eq!(tired_businesses, vec![tired_business]);
+    }
+    
+    #[test]
+    fn test_position_matrix_matches_positions() {
+        let mut engine = AgentEngine::new();
+        engine.add_citizen(1.0, 2.0, HashMap::new());
+        engine.add_citizen(3.0, 4.0, HashMap::new());
+        engine.add_business(5.0, 6.0, "shop".to_string());
+        engine.add_government(7.0, 8.0, HashMap::new());
+        
+        let (matrix, ids) = engine.position_matrix();
+        let positions = engine.get_positions();
+        assert_eq!(matrix.shape(), &[4, 4]);
+        assert_eq!(ids.len(), positions.len());
+        
+        for (row, position) in positions.iter().enumerate() {
+            let code = match position.agent_type.as_str() {
+                "citizen" => 0.0,
+                "business" => 1.0,
+                _ => 2.0,
+            };
+            assert_eq!(ids[row], position.id);
+            assert_eq!(matrix[[row, 0]], position.x);
+            assert_eq!(matrix[[row, 1]], position.y);
+            assert_eq!(matrix[[row, 2]], position.energy);
+            assert_eq!(matrix[[row, 3]], code);
+        }
+    }
+    
+    #[test]
+    fn test_incremental_interactions_match_full_recompute() {
+        let mut engine = AgentEngine::with_seed(3);
+        for i in 0..20 {
+            engine.add_citizen(10.0 * i as f64, 0.0, HashMap::new());
+        }
+        for i in 0..5 {
+            engine.add_business(40.0 * i as f64, 10.0, "shop".to_string());
+        }
+        engine.set_incremental_interactions(Some(0.5));
+        
+        for step in 1..=5 {
+            for citizen in engine.citizens.values_mut() {
+                citizen.position.x += 3.0 * step as f64;
+                citizen.position.y += if citizen.id % 2 == 0 { 2.0 } else { -1.5 };
+            }
+            engine.calculate_interactions();
+            
+            let mut full = engine.clone();
+            full.set_incremental_interactions(None);
+            assert_eq!(engine.get_interaction_count(), full.get_interaction_count());
+        }
+        
+        let count = engine.get_interaction_count();
+        engine.calculate_interactions();
+        assert_eq!(engine.get_interaction_count(), count);
+        assert_eq!(engine.get_interaction_checks(), 0);
+        
+        // Removing an agent drops its cached pairs
+        let business_id = *engine.businesses.keys().next().unwrap();
+        engine.remove_agent(business_id);
+        let mut full = engine.clone();
+        full.set_incremental_interactions(None);
+        assert_eq!(engine.get_interaction_count(), full.get_interaction_count());
+    }
+    
+    #[test]
+    fn test_parallel_processing_matches_serial() {
+        let populate = |engine: &mut AgentEngine| {
+            for i in 0..200 {
+                let personality = HashMap::from([("risk_tolerance".to_string(), (i % 10) as f64 / 10.0)]);
+                engine.add_citizen((i % 20) as f64 * 15.0, (i / 20) as f64 * 15.0, personality);
+            }
+            for i in 0..20 {
+                engine.add_business(i as f64 * 15.0, 150.0, "shop".to_string());
+            }
+        };
+        
+        let mut parallel = AgentEngine::with_seed(11);
+        let mut serial = AgentEngine::with_seed(11);
+        populate(&mut parallel);
+        populate(&mut serial);
+        serial.set_parallel_processing(false);
+        
+        for _ in 0..30 {
+            parallel.process_cycle(1.0);
+            serial.process_cycle(1.0);
+        }
+        
+        let decisions = |engine: &AgentEngine| -> usize {
+            engine.citizens.values().map(|citizen| citizen.decisions.len()).sum()
+        };
+        let velocities = |engine: &AgentEngine| {
+            let mut velocities: Vec<(u32, f64, f64)> = engine.citizens.values()
+                .map(|citizen| (citizen.id, citizen.velocity.x, citizen.velocity.y))
+                .collect();
+            velocities.sort_by_key(|(id, _, _)| *id);
+            velocities
+        };
+        assert!(decisions(&parallel) > 0);
+        assert_eq!(decisions(&parallel), decisions(&serial));
+        assert_eq!(velocities(&parallel), velocities(&serial));
+        assert_eq!(parallel.get_average_energy(), serial.get_average_energy());
+    }
+    
+    #[test]
+    fn test_collision_strategy_switches_at_threshold() {
+        let mut rng = StdRng::seed_from_u64(13);
+        let mut engine = AgentEngine::with_seed(13);
+        engine.collision_grid_threshold = 40;
+        for _ in 0..39 {
+            engine.add_citizen(rng.gen_range(0.0..60.0), rng.gen_range(0.0..60.0), HashMap::new());
+        }
+        
+        engine.handle_collisions();
+        assert_eq!(engine.last_collision_strategy, CollisionStrategy::BruteForce);
+        
+        engine.add_citizen(30.0, 30.0, HashMap::new());
+        let mut grid_engine = engine.clone();
+        let mut brute_engine = engine.clone();
+        
+        engine.handle_collisions();
+        assert_eq!(engine.last_collision_strategy, CollisionStrategy::Grid);
+        
+        grid_engine.handle_collisions_grid();
+        brute_engine.handle_collisions_brute_force();
+        assert_eq!(grid_engine.get_all_positions(), brute_engine.get_all_positions());
+        assert_eq!(engine.get_all_positions(), brute_engine.get_all_positions());
+    }
+    
+    #[test]
+    fn test_pinned_agent_seed_is_reproducible() {
+        let personality = HashMap::from([
+            ("risk_tolerance".to_string(), 0.7),
+            ("social_preference".to_string(), 0.4),
+        ]);
+        
+        let mut first = AgentEngine::with_seed(1);
+        let watched_first = first.add_citizen(50.0, 50.0, personality.clone());
+        
+        // Different engine seed, other agents and a different id for the watched agent
+        let mut second = AgentEngine::with_seed(2);
+        for i in 0..5 {
+            second.add_citizen(10.0 * i as f64, 0.0, HashMap::new());
+        }
+        let watched_second = second.add_citizen(50.0, 50.0, personality);
+        
+        assert_ne!(first.agent_seed(watched_first), second.agent_seed(watched_second));
+        assert!(first.set_agent_seed(watched_first, 1234));
+        assert!(second.set_agent_seed(watched_second, 1234));
+        assert_eq!(first.agent_seed(watched_first), Some(1234));
+        assert!(!first.set_agent_seed(999, 1));
+        assert_eq!(first.agent_seed(999), None);
+        
+        for _ in 0..50 {
+            first.process_cycle(0.1);
+            second.process_cycle(0.1);
+            assert_eq!(first.citizens[&watched_first].velocity, second.citizens[&watched_second].velocity);
+        }
+        assert_eq!(first.citizens[&watched_first].decisions, second.citizens[&watched_second].decisions);
+        assert_eq!(first.citizens[&watched_first].learning_data, second.citizens[&watched_second].learning_data);
+    }
+    
+    #[test]
+    fn test_interaction_types_use_their_own_radius() {
+        let config = InteractionConfig::default();
+        let distance = 12.0;
+        assert!(config.in_range(InteractionType::Economic, distance));
+        assert!(!config.in_range(InteractionType::Social, distance));
+        assert_eq!("Social".parse::<InteractionType>(), Ok(InteractionType::Social));
+        assert!("political".parse::<InteractionType>().is_err());
+        
+        let mut engine = AgentEngine::with_seed(4);
+        engine.add_citizen(100.0, 100.0, HashMap::new());
+        engine.add_citizen(100.0 + distance, 100.0, HashMap::new());
+        engine.add_citizen(100.0, 103.0, HashMap::new());
+        engine.add_business(100.0, 100.0 + distance, "shop".to_string());
+        engine.calculate_interactions();
+        
+        // The business is 12 away from the first citizen: close enough to trade, too far to talk
+        assert_eq!(engine.get_interaction_count(), 3);
+        assert_eq!(engine.get_social_interaction_count(), 1);
+        
+        engine.interaction_config.set_radius(InteractionType::Social, 15.0);
+        engine.calculate_interactions();
+        assert_eq!(engine.get_social_interaction_count(), 3);
+        assert_eq!(engine.get_interaction_count(), 3);
+    }
+    
+    #[test]
+    fn test_social_count_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut engine = AgentEngine::with_seed(11);
+        for _ in 0..400 {
+            engine.add_citizen(rng.gen_range(0.0..300.0), rng.gen_range(0.0..300.0), HashMap::new());
+        }
+        engine.add_business(150.0, 150.0, "shop".to_string());
+        let positions: Vec<Vector2<f64>> = engine.citizens.values().map(|citizen| citizen.position).collect();
+        
+        // Social radius both below and above the proximity radius that sizes the grid
+        for social_radius in [5.0, 40.0] {
+            engine.interaction_config.set_radius(InteractionType::Social, social_radius);
+            engine.calculate_interactions();
+            
+            let mut expected = 0;
+            for (i, first) in positions.iter().enumerate() {
+                expected += positions[i + 1..].iter()
+                    .filter(|second| (*second - first).magnitude() < social_radius)
+                    .count() as u32;
+            }
+            assert!(expected > 0);
+            assert_eq!(engine.get_social_interaction_count(), expected);
+        }
+    }
+    
+    #[test]
+    fn test_trajectory_records_each_cycle() {
+        let mut engine = AgentEngine::with_seed(8);
+        let watched = engine.add_citizen(100.0, 100.0, HashMap::new());
+        engine.add_citizen(300.0, 300.0, HashMap::new());
+        assert!(engine.start_recording(watched));
+        assert!(!engine.start_recording(999));
+        
+        let mut positions = Vec::new();
+        for _ in 0..20 {
+            positions.push(engine.citizens[&watched].position);
+            engine.process_cycle(0.1);
+        }
+        
+        let trajectory = engine.take_trajectory(watched);
+        assert_eq!(trajectory.len(), 20);
+        for ((observation, action), position) in trajectory.iter().zip(&positions) {
+            assert_eq!(observation.len(), 7);
+            assert_eq!((observation[0], observation[1]), (position.x, position.y));
+            assert!(*action < MOVEMENT_ACTIONS);
+        }
+        
+        // Taking a trajectory stops the recording
+        engine.process_cycle(0.1);
+        assert!(engine.take_trajectory(watched).is_empty());
+        
+        // Removing a recorded agent drops its trajectory
+        assert!(engine.start_recording(watched));
+        engine.process_cycle(0.1);
+        assert!(engine.remove_agent(watched));
+        assert!(!engine.recordings.contains_key(&watched));
+    }
+    
+    #[test]
+    fn test_collision_restitution_scales_normal_exchange() {
+        let collide = |restitution: f64| {
+            let mut engine = AgentEngine::new();
+            engine.set_collision_restitution(Some(restitution));
+            let left = engine.add_citizen(50.0, 50.0, HashMap::new());
+            let right = engine.add_citizen(54.0, 50.0, HashMap::new());
+            engine.citizens.get_mut(&left).unwrap().velocity = Vector2::new(3.0, 1.0);
+            engine.citizens.get_mut(&right).unwrap().velocity = Vector2::new(-1.0, 2.0);
+            engine.handle_collisions();
+            (engine.citizens[&left].velocity, engine.citizens[&right].velocity)
+        };
+        
+        // The normal is the x axis: only x components are exchanged
+        let (left, right) = collide(0.0);
+        assert_eq!(left.x, right.x);
+        assert_eq!(left, Vector2::new(1.0, 1.0));
+        assert_eq!(right, Vector2::new(1.0, 2.0));
+        
+        // Elastic: equal masses swap their normal velocities, conserving kinetic energy
+        let (left, right) = collide(1.0);
+        assert_eq!(left, Vector2::new(-1.0, 1.0));
+        assert_eq!(right, Vector2::new(3.0, 2.0));
+        assert_eq!(collide(1.0), collide(7.0));
+        
+        // Without a restitution, the default, colliding agents are only pushed apart
+        let mut engine = AgentEngine::new();
+        let left = engine.add_citizen(50.0, 50.0, HashMap::new());
+        let right = engine.add_citizen(54.0, 50.0, HashMap::new());
+        engine.citizens.get_mut(&left).unwrap().velocity = Vector2::new(3.0, 1.0);
+        engine.handle_collisions();
+        assert_eq!(engine.citizens[&left].velocity, Vector2::new(3.0, 1.0));
+        assert_eq!(engine.citizens[&right].velocity, Vector2::zeros());
+        assert!(engine.citizens[&right].position.x - engine.citizens[&left].position.x > 4.0);
+        
+        // Agents already separating keep their velocities
+        let mut engine = AgentEngine::new();
+        engine.set_collision_restitution(Some(1.0));
+        let left = engine.add_citizen(50.0, 50.0, HashMap::new());
+        engine.add_citizen(54.0, 50.0, HashMap::new());
+        engine.citizens.get_mut(&left).unwrap().velocity = Vector2::new(-2.0, 0.0);
+        engine.handle_collisions();
+        assert_eq!(engine.citizens[&left].velocity, Vector2::new(-2.0, 0.0));
+    }
+    
+    #[test]
+    fn test_spawn_energy_follows_configured_distribution() {
+        let mut engine = AgentEngine::with_seed(11);
+        engine.set_spawn_energy(AgentType::Citizen, 70.0, 12.0);
+        
+        let energies: Vec<f64> = (0..4000)
+            .map(|_| {
+                let id = engine.add_citizen(10.0, 10.0, HashMap::new());
+                engine.citizens[&id].energy
+            })
+            .collect();
+        
+        assert!((mean(&energies).unwrap() - 70.0).abs() < 1.0);
+        assert!((std_dev(&energies).unwrap() - 12.0).abs() < 1.0);
+        
+        // Other types keep the fixed default
+        let business_id = engine.add_business(20.0, 20.0, "shop".to_string());
+        assert_eq!(engine.businesses[&business_id].energy, DEFAULT_SPAWN_ENERGY);
+        
+        // The same seed spawns the same population
+        let mut replay = AgentEngine::with_seed(11);
+        replay.set_spawn_energy(AgentType::Citizen, 70.0, 12.0);
+        let first = replay.add_citizen(10.0, 10.0, HashMap::new());
+        assert_eq!(replay.citizens[&first].energy, energies[0]);
+    }
+    
+    #[test]
+    fn test_hungry_citizen_steers_toward_business() {
+        let mut engine = AgentEngine::with_seed(5);
+        let personality = HashMap::from([(personality::RISK_TOLERANCE.to_string(), 0.2)]);
+        let citizen_id = engine.add_citizen(20.0, 50.0, personality);
+        let business_id = engine.add_business(60.0, 50.0, "shop".to_string());
+        engine.citizens.get_mut(&citizen_id).unwrap().needs.insert("energy".to_string(), 0.9);
+        
+        let start = engine.citizens[&citizen_id].position;
+        for _ in 0..10 {
+            engine.process_cycle(1.0);
+            engine.update_positions(1.0);
+        }
+        
+        let displacement = engine.citizens[&citizen_id].position - start;
+        let toward_business = (engine.businesses[&business_id].position - start).normalize();
+        assert!(displacement.dot(&toward_business) > 5.0);
+        assert!(displacement.dot(&toward_business) > 2.0 * displacement.y.abs());
+    }
+    
+    #[test]
+    fn test_customer_matching_is_nearest_first_and_repeatable() {
+        let build = || {
+            let mut engine = AgentEngine::with_seed(2);
+            engine.set_processing_enabled(AgentType::Citizen, false);
+            engine.set_processing_enabled(AgentType::Business, false);
+            let far = engine.add_business(60.0, 50.0, "shop".to_string());
+            let tied_high = engine.add_business(45.0, 50.0, "shop".to_string());
+            let tied_low = engine.add_business(55.0, 50.0, "shop".to_string());
+            // Enough need for exactly one purchase
+            let citizen = engine.add_citizen(50.0, 50.0, HashMap::new());
+            engine.citizens.get_mut(&citizen).unwrap().needs.insert("goods".to_string(), 0.25);
+            (engine, citizen, far, tied_high.min(tied_low))
+        };
+        
+        let (mut first, citizen, far, nearest) = build();
+        first.process_cycle(0.0);
+        assert_eq!(first.customer_matches, vec![(citizen, nearest)]);
+        
+        let (mut second, ..) = build();
+        second.process_cycle(0.0);
+        assert_eq!(second.customer_matches, first.customer_matches);
+        
+        // Id order ignores distance
+        let (mut by_id, ..) = build();
+        by_id.interaction_config.matching = CustomerMatching::IdOrder;
+        by_id.process_cycle(0.0);
+        assert_eq!(by_id.customer_matches, vec![(citizen, far)]);
+    }
+    
+    #[test]
+    fn test_citizen_history_keeps_latest_decisions() {
+        let run = |capacity: usize| {
+            let mut engine = AgentEngine::with_seed(8);
+            engine.set_citizen_history(capacity);
+            let id = engine.add_citizen(50.0, 50.0, HashMap::new());
+            for _ in 0..400 {
+                engine.process_cycle(0.0);
+            }
+            (engine, id)
+        };
+        let (full, id) = run(1000);
+        let (capped, _) = run(5);
+        
+        let kept = &capped.citizens[&id];
+        assert!(full.citizens[&id].decisions.len() > 5);
+        assert!(full.citizens[&id].learning_data.len() > 5);
+        assert_eq!(kept.decisions.len(), 5);
+        assert_eq!(kept.decisions.iter().cloned().collect::<Vec<_>>(), full.citizens[&id].decisions.recent(5));
+        assert_eq!(kept.learning_data.iter().copied().collect::<Vec<_>>(), full.citizens[&id].learning_data.recent(5));
+        
+        assert_eq!(capped.recent_decisions(id, 2).unwrap(), full.citizens[&id].decisions.recent(2));
+        assert_eq!(capped.recent_decisions(id + 1, 2), None);
+    }
+    
+    #[test]
+    fn test_field_of_view_limits_perception() {
+        let mut engine = AgentEngine::new();
+        let watcher = engine.add_citizen(50.0, 50.0, HashMap::new());
+        let ahead = engine.add_citizen(56.0, 51.0, HashMap::new());
+        let behind = engine.add_citizen(45.0, 50.0, HashMap::new());
+        let beside = engine.add_citizen(50.0, 57.0, HashMap::new());
+        engine.citizens.get_mut(&watcher).unwrap().velocity = Vector2::new(2.0, 0.0);
+        
+        let perceived = |engine: &AgentEngine| -> Vec<u32> {
+            engine.perceived_neighbors(watcher, 20.0).into_iter().map(|(id, _)| id).collect()
+        };
+        assert_eq!(perceived(&engine), vec![behind, ahead, beside]);
+        
+        // A 90 degree cone facing +x
+        engine.set_field_of_view(Some(std::f64::consts::FRAC_PI_2));
+        assert_eq!(perceived(&engine), vec![ahead]);
+        
+        // Without a heading the cone does not apply
+        engine.citizens.get_mut(&watcher).unwrap().velocity = Vector2::zeros();
+        assert_eq!(perceived(&engine), vec![behind, ahead, beside]);
+    }
+    
+    #[test]
+    fn test_stockout_pauses_revenue_until_restock() {
+        let mut engine = AgentEngine::with_seed(6);
+        engine.set_processing_enabled(AgentType::Citizen, false);
+        let shop = engine.add_business(50.0, 50.0, "shop".to_string());
+        {
+            let business = engine.businesses.get_mut(&shop).unwrap();
+            business.stock_capacity = 5.0;
+            business.products.insert(GOODS_PRODUCT.to_string(), 5.0);
+            business.production_rate = 0.25;
+        }
+        for i in 0..20 {
+            let id = engine.add_citizen(45.0 + i as f64 * 0.5, 50.0, HashMap::new());
+            engine.citizens.get_mut(&id).unwrap().needs.insert("goods".to_string(), 1.0);
+        }
+        
+        let mut revenue = Vec::new();
+        for _ in 0..6 {
+            engine.process_cycle(1.0);
+            revenue.push(engine.businesses[&shop].revenue);
+        }
+        
+        // The first cycle sells out the stock
+        assert!(revenue[0] > 0.0);
+        // Partial units do not end the stockout
+        assert_eq!(revenue[1], revenue[0]);
+        assert_eq!(revenue[2], revenue[0]);
+        assert_eq!(revenue[3], revenue[0]);
+        // A whole unit restocked reopens the business
+        assert!(revenue[4] > revenue[3]);
+        assert_eq!(revenue[5], revenue[4]);
+        
+        let detail = engine.get_agent_detail(shop).unwrap();
+        assert_eq!(detail.stock.unwrap()[GOODS_PRODUCT], engine.businesses[&shop].stock_of(GOODS_PRODUCT));
+    }
+    
+    #[test]
+    fn test_results_match_across_thread_counts() {
+        let run = |threads: usize| {
+            let mut engine = AgentEngine::with_seed(23);
+            engine.set_thread_count(Some(threads)).unwrap();
+            for i in 0..300 {
+                let personality = HashMap::from([("risk_tolerance".to_string(), (i % 7) as f64 / 7.0)]);
+                engine.add_citizen((i % 25) as f64 * 12.0, (i / 25) as f64 * 12.0, personality);
+            }
+            for i in 0..25 {
+                engine.add_business(i as f64 * 12.0, 160.0, "shop".to_string());
+            }
+            for _ in 0..25 {
+                engine.process_cycle(1.0);
+            }
+            
+            let mut citizens: Vec<(u32, f64, f64, f64, f64)> = engine.citizens.values()
+                .map(|citizen| (citizen.id, citizen.velocity.x, citizen.velocity.y, citizen.energy, citizen.money))
+                .collect();
+            citizens.sort_by_key(|citizen| citizen.0);
+            let mut revenue: Vec<(u32, f64)> = engine.businesses.values()
+                .map(|business| (business.id, business.revenue))
+                .collect();
+            revenue.sort_by_key(|business| business.0);
+            (citizens, revenue, engine.get_average_energy(), engine.get_interaction_count())
+        };
+        
+        let single = run(1);
+        let multi = run(4);
+        assert_eq!(single, multi);
+        
+        let mut engine = AgentEngine::new();
+        engine.set_thread_count(Some(0)).unwrap();
+        assert_eq!(engine.thread_count, Some(1));
+        engine.set_thread_count(None).unwrap();
+        assert_eq!(engine.thread_count, None);
+    }
+    
+    #[test]
+    fn test_interaction_matrix_counts_each_pair_type() {
+        let mut engine = AgentEngine::with_seed(4);
+        engine.set_processing_enabled(AgentType::Citizen, false);
+        engine.set_processing_enabled(AgentType::Business, false);
+        engine.set_processing_enabled(AgentType::Government, false);
+        engine.interaction_config.set_radius(InteractionType::Proximity, 10.0);
+        
+        // A cluster holding one pair of every type within range
+        let citizen = engine.add_citizen(100.0, 100.0, HashMap::new());
+        engine.add_citizen(104.0, 100.0, HashMap::new());
+        engine.add_business(100.0, 104.0, "shop".to_string());
+        engine.add_government(96.0, 100.0, HashMap::new());
+        // Just outside the range of everyone in the cluster
+        engine.add_business(100.0, 118.0, "shop".to_string());
+        engine.add_government(300.0, 300.0, HashMap::new());
+        engine.add_government(305.0, 300.0, HashMap::new());
+        engine.add_business(200.0, 200.0, "shop".to_string());
+        engine.add_business(200.0, 209.9, "shop".to_string());
+        engine.calculate_interactions();
+        
+        let matrix = engine.get_interaction_matrix();
+        assert_eq!(matrix.citizen_citizen, 1);
+        assert_eq!(matrix.citizen_business, 2);
+        assert_eq!(matrix.citizen_government, 2);
+        assert_eq!(matrix.business_business, 1);
+        assert_eq!(matrix.business_government, 1);
+        assert_eq!(matrix.government_government, 1);
+        assert_eq!(matrix.get(&AgentType::Government, &AgentType::Business), 1);
+        assert_eq!(matrix.total(), 8);
+        assert_eq!(matrix.to_map()["citizen-government"], 2);
+        
+        // The grid finds the same pairs as checking all of them
+        let views: Vec<(Vector2<f64>, AgentType)> = engine.agent_views()
+            .map(|view| (view.position(), view.agent_type()))
+            .collect();
+        let mut brute_force = InteractionMatrix::default();
+        for (i, (first, first_type)) in views.iter().enumerate() {
+            for (second, second_type) in &views[i + 1..] {
+                if (second - first).magnitude() < 10.0 {
+                    *brute_force.slot_mut(first_type, second_type) += 1;
+                }
+            }
+        }
+        assert_eq!(matrix, brute_force);
+        
+        // Moving a citizen out of range drops its pairs
+        engine.citizens.get_mut(&citizen).unwrap().position = Vector2::new(500.0, 500.0);
+        engine.calculate_interactions();
+        let matrix = engine.get_interaction_matrix();
+        assert_eq!(matrix.citizen_citizen, 0);
+        assert_eq!(matrix.citizen_business, 1);
+        assert_eq!(matrix.citizen_government, 1);
+    }
+    
+    #[test]
+    fn test_aging_speeds_decay_and_retires_old_agents() {
+        let mut engine = AgentEngine::with_seed(9);
+        engine.aging = AgingConfig { decay_growth: 0.01, max_age: None };
+        let young = engine.add_citizen(50.0, 50.0, HashMap::new());
+        let old = engine.add_citizen(150.0, 150.0, HashMap::new());
+        let old_business = engine.add_business(250.0, 250.0, "shop".to_string());
+        engine.citizens.get_mut(&old).unwrap().age = 200;
+        engine.businesses.get_mut(&old_business).unwrap().age = 200;
+        let start = engine.citizens[&young].energy;
+        
+        for _ in 0..10 {
+            engine.process_cycle(1.0);
+        }
+        
+        assert_eq!(engine.citizens[&young].age, 10);
+        assert_eq!(engine.citizens[&old].age, 210);
+        let young_loss = start - engine.citizens[&young].energy;
+        let old_loss = start - engine.citizens[&old].energy;
+        assert!(young_loss > 0.0);
+        assert!(old_loss > 2.5 * young_loss);
+        
+        // Everyone past the maximum age is reaped, whatever their energy
+        engine.aging.max_age = Some(100);
+        let removed = engine.process_cycle(1.0);
+        assert_eq!(removed, vec![old, old_business]);
+        assert!(engine.citizens.contains_key(&young));
+        assert!(engine.citizens[&young].energy > 0.0);
+    }
+    
+    #[test]
+    fn test_purchase_effects_differ_by_role() {
+        let mut engine = AgentEngine::with_seed(12);
+        engine.set_processing_enabled(AgentType::Citizen, false);
+        engine.set_processing_enabled(AgentType::Business, false);
+        engine.interaction_config.set_effect(InteractionRole::Customer, InteractionEffect { reward: 0.3, energy: 2.0 });
+        engine.interaction_config.set_effect(InteractionRole::Seller, InteractionEffect { reward: 1.5, energy: -0.5 });
+        let citizen = engine.add_citizen(50.0, 50.0, HashMap::new());
+        let business = engine.add_business(55.0, 50.0, "shop".to_string());
+        let idle = engine.add_citizen(400.0, 400.0, HashMap::new());
+        let citizen_energy = engine.citizens[&citizen].energy;
+        let business_energy = engine.businesses[&business].energy;
+        
+        engine.process_cycle(1.0);
+        
+        assert_eq!(engine.customer_matches, vec![(citizen, business)]);
+        assert_eq!(engine.get_interaction_reward(citizen), 0.3);
+        assert_eq!(engine.get_interaction_reward(business), 1.5);
+        assert!((engine.get_interaction_reward(business) - engine.get_interaction_reward(citizen) - 1.2).abs() < 1e-12);
+        assert_eq!(engine.get_interaction_reward(idle), 0.0);
+        assert_eq!(engine.citizens[&citizen].energy, citizen_energy + 2.0);
+        assert_eq!(engine.businesses[&business].energy, business_energy - 0.5);
+        assert_eq!("Seller".parse::<InteractionRole>(), Ok(InteractionRole::Seller));
+    }
+    
+    #[test]
+    fn test_immovable_government_is_not_pushed() {
+        let mut engine = AgentEngine::with_seed(8);
+        engine.set_collision_restitution(Some(1.0));
+        engine.set_movable(AgentType::Government, false);
+        let government = engine.add_government(100.0, 100.0, HashMap::new());
+        let citizen = engine.add_citizen(101.0, 100.0, HashMap::new());
+        engine.citizens.get_mut(&citizen).unwrap().velocity = Vector2::new(-2.0, 0.0);
+        let overlap = engine.citizens[&citizen].radius + engine.government[&government].radius - 1.0;
+        
+        engine.handle_collisions();
+        
+        // The government holds its place; the citizen takes the full overlap and bounces back
+        assert_eq!(engine.government[&government].position, Vector2::new(100.0, 100.0));
+        assert_eq!(engine.government[&government].velocity, Vector2::zeros());
+        let pushed = engine.citizens[&citizen].position;
+        assert!((pushed.x - (101.0 + overlap)).abs() < 1e-9);
+        assert_eq!(pushed.y, 100.0);
+        assert!(engine.citizens[&citizen].velocity.x > 0.0);
+        
+        // Two immovable agents are left overlapping
+        engine.set_movable(AgentType::Citizen, false);
+        engine.citizens.get_mut(&citizen).unwrap().position = Vector2::new(101.0, 100.0);
+        engine.handle_collisions();
+        assert_eq!(engine.citizens[&citizen].position, Vector2::new(101.0, 100.0));
+    }
+    
+    #[test]
+    fn test_social_preference_scales_pull_toward_company() {
+        let mut engine = AgentEngine::with_seed(9);
+        let loner = engine.add_citizen(100.0, 100.0, HashMap::from([(personality::SOCIAL_PREFERENCE.to_string(), 0.0)]));
+        let sociable = engine.add_citizen(300.0, 100.0, HashMap::from([(personality::SOCIAL_PREFERENCE.to_string(), 1.0)]));
+        engine.add_citizen(110.0, 100.0, HashMap::new());
+        engine.add_citizen(310.0, 100.0, HashMap::new());
+        for id in [loner, sociable] {
+            engine.citizens.get_mut(&id).unwrap().needs.insert("social".to_string(), 0.5);
+        }
+        
+        let goals = engine.citizen_goals();
+        assert_eq!(goals[&loner], Vector2::zeros());
+        assert!((goals[&sociable] - Vector2::new(1.0, 0.0)).magnitude() < 1e-9);
+        
+        engine.set_parallel_processing(true);
+        assert_eq!(engine.citizen_goals(), goals);
+    }
 }
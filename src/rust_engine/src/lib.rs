@@ -4,7 +4,10 @@
 //! Provides critical performance improvements for agent simulation, physics,
 //! and optimization algorithms.
 
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 
@@ -13,10 +16,15 @@ pub mod simulation;
 pub mod agents;
 pub mod optimization;
 pub mod utils;
+pub mod snapshot;
 
-use simulation::CityPhysics;
-use agents::AgentEngine;
-use optimization::OptimizationEngine;
+use simulation::{CityPhysics, PhysicsConfig};
+use agents::{personality, AgentEngine, AgentType, AgingConfig, CustomerMatching, InteractionEffect, InteractionMatrix, InteractionRole, InteractionType};
+use optimization::{GeneticOptimizer, OptimizationEngine};
+use snapshot::{AgentEngineState, SimulationSnapshot, SnapshotFormat};
+use utils::math;
+use utils::performance::{MetricsRecorder, MetricsRow, PhaseTimings};
+use utils::random;
 
 /// Main simulation engine that coordinates all components
 #[pyclass]
@@ -26,6 +34,8 @@ pub struct RustSimulationEngine {
     pub agents: AgentEngine,
     pub optimization: OptimizationEngine,
     pub performance_metrics: PerformanceMetrics,
+    pub metrics_recorder: MetricsRecorder,
+    pub phase_timings: PhaseTimings,
 }
 
 #[pymethods]
@@ -37,17 +47,36 @@ impl RustSimulationEngine {
         let agents = AgentEngine::new();
         let optimization = OptimizationEngine::new();
         let performance_metrics = PerformanceMetrics::new();
+        let metrics_recorder = MetricsRecorder::new();
+        let phase_timings = PhaseTimings::new();
         
         Self {
             physics,
             agents,
             optimization,
             performance_metrics,
+            metrics_recorder,
+            phase_timings,
         }
     }
     
+    /// Create a simulation engine that is reproducible from a seed
+    ///
+    /// Physics and agent behavior draw from separate substreams of the seed.
+    #[staticmethod]
+    pub fn new_seeded(width: f64, height: f64, seed: u64) -> Self {
+        let mut engine = Self::new(width, height);
+        engine.agents = AgentEngine::with_seed(random::derive_seed(seed, random::BEHAVIOR_STREAM));
+        engine.physics.rng = random::stream_rng(seed, random::PHYSICS_STREAM);
+        engine.optimization.genetic_optimizer = GeneticOptimizer::with_seed(random::derive_seed(seed, random::LEARNING_STREAM));
+        engine
+    }
+    
     /// Add a citizen agent to the simulation
+    ///
+    /// Recognized personality traits must be within 0.0..=1.0; missing ones default to 0.5.
     pub fn add_citizen(&mut self, x: f64, y: f64, personality: HashMap<String, f64>) -> PyResult<u32> {
+        let personality = personality::normalize(personality).map_err(PyValueError::new_err)?;
         let agent_id = self.agents.add_citizen(x, y, personality);
         Ok(agent_id)
     }
@@ -64,41 +93,394 @@ impl RustSimulationEngine {
         Ok(agent_id)
     }
     
+    /// Add many citizens in one call, returning their ids in input order
+    ///
+    /// Personalities are validated like in `add_citizen`; no citizen is added if one is invalid.
+    pub fn add_citizens_bulk(
+        &mut self,
+        coords: Vec<(f64, f64)>,
+        personalities: Vec<HashMap<String, f64>>,
+    ) -> PyResult<Vec<u32>> {
+        check_bulk_lengths(coords.len(), personalities.len(), "personalities")?;
+        let personalities = personalities
+            .into_iter()
+            .map(personality::normalize)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(PyValueError::new_err)?;
+        Ok(coords
+            .into_iter()
+            .zip(personalities)
+            .map(|((x, y), personality)| self.agents.add_citizen(x, y, personality))
+            .collect())
+    }
+    
+    /// Add many businesses in one call, returning their ids in input order
+    pub fn add_businesses_bulk(
+        &mut self,
+        coords: Vec<(f64, f64)>,
+        business_types: Vec<String>,
+    ) -> PyResult<Vec<u32>> {
+        check_bulk_lengths(coords.len(), business_types.len(), "business_types")?;
+        Ok(coords
+            .into_iter()
+            .zip(business_types)
+            .map(|((x, y), business_type)| self.agents.add_business(x, y, business_type))
+            .collect())
+    }
+    
+    /// Add many government agents in one call, returning their ids in input order
+    pub fn add_governments_bulk(
+        &mut self,
+        coords: Vec<(f64, f64)>,
+        policies: Vec<HashMap<String, f64>>,
+    ) -> PyResult<Vec<u32>> {
+        check_bulk_lengths(coords.len(), policies.len(), "policies")?;
+        Ok(coords
+            .into_iter()
+            .zip(policies)
+            .map(|((x, y), policies)| self.agents.add_government(x, y, policies))
+            .collect())
+    }
+    
+    /// Remove an agent by id, returning whether it existed
+    pub fn remove_agent(&mut self, id: u32) -> PyResult<bool> {
+        let removed = self.agents.remove_agent(id);
+        if removed {
+            self.physics.remove_from_grid(id);
+        }
+        Ok(removed)
+    }
+    
+    /// Add a rectangular building with origin corner `(x, y)` and the given size
+    pub fn add_obstacle(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.physics.add_obstacle(x, y, width, height);
+    }
+    
+    /// Pull agents toward `(x, y)` with force `strength / (1 + falloff * distance)`
+    pub fn set_attractor(&mut self, x: f64, y: f64, strength: f64, falloff: f64) {
+        self.physics.set_attractor(x, y, strength, falloff);
+    }
+    
+    /// Remove the attractor set with `set_attractor`
+    pub fn clear_attractor(&mut self) {
+        self.physics.clear_attractor();
+    }
+    
+    /// Push every agent with a constant wind and scale the friction by `friction_modifier`
+    /// (0 to 1), e.g. to model a storm
+    #[pyo3(signature = (wind_x, wind_y, friction_modifier=1.0))]
+    pub fn set_weather(&mut self, wind_x: f64, wind_y: f64, friction_modifier: f64) {
+        self.physics.set_weather(wind_x, wind_y, friction_modifier);
+    }
+    
+    /// Return to calm weather
+    pub fn clear_weather(&mut self) {
+        self.physics.clear_weather();
+    }
+    
+    /// Pin the random stream used by personality mutation, for reproducible evolution studies
+    pub fn set_mutation_seed(&mut self, seed: u64) {
+        self.optimization.genetic_optimizer.set_mutation_seed(seed);
+    }
+    
+    /// Tune the physics constants; omitted values keep their current setting
+    ///
+    /// `friction` is the fraction of velocity kept per unit of time (1 disables damping),
+    /// `gravity` pulls agents toward +y and `collision_radius` is the citizen collision
+    /// radius, with other agent types scaled in proportion.
+    #[pyo3(signature = (friction=None, gravity=None, collision_radius=None, wall_friction=None))]
+    pub fn set_physics_config(
+        &mut self,
+        friction: Option<f64>,
+        gravity: Option<f64>,
+        collision_radius: Option<f64>,
+        wall_friction: Option<f64>,
+    ) {
+        let current = self.physics.config();
+        self.physics.set_config(PhysicsConfig {
+            gravity: gravity.unwrap_or(current.gravity),
+            friction: friction.unwrap_or(current.friction),
+            wall_friction: wall_friction.unwrap_or(current.wall_friction),
+            collision_radius: collision_radius.unwrap_or(current.collision_radius),
+        });
+    }
+    
+    /// Set the fraction (0 to 1) of into-wall velocity agents lose on touching the city edge
+    pub fn set_wall_friction(&mut self, wall_friction: f64) {
+        self.physics.set_wall_friction(wall_friction);
+    }
+    
+    /// Replace the rectangular city outline with a polygon given by its `(x, y)` vertices
+    pub fn set_boundary_polygon(&mut self, vertices: Vec<(f64, f64)>) -> PyResult<()> {
+        self.physics
+            .set_boundary_polygon(vertices)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+    
+    /// Go back to the rectangular city outline
+    pub fn clear_boundary(&mut self) {
+        self.physics.clear_boundary();
+    }
+    
+    /// Set the fraction (0 to 1) of approach speed colliding agents keep; 1 is elastic
+    ///
+    /// `None`, the default, only pushes colliding agents apart without changing velocities.
+    pub fn set_collision_restitution(&mut self, restitution: Option<f64>) {
+        self.agents.set_collision_restitution(restitution);
+    }
+    
+    /// Get a random position outside obstacles, drawn from the physics random stream
+    #[pyo3(signature = (max_attempts = simulation::DEFAULT_SPAWN_ATTEMPTS))]
+    pub fn get_spawn_position(&mut self, max_attempts: usize) -> PyResult<(f64, f64)> {
+        self.physics
+            .next_spawn_position(max_attempts)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+    
+    /// Enable a sinusoidal economic cycle modulating business activity
+    pub fn set_economic_cycle(&mut self, period: f64, amplitude: f64) {
+        self.agents.economic_cycle = Some(agents::EconomicCycle::new(period, amplitude));
+    }
+    
+    /// Disable the economic cycle
+    pub fn clear_economic_cycle(&mut self) {
+        self.agents.economic_cycle = None;
+    }
+    
+    /// Set the revenue a business earns from each citizen interaction
+    pub fn set_revenue_per_interaction(&mut self, amount: f64) {
+        self.agents.interaction_config.revenue_per_interaction = amount;
+    }
+    
+    /// Set the range of an interaction type ("social", "economic" or "proximity")
+    pub fn set_interaction_radius(&mut self, interaction_type: &str, radius: f64) -> PyResult<()> {
+        let interaction_type = interaction_type.parse::<InteractionType>().map_err(PyValueError::new_err)?;
+        self.agents.interaction_config.set_radius(interaction_type, radius);
+        Ok(())
+    }
+    
+    /// Pairs of agents in proximity range per pair type, keyed like "citizen-business"
+    pub fn get_interaction_matrix(&self) -> HashMap<String, u32> {
+        self.agents.get_interaction_matrix().to_map()
+    }
+    
+    /// Set the reward and energy one side of a purchase gains ("customer" or "seller")
+    pub fn set_interaction_effect(&mut self, role: &str, reward: f64, energy: f64) -> PyResult<()> {
+        let role = role.parse::<InteractionRole>().map_err(PyValueError::new_err)?;
+        self.agents.interaction_config.set_effect(role, InteractionEffect { reward, energy });
+        Ok(())
+    }
+    
+    /// Reward an agent earned from purchases in the last update
+    pub fn get_interaction_reward(&self, id: u32) -> f64 {
+        self.agents.get_interaction_reward(id)
+    }
+    
+    /// Let collisions push agents of a type ("citizen", "business" or "government") or not
+    pub fn set_movable(&mut self, agent_type: &str, movable: bool) -> PyResult<()> {
+        let agent_type = agent_type.parse::<AgentType>().map_err(PyValueError::new_err)?;
+        self.agents.set_movable(agent_type, movable);
+        Ok(())
+    }
+    
+    /// Set the order in which citizens try businesses in range ("nearest" or "id")
+    pub fn set_customer_matching(&mut self, rule: &str) -> PyResult<()> {
+        self.agents.interaction_config.matching = rule.parse::<CustomerMatching>().map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+    
+    /// Make energy decay grow with age and retire citizens and businesses past `max_age` cycles
+    ///
+    /// `decay_growth` is the extra decay per cycle of age, as a fraction of the base rate.
+    #[pyo3(signature = (decay_growth, max_age=None))]
+    pub fn set_aging(&mut self, decay_growth: f64, max_age: Option<u64>) {
+        self.agents.aging = AgingConfig { decay_growth, max_age };
+    }
+    
+    /// Set how many cycles an agent may stay at zero energy before it is removed
+    pub fn set_death_grace_period(&mut self, cycles: u32) {
+        self.agents.death_grace_cycles = cycles;
+    }
+    
     /// Update the simulation for one time step
     pub fn update_simulation(&mut self, delta_time: f64) -> PyResult<SimulationResult> {
         let start_time = std::time::Instant::now();
-        
-        // Update physics
-        self.physics.update_physics(&mut self.agents, delta_time);
-        
-        // Process agent behaviors
-        self.agents.process_cycle(delta_time);
-        
-        // Run optimizations
-        self.optimization.optimize_traffic(&mut self.agents);
-        self.optimization.optimize_resources(&mut self.agents);
+        let removed_agents = self.advance(delta_time);
         
         // Update performance metrics
         let update_time = start_time.elapsed();
         self.performance_metrics.update(update_time, self.agents.get_agent_count());
+        self.metrics_recorder.record(MetricsRow {
+            tick: self.agents.tick,
+            agent_count: self.agents.get_agent_count() as f64,
+            avg_energy: self.agents.get_average_energy(),
+            interactions: self.agents.get_interaction_count() as f64,
+            update_time_ms: update_time.as_secs_f64() * 1000.0,
+        });
         
         Ok(SimulationResult {
             agents_updated: self.agents.get_agent_count(),
             interactions_calculated: self.agents.get_interaction_count(),
+            interaction_matrix: self.agents.get_interaction_matrix(),
+            transaction_volume: self.agents.get_transaction_volume(),
             performance_metrics: self.performance_metrics.clone(),
+            removed_agents,
         })
     }
     
+    /// Advance the simulation `ticks` steps to let it settle before measuring
+    ///
+    /// Agent state keeps the progress, but nothing is recorded: performance metrics,
+    /// phase timings and the metrics history all start from scratch afterwards.
+    #[pyo3(signature = (ticks, delta_time = 0.1))]
+    pub fn warmup(&mut self, ticks: u32, delta_time: f64) {
+        for _ in 0..ticks {
+            self.advance(delta_time);
+        }
+        
+        self.performance_metrics = PerformanceMetrics::new();
+        self.phase_timings = PhaseTimings::new();
+        self.metrics_recorder.clear();
+    }
+    
+    /// Run fixed steps of `delta_time` for at most `max_seconds` of wall-clock time,
+    /// returning the number of steps executed
+    #[pyo3(name = "step_for_duration", signature = (max_seconds, delta_time = FIXED_STEP_DELTA_TIME))]
+    pub fn py_step_for_duration(&mut self, max_seconds: f64, delta_time: f64) -> PyResult<usize> {
+        let max = std::time::Duration::try_from_secs_f64(max_seconds)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        self.step_with_budget(max, delta_time)
+    }
+    
+    /// Run until `metric` stabilizes, then return a JSON snapshot and whether it converged
+    ///
+    /// `metric` is one of "avg_energy", "agent_count", "interactions" or "transaction_volume".
+    /// It has stabilized once it changed by at most `tol` over `stable_ticks` consecutive
+    /// steps (`EQUILIBRIUM_STABLE_TICKS`, 5, by default).
+    #[pyo3(
+        name = "equilibrium_snapshot",
+        signature = (metric, tol, max_ticks, delta_time = FIXED_STEP_DELTA_TIME, stable_ticks = EQUILIBRIUM_STABLE_TICKS)
+    )]
+    pub fn py_equilibrium_snapshot(
+        &mut self,
+        metric: &str,
+        tol: f64,
+        max_ticks: u32,
+        delta_time: f64,
+        stable_ticks: u32,
+    ) -> PyResult<(String, bool)> {
+        let metric_fn: fn(&Self) -> f64 = match metric {
+            "avg_energy" => |engine| engine.agents.get_average_energy(),
+            "agent_count" => |engine| engine.agents.get_agent_count() as f64,
+            "interactions" => |engine| engine.agents.get_interaction_count() as f64,
+            "transaction_volume" => |engine| engine.agents.get_transaction_volume(),
+            _ => return Err(PyValueError::new_err(format!("unknown equilibrium metric: {}", metric))),
+        };
+        self.equilibrium_snapshot_with(metric_fn, tol, max_ticks, delta_time, stable_ticks)
+    }
+    
     /// Get current agent positions
     pub fn get_agent_positions(&self) -> PyResult<Vec<AgentPosition>> {
         Ok(self.agents.get_positions())
     }
     
+    /// Get agent positions as a numpy `[n, 4]` array of (x, y, energy, type code) and an id array
+    ///
+    /// Type codes are 0 for citizens, 1 for businesses and 2 for government.
+    pub fn get_positions_ndarray<'py>(&self, py: Python<'py>) -> (&'py PyArray2<f64>, &'py PyArray1<u32>) {
+        let (matrix, ids) = self.agents.position_matrix();
+        (matrix.into_pyarray(py), ids.into_pyarray(py))
+    }
+    
+    /// Get the full state of one agent, or None if the id is unknown
+    pub fn get_agent(&self, id: u32) -> Option<AgentDetail> {
+        self.agents.get_agent_detail(id)
+    }
+    
+    /// Up to `n` most recent decisions of a citizen, oldest first (None for other ids)
+    pub fn get_recent_decisions(&self, id: u32, n: usize) -> Option<Vec<String>> {
+        self.agents.recent_decisions(id, n)
+    }
+    
+    /// Agents within `radius` of an agent that fall in its field of view, as (id, distance) nearest first
+    pub fn get_perceived_neighbors(&self, id: u32, radius: f64) -> Vec<(u32, f64)> {
+        self.agents.perceived_neighbors(id, radius)
+    }
+    
+    /// Limit perception to a cone of `angle` radians around each agent's heading (None for all around)
+    #[pyo3(signature = (angle=None))]
+    pub fn set_field_of_view(&mut self, angle: Option<f64>) {
+        self.agents.set_field_of_view(angle);
+    }
+    
+    /// Run agent processing on `threads` threads (None for the default pool)
+    #[pyo3(signature = (threads=None))]
+    pub fn set_thread_count(&mut self, threads: Option<usize>) -> PyResult<()> {
+        self.agents.set_thread_count(threads)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+    
+    /// Set how many decisions and learning samples each citizen retains
+    pub fn set_decision_history(&mut self, capacity: usize) {
+        self.agents.set_citizen_history(capacity);
+    }
+    
     /// Get performance metrics
     pub fn get_performance_metrics(&self) -> PyResult<PerformanceMetrics> {
         Ok(self.performance_metrics.clone())
     }
     
+    /// Record one averaged metrics row every `interval` updates
+    pub fn set_metrics_interval(&mut self, interval: usize) {
+        self.metrics_recorder.set_interval(interval);
+    }
+    
+    /// Get the recorded metrics history
+    pub fn get_metrics_history(&self) -> PyResult<Vec<MetricsRow>> {
+        Ok(self.metrics_recorder.rows().to_vec())
+    }
+    
+    /// Serialize the simulation state as "json", "bincode" or "msgpack"
+    pub fn to_snapshot(&self, py: Python<'_>, format: &str) -> PyResult<PyObject> {
+        let format = format.parse::<SnapshotFormat>().map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let bytes = SimulationSnapshot::capture(&self.physics, &self.agents)
+            .to_snapshot(format)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok(PyBytes::new(py, &bytes).into())
+    }
+    
+    /// Rebuild a simulation engine from a snapshot produced by `to_snapshot`
+    #[staticmethod]
+    pub fn from_snapshot(data: &[u8], format: &str) -> PyResult<Self> {
+        let format = format.parse::<SnapshotFormat>().map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let snapshot = SimulationSnapshot::from_snapshot(data, format)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let (physics, agents) = snapshot.restore();
+        
+        let mut engine = Self::new(physics.width, physics.height);
+        engine.physics = physics;
+        engine.agents = agents;
+        Ok(engine)
+    }
+    
+    /// Serialize all agents, the next agent id and the interaction count to JSON
+    pub fn serialize_state(&self) -> PyResult<String> {
+        AgentEngineState::capture(&self.agents)
+            .to_json()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    }
+    
+    /// Replace the agents with those serialized by `serialize_state`
+    ///
+    /// Physics and engine settings are kept and the spatial indexes are rebuilt for the
+    /// loaded agents; the engine is left unchanged on error.
+    pub fn load_state(&mut self, json: &str) -> PyResult<()> {
+        let state = AgentEngineState::from_json(json).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        state.apply(&mut self.agents);
+        self.physics.rebuild_spatial_indexes(&self.agents);
+        Ok(())
+    }
+    
     /// Get simulation statistics
     pub fn get_simulation_stats(&self) -> PyResult<SimulationStats> {
         Ok(SimulationStats {
@@ -111,9 +493,213 @@ impl RustSimulationEngine {
             city_height: self.physics.height,
         })
     }
+    
+    /// Render performance metrics and agent counts in Prometheus text exposition format
+    pub fn metrics_text(&self) -> String {
+        let mut text = String::new();
+        let metrics = &self.performance_metrics;
+        
+        let unlabeled = |value: f64| [("", value)];
+        
+        write_metric(
+            &mut text, "updates_per_second", "gauge",
+            "Simulation updates per second in the last update",
+            &unlabeled(metrics.updates_per_second),
+        );
+        write_metric(
+            &mut text, "avg_update_time_ms", "gauge",
+            "Average duration of a simulation update in milliseconds",
+            &unlabeled(metrics.avg_update_time_ms),
+        );
+        write_metric(
+            &mut text, "updates_total", "counter",
+            "Simulation updates executed",
+            &unlabeled(metrics.total_updates as f64),
+        );
+        write_metric(
+            &mut text, "agents", "gauge",
+            "Agents in the simulation by type",
+            &[
+                ("type=\"citizen\"", self.agents.get_citizen_count() as f64),
+                ("type=\"business\"", self.agents.get_business_count() as f64),
+                ("type=\"government\"", self.agents.get_government_count() as f64),
+            ],
+        );
+        write_metric(
+            &mut text, "avg_energy", "gauge",
+            "Average energy across all agents",
+            &unlabeled(self.agents.get_average_energy()),
+        );
+        
+        text
+    }
+    
+    /// Collect a health report of the simulation for troubleshooting
+    ///
+    /// Phase timings are averages over all updates so far; the interaction rate is the
+    /// number of interactions per agent in the last update. The energy summary covers
+    /// agents with finite energy only.
+    pub fn diagnostics(&self) -> DiagnosticsReport {
+        let mut energies = Vec::new();
+        let mut non_finite_agents = Vec::new();
+        let mut non_finite_positions = false;
+        let mut non_finite_velocities = false;
+        let mut non_finite_energy = false;
+        
+        for view in self.agents.agent_views() {
+            let position_finite = view.position().iter().all(|v| v.is_finite());
+            let velocity_finite = view.velocity().iter().all(|v| v.is_finite());
+            let energy_finite = view.energy().is_finite();
+            
+            non_finite_positions |= !position_finite;
+            non_finite_velocities |= !velocity_finite;
+            non_finite_energy |= !energy_finite;
+            if !(position_finite && velocity_finite && energy_finite) {
+                non_finite_agents.push(view.id());
+            }
+            if energy_finite {
+                energies.push(view.energy());
+            }
+        }
+        non_finite_agents.sort_unstable();
+        
+        let total_agents = self.agents.get_agent_count();
+        let interactions = self.agents.get_interaction_count();
+        let average_ms = |counter: &utils::performance::PerformanceCounter| counter.average_time().as_secs_f64() * 1000.0;
+        
+        DiagnosticsReport {
+            total_agents,
+            citizens: self.agents.get_citizen_count(),
+            businesses: self.agents.get_business_count(),
+            government: self.agents.get_government_count(),
+            energy_min: energies.iter().copied().reduce(f64::min).unwrap_or(0.0),
+            energy_max: energies.iter().copied().reduce(f64::max).unwrap_or(0.0),
+            energy_mean: math::mean(&energies).unwrap_or(0.0),
+            energy_median: math::median(&energies).unwrap_or(0.0),
+            energy_std_dev: math::std_dev(&energies).unwrap_or(0.0),
+            interactions,
+            interaction_rate: if total_agents > 0 { interactions as f64 / total_agents as f64 } else { 0.0 },
+            total_updates: self.performance_metrics.total_updates,
+            physics_time_ms: average_ms(&self.phase_timings.physics),
+            behavior_time_ms: average_ms(&self.phase_timings.behavior),
+            optimization_time_ms: average_ms(&self.phase_timings.optimization),
+            non_finite_positions,
+            non_finite_velocities,
+            non_finite_energy,
+            non_finite_agents,
+        }
+    }
+}
+
+impl RustSimulationEngine {
+    /// Run fixed steps of `FIXED_STEP_DELTA_TIME` until `max` wall-clock time has
+    /// elapsed, returning the number of steps executed
+    ///
+    /// Steps are recorded like `update_simulation`. The budget is checked before each
+    /// step, so the last one may overrun it by at most one step duration. Stops at the
+    /// first step that fails and returns its error.
+    pub fn step_for_duration(&mut self, max: std::time::Duration) -> PyResult<usize> {
+        self.step_with_budget(max, FIXED_STEP_DELTA_TIME)
+    }
+    
+    /// Run fixed steps until `metric_fn` stabilizes, then snapshot the simulation as JSON
+    ///
+    /// The metric has stabilized once it changed by at most `tol` over
+    /// `EQUILIBRIUM_STABLE_TICKS` consecutive steps. Returns the snapshot, restorable
+    /// with `from_snapshot(.., "json")`, and whether that happened within `max_ticks`
+    /// steps; otherwise the snapshot is taken after `max_ticks` steps. Stops at the first
+    /// step that fails and returns its error.
+    pub fn equilibrium_snapshot(
+        &mut self,
+        metric_fn: impl FnMut(&Self) -> f64,
+        tol: f64,
+        max_ticks: u32,
+    ) -> PyResult<(String, bool)> {
+        self.equilibrium_snapshot_with(metric_fn, tol, max_ticks, FIXED_STEP_DELTA_TIME, EQUILIBRIUM_STABLE_TICKS)
+    }
+    
+    fn equilibrium_snapshot_with(
+        &mut self,
+        mut metric_fn: impl FnMut(&Self) -> f64,
+        tol: f64,
+        max_ticks: u32,
+        delta_time: f64,
+        required_stable_ticks: u32,
+    ) -> PyResult<(String, bool)> {
+        let mut previous = metric_fn(self);
+        let mut stable_ticks = 0;
+        let mut converged = false;
+        
+        for _ in 0..max_ticks {
+            self.update_simulation(delta_time)?;
+            let current = metric_fn(self);
+            stable_ticks = if (current - previous).abs() <= tol { stable_ticks + 1 } else { 0 };
+            previous = current;
+            
+            if stable_ticks >= required_stable_ticks {
+                converged = true;
+                break;
+            }
+        }
+        
+        let bytes = SimulationSnapshot::capture(&self.physics, &self.agents)
+            .to_snapshot(SnapshotFormat::Json)
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        let json = String::from_utf8(bytes).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+        Ok((json, converged))
+    }
+    
+    fn step_with_budget(&mut self, max: std::time::Duration, delta_time: f64) -> PyResult<usize> {
+        let start = std::time::Instant::now();
+        let mut steps = 0;
+        while start.elapsed() < max {
+            self.update_simulation(delta_time)?;
+            steps += 1;
+        }
+        Ok(steps)
+    }
+    
+    /// Run physics, agent behavior and optimization for one step, returning removed agents
+    fn advance(&mut self, delta_time: f64) -> Vec<u32> {
+        // Update physics
+        let phase_start = std::time::Instant::now();
+        self.physics.update_physics(&mut self.agents, delta_time);
+        self.phase_timings.physics.record(phase_start.elapsed());
+        
+        // Process agent behaviors
+        let phase_start = std::time::Instant::now();
+        let removed_agents = self.agents.process_cycle(delta_time);
+        for id in &removed_agents {
+            self.physics.remove_from_grid(*id);
+        }
+        self.phase_timings.behavior.record(phase_start.elapsed());
+        
+        // Run optimizations
+        let phase_start = std::time::Instant::now();
+        self.optimization.optimize_traffic(&mut self.agents);
+        self.optimization.optimize_resources(&mut self.agents);
+        self.phase_timings.optimization.record(phase_start.elapsed());
+        
+        removed_agents
+    }
 }
 
+/// Time step used by `step_for_duration` and `equilibrium_snapshot`
+pub const FIXED_STEP_DELTA_TIME: f64 = 0.1;
+
+/// Consecutive steps a metric must stay within tolerance to count as converged
+pub const EQUILIBRIUM_STABLE_TICKS: u32 = 5;
+
+/// Shortest update duration used when computing rates, so that updates faster than
+/// the timer resolution still give a finite `updates_per_second`
+const MIN_MEASURABLE_UPDATE_MS: f64 = 0.001;
+
 /// Performance metrics for monitoring
+///
+/// Memory and CPU usage are estimated from the agent count and update time unless the
+/// `sysinfo` feature is enabled, in which case they are read from the process through
+/// the `sysinfo` crate.
+/// `is_estimated` tells which of the two the latest values are.
 #[pyclass]
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
@@ -122,6 +708,12 @@ pub struct PerformanceMetrics {
     pub cpu_usage_percent: f64,
     pub total_updates: u64,
     pub avg_update_time_ms: f64,
+    /// True when `memory_usage_mb` or `cpu_usage_percent` is an estimate rather than a measurement
+    pub is_estimated: bool,
+    /// Wall clock and process CPU time at the previous update
+    #[cfg(feature = "sysinfo")]
+    #[serde(skip)]
+    last_cpu_sample: Option<(std::time::Instant, std::time::Duration)>,
 }
 
 impl PerformanceMetrics {
@@ -132,6 +724,9 @@ impl PerformanceMetrics {
             cpu_usage_percent: 0.0,
             total_updates: 0,
             avg_update_time_ms: 0.0,
+            is_estimated: true,
+            #[cfg(feature = "sysinfo")]
+            last_cpu_sample: None,
         }
     }
     
@@ -139,9 +734,43 @@ impl PerformanceMetrics {
         self.total_updates += 1;
         let update_time_ms = update_time.as_secs_f64() * 1000.0;
         self.avg_update_time_ms = (self.avg_update_time_ms * (self.total_updates - 1) as f64 + update_time_ms) / self.total_updates as f64;
-        self.updates_per_second = 1000.0 / update_time_ms;
-        self.memory_usage_mb = agent_count as f64 * 0.1; // Simulated
-        self.cpu_usage_percent = (update_time_ms * 10.0).min(100.0); // Simulated
+        self.updates_per_second = 1000.0 / update_time_ms.max(MIN_MEASURABLE_UPDATE_MS);
+        self.memory_usage_mb = agent_count as f64 * 0.1; // Estimated
+        self.cpu_usage_percent = (update_time_ms * 10.0).min(100.0); // Estimated
+        self.is_estimated = !self.sample_process();
+    }
+    
+    /// Replace the estimates with readings from the process where available
+    ///
+    /// Returns whether both memory and CPU were measured. CPU usage is measured between
+    /// consecutive updates, so the first update keeps the estimate, and may exceed 100%
+    /// when several cores are busy.
+    #[cfg(feature = "sysinfo")]
+    fn sample_process(&mut self) -> bool {
+        use utils::performance::process;
+        
+        let Some((memory_mb, cpu_time)) = process::usage() else {
+            return false;
+        };
+        self.memory_usage_mb = memory_mb;
+        
+        let now = std::time::Instant::now();
+        let mut cpu_measured = false;
+        if let Some((last_wall, last_cpu)) = self.last_cpu_sample {
+            let wall = now.duration_since(last_wall).as_secs_f64();
+            if wall > 0.0 {
+                self.cpu_usage_percent = cpu_time.saturating_sub(last_cpu).as_secs_f64() / wall * 100.0;
+                cpu_measured = true;
+            }
+        }
+        self.last_cpu_sample = Some((now, cpu_time));
+        
+        cpu_measured
+    }
+    
+    #[cfg(not(feature = "sysinfo"))]
+    fn sample_process(&mut self) -> bool {
+        false
     }
 }
 
@@ -151,7 +780,48 @@ impl PerformanceMetrics {
 pub struct SimulationResult {
     pub agents_updated: u32,
     pub interactions_calculated: u32,
+    /// Pairs of agents in proximity range, per pair of agent types
+    pub interaction_matrix: InteractionMatrix,
+    pub transaction_volume: f64,
     pub performance_metrics: PerformanceMetrics,
+    pub removed_agents: Vec<u32>,
+}
+
+/// Prefix of every metric rendered by `metrics_text`
+const METRICS_PREFIX: &str = "rust_engine";
+
+/// Append one metric family in Prometheus text format, one sample per `(labels, value)` pair
+fn write_metric(text: &mut String, name: &str, kind: &str, help: &str, samples: &[(&str, f64)]) {
+    use std::fmt::Write;
+    
+    let name = format!("{}_{}", METRICS_PREFIX, name);
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        // Prometheus spells non-finite values "+Inf", "-Inf" and "NaN"
+        let value = match *value {
+            v if v.is_nan() => "NaN".to_string(),
+            v if v == f64::INFINITY => "+Inf".to_string(),
+            v if v == f64::NEG_INFINITY => "-Inf".to_string(),
+            v => v.to_string(),
+        };
+        if labels.is_empty() {
+            let _ = writeln!(text, "{} {}", name, value);
+        } else {
+            let _ = writeln!(text, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+/// Ensure bulk insertion inputs line up one-to-one with the coordinates
+fn check_bulk_lengths(coords: usize, values: usize, name: &str) -> PyResult<()> {
+    if coords != values {
+        return Err(PyValueError::new_err(format!(
+            "coords has {} entries but {} has {}",
+            coords, name, values
+        )));
+    }
+    Ok(())
 }
 
 /// Agent position information
@@ -167,6 +837,28 @@ pub struct AgentPosition {
     pub velocity_y: f64,
 }
 
+/// Full state of a single agent
+///
+/// Type-specific fields are `None` for agents of other types.
+#[pyclass]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AgentDetail {
+    pub id: u32,
+    pub agent_type: String,
+    pub x: f64,
+    pub y: f64,
+    pub velocity_x: f64,
+    pub velocity_y: f64,
+    pub energy: f64,
+    pub personality: Option<HashMap<String, f64>>,
+    pub revenue: Option<f64>,
+    pub customers: Option<u32>,
+    /// Units on hand per product
+    pub stock: Option<HashMap<String, f64>>,
+    pub budget: Option<f64>,
+    pub approval_rating: Option<f64>,
+}
+
 /// Simulation statistics
 #[pyclass]
 #[derive(Clone, Serialize, Deserialize)]
@@ -180,6 +872,33 @@ pub struct SimulationStats {
     pub city_height: f64,
 }
 
+/// Consolidated health report of a simulation engine
+///
+/// The `non_finite_*` flags are set when any agent has a NaN or infinite value in that field.
+#[pyclass]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DiagnosticsReport {
+    pub total_agents: u32,
+    pub citizens: u32,
+    pub businesses: u32,
+    pub government: u32,
+    pub energy_min: f64,
+    pub energy_max: f64,
+    pub energy_mean: f64,
+    pub energy_median: f64,
+    pub energy_std_dev: f64,
+    pub interactions: u32,
+    pub interaction_rate: f64,
+    pub total_updates: u64,
+    pub physics_time_ms: f64,
+    pub behavior_time_ms: f64,
+    pub optimization_time_ms: f64,
+    pub non_finite_positions: bool,
+    pub non_finite_velocities: bool,
+    pub non_finite_energy: bool,
+    pub non_finite_agents: Vec<u32>,
+}
+
 /// Initialize the Python module
 #[pymodule]
 fn rust_engine(_py: Python, m: &PyModule) -> PyResult<()> {
@@ -187,7 +906,10 @@ fn rust_engine(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PerformanceMetrics>()?;
     m.add_class::<SimulationResult>()?;
     m.add_class::<AgentPosition>()?;
+    m.add_class::<AgentDetail>()?;
     m.add_class::<SimulationStats>()?;
+    m.add_class::<DiagnosticsReport>()?;
+    m.add_class::<MetricsRow>()?;
     
     // Add version info
     m.add("__version__", "0.1.0")?;
@@ -195,3 +917,448 @@ fn rust_engine(_py: Python, m: &PyModule) -> PyResult<()> {
     
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn populated_engine(seed: u64) -> RustSimulationEngine {
+        let mut engine = RustSimulationEngine::new_seeded(500.0, 500.0, seed);
+        for i in 0..20 {
+            let personality = HashMap::from([
+                ("risk_tolerance".to_string(), 0.3 + 0.03 * i as f64),
+                ("social_preference".to_string(), 0.5),
+            ]);
+            engine.add_citizen(20.0 * i as f64, 10.0 * i as f64, personality).unwrap();
+        }
+        for i in 0..5 {
+            engine.add_business(100.0 * i as f64, 250.0, "shop".to_string()).unwrap();
+        }
+        engine.add_government(250.0, 250.0, HashMap::new()).unwrap();
+        engine
+    }
+
+    fn sorted_positions(engine: &RustSimulationEngine) -> Vec<(u32, f64, f64)> {
+        let mut positions: Vec<(u32, f64, f64)> = engine
+            .get_agent_positions()
+            .unwrap()
+            .into_iter()
+            .map(|p| (p.id, p.x, p.y))
+            .collect();
+        positions.sort_by_key(|(id, _, _)| *id);
+        positions
+    }
+
+    #[test]
+    fn test_seeded_engines_run_in_lockstep() {
+        let mut first = populated_engine(42);
+        let mut second = populated_engine(42);
+
+        for _ in 0..50 {
+            first.update_simulation(0.1).unwrap();
+            second.update_simulation(0.1).unwrap();
+            assert_eq!(sorted_positions(&first), sorted_positions(&second));
+        }
+    }
+
+    #[test]
+    fn test_get_agent_for_each_type() {
+        let mut engine = RustSimulationEngine::new(500.0, 500.0);
+        let personality = HashMap::from([
+            ("risk_tolerance".to_string(), 0.7),
+            ("social_preference".to_string(), 0.2),
+            ("innovation_level".to_string(), 0.4),
+        ]);
+        let citizen_id = engine.add_citizen(10.0, 20.0, personality.clone()).unwrap();
+        let business_id = engine.add_business(30.0, 40.0, "shop".to_string()).unwrap();
+        let government_id = engine.add_government(50.0, 60.0, HashMap::new()).unwrap();
+
+        let citizen = engine.get_agent(citizen_id).unwrap();
+        assert_eq!(citizen.agent_type, "citizen");
+        assert_eq!((citizen.x, citizen.y), (10.0, 20.0));
+        assert_eq!(citizen.personality, Some(personality));
+        assert!(citizen.revenue.is_none() && citizen.budget.is_none());
+
+        let business = engine.get_agent(business_id).unwrap();
+        assert_eq!(business.agent_type, "business");
+        assert_eq!((business.x, business.y), (30.0, 40.0));
+        assert_eq!(business.revenue, Some(0.0));
+        assert_eq!(business.customers, Some(0));
+        assert!(business.personality.is_none());
+
+        let government = engine.get_agent(government_id).unwrap();
+        assert_eq!(government.agent_type, "government");
+        assert_eq!(government.energy, 100.0);
+        assert!(government.budget.is_some());
+        assert!(government.approval_rating.is_some());
+        assert!(government.customers.is_none());
+    }
+
+    #[test]
+    fn test_get_agent_missing_id() {
+        let mut engine = RustSimulationEngine::new(500.0, 500.0);
+        engine.add_citizen(10.0, 20.0, HashMap::new()).unwrap();
+        assert!(engine.get_agent(999).is_none());
+    }
+
+    #[test]
+    fn test_behavior_draws_do_not_shift_physics_stream() {
+        use rand::Rng;
+
+        let mut first = RustSimulationEngine::new_seeded(500.0, 500.0, 11);
+        let mut second = RustSimulationEngine::new_seeded(500.0, 500.0, 11);
+        for _ in 0..7 {
+            second.agents.rng.gen::<f64>();
+        }
+
+        for _ in 0..20 {
+            assert_eq!(first.get_spawn_position(10).unwrap(), second.get_spawn_position(10).unwrap());
+        }
+        assert_ne!(
+            random::derive_seed(11, random::PHYSICS_STREAM),
+            random::derive_seed(11, random::BEHAVIOR_STREAM)
+        );
+    }
+
+    #[test]
+    fn test_add_citizens_bulk_assigns_contiguous_ids() {
+        let mut engine = RustSimulationEngine::new(1000.0, 1000.0);
+        let coords: Vec<(f64, f64)> = (0..1000).map(|i| (i as f64, (i % 100) as f64)).collect();
+        let personalities = vec![HashMap::new(); 1000];
+
+        let ids = engine.add_citizens_bulk(coords, personalities).unwrap();
+
+        assert_eq!(ids.len(), 1000);
+        assert!(ids.windows(2).all(|pair| pair[1] == pair[0] + 1));
+        assert_eq!(engine.agents.get_citizen_count(), 1000);
+    }
+
+    #[test]
+    fn test_bulk_insert_rejects_length_mismatch() {
+        let mut engine = RustSimulationEngine::new(100.0, 100.0);
+        let result = engine.add_businesses_bulk(vec![(1.0, 1.0), (2.0, 2.0)], vec!["shop".to_string()]);
+
+        assert!(result.is_err());
+        assert_eq!(engine.agents.get_agent_count(), 0);
+    }
+
+    #[test]
+    fn test_diagnostics_report_for_healthy_simulation() {
+        let mut engine = populated_engine(5);
+        for _ in 0..10 {
+            engine.update_simulation(0.1).unwrap();
+        }
+
+        let report = engine.diagnostics();
+
+        assert_eq!(report.total_agents, engine.agents.get_agent_count());
+        assert_eq!(report.citizens + report.businesses + report.government, report.total_agents);
+        assert_eq!(report.total_updates, 10);
+        assert!(report.energy_min <= report.energy_median && report.energy_median <= report.energy_max);
+        assert!(report.energy_mean > 0.0);
+        assert!(report.energy_std_dev >= 0.0);
+        assert_eq!(report.interaction_rate, report.interactions as f64 / report.total_agents as f64);
+        assert!(report.physics_time_ms > 0.0);
+        assert!(report.behavior_time_ms > 0.0);
+        assert!(report.optimization_time_ms > 0.0);
+        assert!(!report.non_finite_positions);
+        assert!(!report.non_finite_velocities);
+        assert!(!report.non_finite_energy);
+        assert!(report.non_finite_agents.is_empty());
+
+        let id = *engine.agents.citizens.keys().min().unwrap();
+        engine.agents.citizens.get_mut(&id).unwrap().velocity.x = f64::NAN;
+        let report = engine.diagnostics();
+        assert!(report.non_finite_velocities);
+        assert!(!report.non_finite_positions);
+        assert_eq!(report.non_finite_agents, vec![id]);
+    }
+
+    #[test]
+    fn test_metrics_text_is_valid_prometheus() {
+        let mut engine = populated_engine(9);
+        for _ in 0..3 {
+            engine.update_simulation(0.1).unwrap();
+        }
+
+        let text = engine.metrics_text();
+        let mut declared = std::collections::HashSet::new();
+        let mut samples = HashMap::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                let mut parts = comment.splitn(3, ' ');
+                let keyword = parts.next().unwrap();
+                let name = parts.next().unwrap();
+                assert!(keyword == "HELP" || keyword == "TYPE", "unexpected comment: {}", line);
+                if keyword == "TYPE" {
+                    assert!(matches!(parts.next(), Some("gauge" | "counter")));
+                    declared.insert(name.to_string());
+                }
+                continue;
+            }
+
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let name = series.split('{').next().unwrap();
+            assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'), "bad name: {}", name);
+            assert!(declared.contains(name), "sample before TYPE: {}", line);
+            if let Some(labels) = series.strip_prefix(name).filter(|labels| !labels.is_empty()) {
+                assert!(labels.starts_with('{') && labels.ends_with('}'));
+            }
+            samples.insert(series.to_string(), value.parse::<f64>().unwrap());
+        }
+
+        assert_eq!(samples["rust_engine_updates_total"], 3.0);
+        assert_eq!(samples["rust_engine_agents{type=\"citizen\"}"], engine.agents.get_citizen_count() as f64);
+        assert_eq!(samples["rust_engine_agents{type=\"business\"}"], 5.0);
+        assert_eq!(samples["rust_engine_agents{type=\"government\"}"], 1.0);
+        for name in ["rust_engine_updates_per_second", "rust_engine_avg_update_time_ms", "rust_engine_avg_energy"] {
+            assert!(samples.contains_key(name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn test_warmup_advances_without_recording() {
+        let mut engine = populated_engine(13);
+        let initial_positions = sorted_positions(&engine);
+
+        engine.warmup(20, 0.1);
+
+        assert_eq!(engine.agents.tick, 20);
+        assert_ne!(sorted_positions(&engine), initial_positions);
+        assert!(engine.get_metrics_history().unwrap().is_empty());
+        assert_eq!(engine.get_performance_metrics().unwrap().total_updates, 0);
+        assert_eq!(engine.diagnostics().total_updates, 0);
+        assert_eq!(engine.phase_timings.physics.count(), 0);
+
+        engine.update_simulation(0.1).unwrap();
+        let history = engine.get_metrics_history().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].tick, 21);
+    }
+
+    #[test]
+    fn test_zero_duration_update_keeps_metrics_finite() {
+        let mut metrics = PerformanceMetrics::new();
+        for _ in 0..3 {
+            metrics.update(std::time::Duration::ZERO, 50);
+        }
+
+        assert_eq!(metrics.total_updates, 3);
+        assert_eq!(metrics.updates_per_second, 1000.0 / MIN_MEASURABLE_UPDATE_MS);
+        for value in [
+            metrics.updates_per_second,
+            metrics.memory_usage_mb,
+            metrics.cpu_usage_percent,
+            metrics.avg_update_time_ms,
+        ] {
+            assert!(value.is_finite());
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "sysinfo"))]
+    fn test_metrics_marked_as_estimates_without_sysinfo() {
+        let mut engine = populated_engine(2);
+        engine.update_simulation(0.1).unwrap();
+
+        let metrics = engine.get_performance_metrics().unwrap();
+        assert!(metrics.is_estimated);
+        assert_eq!(metrics.memory_usage_mb, engine.agents.get_agent_count() as f64 * 0.1);
+    }
+
+    #[test]
+    #[cfg(feature = "sysinfo")]
+    fn test_sysinfo_measures_process_memory() {
+        let mut engine = RustSimulationEngine::new(1000.0, 1000.0);
+        let coords: Vec<(f64, f64)> = (0..5000).map(|i| ((i % 1000) as f64, (i / 5) as f64)).collect();
+        engine.add_citizens_bulk(coords, vec![HashMap::new(); 5000]).unwrap();
+        engine.update_simulation(0.1).unwrap();
+        engine.update_simulation(0.1).unwrap();
+
+        let metrics = engine.get_performance_metrics().unwrap();
+        assert!(metrics.memory_usage_mb > 0.0);
+        assert!(metrics.cpu_usage_percent.is_finite() && metrics.cpu_usage_percent >= 0.0);
+        assert!(!metrics.is_estimated);
+    }
+
+    #[test]
+    #[cfg(feature = "profiling")]
+    fn test_profiling_records_instrumented_spans() {
+        use tracing_subscriber::layer::SubscriberExt;
+        use utils::profiling::Profile;
+
+        let profile = Profile::new();
+        let subscriber = tracing_subscriber::registry().with(profile.layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut engine = populated_engine(4);
+            for _ in 0..3 {
+                engine.update_simulation(0.1).unwrap();
+            }
+        });
+
+        for span in ["process_cycle", "update_physics", "calculate_interactions"] {
+            let stats = profile.stats(span).unwrap_or_else(|| panic!("no samples for {}", span));
+            assert_eq!(stats.samples, 3);
+            assert!(stats.max <= stats.total);
+        }
+    }
+
+    #[test]
+    fn test_step_for_duration_respects_budget() {
+        let mut engine = populated_engine(17);
+        let budget = std::time::Duration::from_millis(50);
+
+        let start = std::time::Instant::now();
+        let steps = engine.step_for_duration(budget).unwrap();
+        let elapsed = start.elapsed();
+
+        assert!(steps > 0);
+        assert_eq!(engine.agents.tick, steps as u64);
+        assert_eq!(engine.get_performance_metrics().unwrap().total_updates, steps as u64);
+        assert!(elapsed >= budget);
+        // The last step started before the budget ran out: the steps before it took less
+        let rows = engine.metrics_recorder.rows();
+        assert_eq!(rows.len(), steps);
+        let before_last_ms: f64 = rows[..steps - 1].iter().map(|row| row.update_time_ms).sum();
+        assert!(before_last_ms < budget.as_secs_f64() * 1000.0, "kept stepping past the budget");
+
+        assert_eq!(engine.step_for_duration(std::time::Duration::ZERO).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_agent_state_round_trip() {
+        let mut engine = populated_engine(19);
+        for _ in 0..5 {
+            engine.update_simulation(0.1).unwrap();
+        }
+        let saved_positions = sorted_positions(&engine);
+        let saved_next_id = engine.agents.next_id;
+        let saved_interactions = engine.agents.interaction_count;
+        let json = engine.serialize_state().unwrap();
+
+        for _ in 0..5 {
+            engine.update_simulation(0.1).unwrap();
+        }
+        engine.add_citizen(10.0, 10.0, HashMap::new()).unwrap();
+        let first_id = saved_positions[0].0;
+        engine.remove_agent(first_id).unwrap();
+        assert_ne!(sorted_positions(&engine), saved_positions);
+
+        engine.load_state(&json).unwrap();
+        assert_eq!(sorted_positions(&engine), saved_positions);
+        assert_eq!(engine.agents.next_id, saved_next_id);
+        assert_eq!(engine.agents.interaction_count, saved_interactions);
+        assert_eq!(engine.serialize_state().unwrap(), json);
+
+        assert!(engine.load_state("{\"citizens\": 3}").is_err());
+        assert_eq!(sorted_positions(&engine), saved_positions);
+    }
+
+    #[test]
+    fn test_restored_agents_are_found_by_area_queries() {
+        let area_queries = |engine: &RustSimulationEngine| {
+            let mut exact = engine.physics.get_agents_in_area(150.0, 150.0, 120.0);
+            let mut cells = engine.physics.get_agents_in_grid_area(150.0, 150.0, 120.0);
+            exact.sort_unstable();
+            cells.sort_unstable();
+            (exact, cells)
+        };
+        let mut engine = populated_engine(23);
+        for _ in 0..5 {
+            engine.update_simulation(0.1).unwrap();
+        }
+        let saved = area_queries(&engine);
+        assert!(!saved.0.is_empty());
+        let json = engine.serialize_state().unwrap();
+        let snapshot = SimulationSnapshot::capture(&engine.physics, &engine.agents)
+            .to_snapshot(SnapshotFormat::Json)
+            .unwrap();
+
+        // Move the watched agents out of the area and index them there
+        for &id in &saved.0 {
+            engine.remove_agent(id).unwrap();
+            engine.add_citizen(480.0, 480.0, HashMap::new()).unwrap();
+        }
+        engine.update_simulation(0.1).unwrap();
+        assert!(area_queries(&engine).0.is_empty());
+        engine.load_state(&json).unwrap();
+        assert_eq!(area_queries(&engine), saved);
+
+        let restored = RustSimulationEngine::from_snapshot(&snapshot, "json").unwrap();
+        assert_eq!(area_queries(&restored), saved);
+    }
+
+    #[test]
+    fn test_citizen_personality_is_validated() {
+        let mut engine = RustSimulationEngine::new_seeded(100.0, 100.0, 3);
+
+        let out_of_range = HashMap::from([("risk_tolerance".to_string(), 1.5)]);
+        assert!(engine.add_citizen(10.0, 10.0, out_of_range.clone()).is_err());
+        assert!(engine.add_citizens_bulk(vec![(1.0, 1.0), (2.0, 2.0)], vec![HashMap::new(), out_of_range]).is_err());
+        assert_eq!(engine.agents.get_agent_count(), 0);
+
+        // Missing traits get the default and unknown ones are kept
+        let partial = HashMap::from([
+            ("risk_tolerance".to_string(), 0.9),
+            ("risk_tolerence".to_string(), 0.2),
+        ]);
+        let id = engine.add_citizen(10.0, 10.0, partial).unwrap();
+        let stored = &engine.agents.citizens[&id].personality;
+        assert_eq!(stored["risk_tolerance"], 0.9);
+        assert_eq!(stored["social_preference"], personality::DEFAULT_TRAIT_VALUE);
+        assert_eq!(stored["innovation_level"], personality::DEFAULT_TRAIT_VALUE);
+        assert_eq!(stored["risk_tolerence"], 0.2);
+        assert_eq!(stored.len(), 4);
+    }
+
+    #[test]
+    fn test_equilibrium_snapshot_reports_convergence() {
+        let mut engine = populated_engine(29);
+        let (json, converged) = engine
+            .equilibrium_snapshot(|engine| engine.agents.get_agent_count() as f64, 1e-9, 100)
+            .unwrap();
+        assert!(converged);
+        assert_eq!(engine.agents.tick, EQUILIBRIUM_STABLE_TICKS as u64);
+
+        let restored = RustSimulationEngine::from_snapshot(json.as_bytes(), "json").unwrap();
+        assert_eq!(restored.agents.tick, engine.agents.tick);
+        assert_eq!(sorted_positions(&restored), sorted_positions(&engine));
+
+        // A metric that keeps moving never settles: the run stops at max_ticks
+        let mut engine = populated_engine(29);
+        let (json, converged) = engine
+            .equilibrium_snapshot(|engine| engine.agents.tick as f64, 0.5, 40)
+            .unwrap();
+        assert!(!converged);
+        assert_eq!(engine.agents.tick, 40);
+        let restored = RustSimulationEngine::from_snapshot(json.as_bytes(), "json").unwrap();
+        assert_eq!(restored.agents.get_agent_count(), engine.agents.get_agent_count());
+
+        // Stabilizing only on the last allowed step still counts
+        let mut engine = populated_engine(29);
+        let (_, converged) = engine
+            .equilibrium_snapshot(|engine| engine.agents.tick.min(10) as f64, 0.5, 15)
+            .unwrap();
+        assert!(converged);
+        assert_eq!(engine.agents.tick, 15);
+
+        // A longer stability window waits for more unchanged steps
+        let mut engine = populated_engine(29);
+        let metric = |engine: &RustSimulationEngine| engine.agents.get_agent_count() as f64;
+        let (_, converged) = engine
+            .equilibrium_snapshot_with(metric, 1e-9, 100, FIXED_STEP_DELTA_TIME, 12)
+            .unwrap();
+        assert!(converged);
+        assert_eq!(engine.agents.tick, 12);
+    }
+
+    #[test]
+    fn test_set_movable_parses_agent_type() {
+        let mut engine = RustSimulationEngine::new_seeded(100.0, 100.0, 3);
+
+        engine.set_movable("Government", false).unwrap();
+        assert!(!engine.agents.government_movable);
+        assert!(engine.agents.citizens_movable);
+        assert!(engine.set_movable("tree", false).is_err());
+    }
+}
@@ -6,8 +6,13 @@
 //! - Agent behavior optimization
 //! - City planning optimization
 
-use crate::agents::AgentEngine;
-use std::collections::HashMap;
+use crate::agents::{AgentEngine, Citizen};
+use crate::simulation::CityPhysics;
+use crate::utils::random;
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{BTreeSet, HashMap};
 
 /// Main optimization engine
 #[derive(Clone)]
@@ -15,6 +20,7 @@ pub struct OptimizationEngine {
     pub traffic_optimizer: TrafficOptimizer,
     pub resource_optimizer: ResourceOptimizer,
     pub behavior_optimizer: BehaviorOptimizer,
+    pub genetic_optimizer: GeneticOptimizer,
 }
 
 impl OptimizationEngine {
@@ -24,6 +30,7 @@ impl OptimizationEngine {
             traffic_optimizer: TrafficOptimizer::new(),
             resource_optimizer: ResourceOptimizer::new(),
             behavior_optimizer: BehaviorOptimizer::new(),
+            genetic_optimizer: GeneticOptimizer::new(),
         }
     }
     
@@ -41,6 +48,26 @@ impl OptimizationEngine {
     pub fn optimize_behavior(&mut self, agents: &mut AgentEngine) {
         self.behavior_optimizer.optimize(agents);
     }
+    
+    /// Evolve citizen personalities
+    pub fn optimize_personalities(&mut self, agents: &mut AgentEngine) {
+        self.genetic_optimizer.optimize(agents);
+    }
+}
+
+/// Cell size of the traffic congestion grid, same as the physics grid
+const TRAFFIC_GRID_SIZE: f64 = 50.0;
+
+/// Grid cell coordinates of the congestion map
+pub type GridCell = (i32, i32);
+
+/// Reroute between two grid cells, with the congestion it was computed for
+#[derive(Debug, Clone)]
+pub struct CachedRoute {
+    /// Centers of the cells to pass through, excluding the origin
+    pub waypoints: Vec<(f64, f64)>,
+    /// Congestion of every cell on the route when it was computed
+    pub congestion: Vec<(GridCell, f64)>,
 }
 
 /// Traffic flow optimization
@@ -48,7 +75,14 @@ impl OptimizationEngine {
 pub struct TrafficOptimizer {
     pub congestion_threshold: f64,
     pub optimization_strength: f64,
-    pub path_cache: HashMap<(u32, u32), Vec<(f64, f64)>>,
+    /// Reroutes keyed by (origin cell, destination cell)
+    pub path_cache: HashMap<(GridCell, GridCell), CachedRoute>,
+    /// Relative congestion change on a route's cells that invalidates it
+    pub cache_tolerance: f64,
+    /// How many cells ahead of an agent its destination cell lies
+    pub lookahead_cells: i32,
+    pub cache_hits: u64,
+    pub path_computations: u64,
 }
 
 impl TrafficOptimizer {
@@ -57,9 +91,28 @@ impl TrafficOptimizer {
             congestion_threshold: 10.0, // Minimum distance between agents
             optimization_strength: 0.1,
             path_cache: HashMap::new(),
+            cache_tolerance: 0.25,
+            lookahead_cells: 3,
+            cache_hits: 0,
+            path_computations: 0,
         }
     }
     
+    /// Drop every cached route
+    pub fn clear_cache(&mut self) {
+        self.path_cache.clear();
+    }
+    
+    /// Number of reroutes served from the cache
+    pub fn get_cache_hits(&self) -> u64 {
+        self.cache_hits
+    }
+    
+    /// Number of reroutes computed from scratch
+    pub fn get_path_computations(&self) -> u64 {
+        self.path_computations
+    }
+    
     /// Optimize traffic flow for all agents
     pub fn optimize(&mut self, agents: &mut AgentEngine) {
         // Get all agent positions
@@ -99,47 +152,100 @@ impl TrafficOptimizer {
     
     /// Apply traffic optimization to reduce congestion
     fn apply_traffic_optimization(&mut self, agents: &mut AgentEngine, congestion_map: &HashMap<(i32, i32), f64>) {
-        // Simple traffic optimization: redirect agents away from congested areas
+        // Redirect agents in congested cells along a route through quieter cells
         for citizen in agents.citizens.values_mut() {
-            let grid_x = (citizen.position.x / 50.0) as i32;
-            let grid_y = (citizen.position.y / 50.0) as i32;
+            let origin = Self::grid_cell(citizen.position);
             
-            if let Some(&congestion) = congestion_map.get(&(grid_x, grid_y)) {
-                if congestion > self.congestion_threshold {
-                    // Redirect agent away from congestion
-                    let avoidance_force = self.calculate_avoidance_force(citizen.position, congestion_map);
-                    citizen.velocity += avoidance_force * self.optimization_strength;
+            if let Some(&congestion) = congestion_map.get(&origin) {
+                if congestion > self.congestion_threshold && citizen.velocity.magnitude() > 0.0 {
+                    let heading = citizen.velocity.normalize() * TRAFFIC_GRID_SIZE * self.lookahead_cells as f64;
+                    let destination = Self::grid_cell(citizen.position + heading);
+                    
+                    let waypoints = &self.cached_route(origin, destination, congestion_map).waypoints;
+                    if let Some(&(x, y)) = waypoints.first() {
+                        let direction = nalgebra::Vector2::new(x, y) - citizen.position;
+                        if direction.magnitude() > 0.0 {
+                            // Scale down the force
+                            citizen.velocity += direction.normalize() * 0.1 * self.optimization_strength;
+                        }
+                    }
                 }
             }
         }
     }
     
-    /// Calculate avoidance force to reduce congestion
-    fn calculate_avoidance_force(&self, position: nalgebra::Vector2<f64>, congestion_map: &HashMap<(i32, i32), f64>) -> nalgebra::Vector2<f64> {
-        let mut force = nalgebra::Vector2::new(0.0, 0.0);
-        let grid_size = 50.0;
+    /// Route between two cells, reused from the cache while its congestion is still current
+    fn cached_route(
+        &mut self,
+        origin: GridCell,
+        destination: GridCell,
+        congestion_map: &HashMap<(i32, i32), f64>,
+    ) -> &CachedRoute {
+        let key = (origin, destination);
+        let tolerance = self.cache_tolerance;
+        let still_valid = self.path_cache.get(&key).is_some_and(|route| {
+            route.congestion.iter().all(|(cell, cached)| {
+                let current = congestion_map.get(cell).copied().unwrap_or(0.0);
+                (current - cached).abs() <= tolerance * cached.max(1.0)
+            })
+        });
         
-        // Check surrounding grid cells
-        for dx in -1..=1 {
-            for dy in -1..=1 {
-                let grid_x = (position.x / grid_size) as i32 + dx;
-                let grid_y = (position.y / grid_size) as i32 + dy;
-                
-                if let Some(&congestion) = congestion_map.get(&(grid_x, grid_y)) {
-                    if congestion > self.congestion_threshold {
-                        // Calculate direction away from congested area
-                        let target_x = (grid_x as f64 + 0.5) * grid_size;
-                        let target_y = (grid_y as f64 + 0.5) * grid_size;
-                        let direction = position - nalgebra::Vector2::new(target_x, target_y);
-                        let normalized_direction = direction.normalize();
-                        
-                        force += normalized_direction * congestion;
+        if still_valid {
+            self.cache_hits += 1;
+        } else {
+            self.path_computations += 1;
+            let route = Self::compute_route(origin, destination, congestion_map);
+            self.path_cache.insert(key, route);
+        }
+        &self.path_cache[&key]
+    }
+    
+    /// Walk from origin to destination, stepping each time to the least congested
+    /// neighbouring cell that gets closer to the destination
+    fn compute_route(origin: GridCell, destination: GridCell, congestion_map: &HashMap<(i32, i32), f64>) -> CachedRoute {
+        let congestion_of = |cell: &GridCell| congestion_map.get(cell).copied().unwrap_or(0.0);
+        let distance = |a: GridCell, b: GridCell| (a.0 - b.0).abs().max((a.1 - b.1).abs());
+        
+        let mut route = CachedRoute {
+            waypoints: Vec::new(),
+            congestion: vec![(origin, congestion_of(&origin))],
+        };
+        
+        let mut current = origin;
+        while current != destination {
+            let remaining = distance(current, destination);
+            let mut best: Option<(GridCell, f64)> = None;
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    let cell = (current.0 + dx, current.1 + dy);
+                    if distance(cell, destination) >= remaining {
+                        continue;
+                    }
+                    let congestion = congestion_of(&cell);
+                    match best {
+                        Some((_, lowest)) if lowest <= congestion => {}
+                        _ => best = Some((cell, congestion)),
                     }
                 }
             }
+            
+            // Some neighbour always gets closer, so this only guards the loop
+            let Some((next, congestion)) = best else {
+                break;
+            };
+            route.waypoints.push((
+                (next.0 as f64 + 0.5) * TRAFFIC_GRID_SIZE,
+                (next.1 as f64 + 0.5) * TRAFFIC_GRID_SIZE,
+            ));
+            route.congestion.push((next, congestion));
+            current = next;
         }
         
-        force.normalize() * 0.1 // Scale down the force
+        route
+    }
+    
+    fn grid_cell(position: nalgebra::Vector2<f64>) -> GridCell {
+        ((position.x / TRAFFIC_GRID_SIZE) as i32, (position.y / TRAFFIC_GRID_SIZE) as i32)
     }
 }
 
@@ -159,33 +265,50 @@ impl ResourceOptimizer {
     }
     
     /// Optimize resource allocation among agents
+    ///
+    /// Energy is only moved between agents, never created or destroyed.
     pub fn optimize(&mut self, agents: &mut AgentEngine) {
-        // Calculate total resources
-        let total_energy = agents.get_average_energy() * agents.get_agent_count() as f64;
-        
         // Redistribute resources based on need
-        self.redistribute_energy(agents, total_energy);
+        self.redistribute_energy(agents);
         
         // Optimize business resource allocation
         self.optimize_business_resources(agents);
     }
     
-    /// Redistribute energy among agents
-    fn redistribute_energy(&self, agents: &mut AgentEngine, total_energy: f64) {
-        let target_energy = total_energy / agents.get_agent_count() as f64;
+    /// Redistribute energy among citizens and businesses
+    ///
+    /// Agents below half the average energy ask for `redistribution_rate` of their gap
+    /// to the average. The energy is taken from agents above the average in proportion
+    /// to their surplus, so donors never drop below the average and recipients never
+    /// exceed it.
+    fn redistribute_energy(&self, agents: &mut AgentEngine) {
+        let mut energies: Vec<&mut f64> = agents.citizens.values_mut().map(|c| &mut c.energy)
+            .chain(agents.businesses.values_mut().map(|b| &mut b.energy))
+            .collect();
+        if energies.is_empty() {
+            return;
+        }
         
-        // Redistribute among citizens
-        for citizen in agents.citizens.values_mut() {
-            if citizen.energy < target_energy * 0.5 {
-                citizen.energy += (target_energy - citizen.energy) * self.redistribution_rate;
+        let target_energy = energies.iter().map(|energy| **energy).sum::<f64>() / energies.len() as f64;
+        let need = |energy: f64| {
+            if energy < target_energy * 0.5 {
+                (target_energy - energy) * self.redistribution_rate
+            } else {
+                0.0
             }
+        };
+        
+        let total_need: f64 = energies.iter().map(|energy| need(**energy)).sum();
+        let total_surplus: f64 = energies.iter().map(|energy| (**energy - target_energy).max(0.0)).sum();
+        let transfer = total_need.min(total_surplus);
+        if transfer <= 0.0 {
+            return;
         }
         
-        // Redistribute among businesses
-        for business in agents.businesses.values_mut() {
-            if business.energy < target_energy * 0.5 {
-                business.energy += (target_energy - business.energy) * self.redistribution_rate;
-            }
+        for energy in energies.iter_mut() {
+            let received = need(**energy) / total_need * transfer;
+            let given = (**energy - target_energy).max(0.0) / total_surplus * transfer;
+            **energy += received - given;
         }
     }
     
@@ -203,14 +326,33 @@ impl ResourceOptimizer {
         if business_count > 0 {
             let avg_revenue = total_revenue / business_count as f64;
             
-            // Optimize based on performance
-            for business in agents.businesses.values_mut() {
+            // Overperforming businesses give up to 2 energy, never dropping below 50,
+            // and underperforming ones receive an equal share of it, up to 5 each
+            let mut donations = Vec::new();
+            let mut recipients = Vec::new();
+            for business in agents.businesses.values() {
                 if business.revenue < avg_revenue * 0.5 {
-                    // Boost underperforming businesses
-                    business.energy += 5.0;
+                    recipients.push(business.id);
                 } else if business.revenue > avg_revenue * 1.5 {
-                    // Reduce overperforming businesses to balance
-                    business.energy = (business.energy - 2.0).max(50.0);
+                    donations.push((business.id, (business.energy - 50.0).clamp(0.0, 2.0)));
+                }
+            }
+            
+            let pool: f64 = donations.iter().map(|(_, amount)| amount).sum();
+            if recipients.is_empty() || pool <= 0.0 {
+                return;
+            }
+            let boost = (pool / recipients.len() as f64).min(5.0);
+            let scale = boost * recipients.len() as f64 / pool;
+            
+            for (id, amount) in donations {
+                if let Some(business) = agents.businesses.get_mut(&id) {
+                    business.energy -= amount * scale;
+                }
+            }
+            for id in recipients {
+                if let Some(business) = agents.businesses.get_mut(&id) {
+                    business.energy += boost;
                 }
             }
         }
@@ -277,10 +419,473 @@ impl BehaviorOptimizer {
                 // Struggling business - try to attract more customers
                 business.customers = (business.customers + 2).min(1000);
             }
+        }
+    }
+}
+
+/// Default fitness: current energy plus money earned as a measure of success
+pub fn default_fitness(citizen: &Citizen) -> f64 {
+    citizen.energy + citizen.money
+}
+
+/// Genetic optimization of citizen personalities
+///
+/// Each citizen's `personality` map is its genome. Every `generation_interval`
+/// cycles the fittest citizens are bred by uniform crossover and mutation, and
+/// the offspring genomes replace those of the least fit citizens.
+///
+/// Parent selection and crossover draw from one stream and mutation from another,
+/// so the mutation seed can be pinned without affecting which parents are chosen.
+#[derive(Clone)]
+pub struct GeneticOptimizer {
+    /// Maximum number of citizens, in id order, taking part in a generation
+    pub population_size: usize,
+    /// Fraction of the population kept as parents; as many citizens are replaced
+    pub elite_fraction: f64,
+    /// Probability of mutating each trait of an offspring
+    pub mutation_rate: f64,
+    /// Largest change a mutation applies to a trait
+    pub mutation_scale: f64,
+    pub generation_interval: u32,
+    pub fitness: fn(&Citizen) -> f64,
+    pub generation: u32,
+    cycles_since_generation: u32,
+    rng: StdRng,
+    mutation_rng: StdRng,
+}
+
+impl Default for GeneticOptimizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GeneticOptimizer {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy(), StdRng::from_entropy())
+    }
+    
+    /// Create a genetic optimizer with a fixed seed for reproducible evolution
+    ///
+    /// Mutation draws from the `MUTATION_STREAM` substream of `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed), random::stream_rng(seed, random::MUTATION_STREAM))
+    }
+    
+    fn with_rng(rng: StdRng, mutation_rng: StdRng) -> Self {
+        Self {
+            population_size: 1000,
+            elite_fraction: 0.2,
+            mutation_rate: 0.1,
+            mutation_scale: 0.1,
+            generation_interval: 100,
+            fitness: default_fitness,
+            generation: 0,
+            cycles_since_generation: 0,
+            rng,
+            mutation_rng,
+        }
+    }
+    
+    /// Restart the mutation stream from `seed`, leaving selection and crossover untouched
+    pub fn set_mutation_seed(&mut self, seed: u64) {
+        self.mutation_rng = StdRng::seed_from_u64(seed);
+    }
+    
+    /// Count one cycle and run a generation when the interval is reached
+    pub fn optimize(&mut self, agents: &mut AgentEngine) {
+        self.cycles_since_generation += 1;
+        if self.cycles_since_generation >= self.generation_interval {
+            self.cycles_since_generation = 0;
+            self.evolve(agents);
+        }
+    }
+    
+    /// Run one generation immediately
+    pub fn evolve(&mut self, agents: &mut AgentEngine) {
+        let mut ids: Vec<u32> = agents.citizens.keys().copied().collect();
+        ids.sort_unstable();
+        ids.truncate(self.population_size);
+        
+        // Rank by fitness, fittest first
+        let mut ranked: Vec<(u32, f64)> = ids
+            .into_iter()
+            .map(|id| (id, (self.fitness)(&agents.citizens[&id])))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        
+        let elite_count = ((ranked.len() as f64 * self.elite_fraction).ceil() as usize).min(ranked.len() / 2);
+        if elite_count == 0 {
+            return;
+        }
+        
+        let parents: Vec<_> = ranked[..elite_count]
+            .iter()
+            .map(|(id, _)| agents.citizens[id].personality.clone())
+            .collect();
+        
+        for (id, _) in &ranked[ranked.len() - elite_count..] {
+            let mother = &parents[self.rng.gen_range(0..parents.len())];
+            let father = &parents[self.rng.gen_range(0..parents.len())];
+            let child = self.breed(mother, father);
+            if let Some(citizen) = agents.citizens.get_mut(id) {
+                citizen.personality = child;
+            }
+        }
+        
+        self.generation += 1;
+    }
+    
+    /// Uniform crossover of two genomes followed by mutation, traits kept in [0, 1]
+    fn breed(&mut self, mother: &HashMap<String, f64>, father: &HashMap<String, f64>) -> HashMap<String, f64> {
+        // Visit traits in name order so the draws do not depend on hash map iteration order
+        let trait_names: BTreeSet<&String> = mother.keys().chain(father.keys()).collect();
+        
+        let mut child = HashMap::new();
+        for trait_name in trait_names {
+            let inherited = match (mother.get(trait_name), father.get(trait_name)) {
+                (Some(&m), Some(&f)) => if self.rng.gen::<bool>() { m } else { f },
+                (Some(&value), None) | (None, Some(&value)) => value,
+                (None, None) => continue,
+            };
             
-            // Update products based on demand
-            let demand_factor = business.customers as f64 / 100.0;
-            business.products.insert("demand".to_string(), demand_factor);
+            let value = if self.mutation_rng.gen::<f64>() < self.mutation_rate {
+                inherited + self.mutation_rng.gen_range(-self.mutation_scale..=self.mutation_scale)
+            } else {
+                inherited
+            };
+            child.insert(trait_name.clone(), value.clamp(0.0, 1.0));
+        }
+        child
+    }
+}
+
+/// How the annealing temperature decreases over the iteration budget
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoolingSchedule {
+    /// Multiply the temperature by `factor` every iteration
+    Geometric { factor: f64 },
+    /// Decrease the temperature linearly to zero at the end of the budget
+    Linear,
+}
+
+impl CoolingSchedule {
+    /// Temperature at `iteration` out of `budget`, starting from `initial`
+    pub fn temperature(&self, initial: f64, iteration: usize, budget: usize) -> f64 {
+        match self {
+            CoolingSchedule::Geometric { factor } => initial * factor.powi(iteration as i32),
+            CoolingSchedule::Linear => initial * (1.0 - iteration as f64 / budget.max(1) as f64),
+        }
+    }
+}
+
+/// Outcome of a simulated annealing run
+#[derive(Debug, Clone)]
+pub struct AnnealingResult {
+    /// Average citizen-to-nearest-business distance before optimization
+    pub initial_objective: f64,
+    /// Average citizen-to-nearest-business distance of the best layout
+    pub best_objective: f64,
+    /// Best business positions found, by business id
+    pub layout: Vec<(u32, (f64, f64))>,
+    pub accepted_moves: usize,
+}
+
+/// Simulated annealing of business placement
+///
+/// Minimizes the average distance from each citizen to its nearest business by
+/// moving one business at a time, accepting worse layouts with the Metropolis
+/// criterion so the search can leave local minima.
+#[derive(Clone)]
+pub struct SimulatedAnnealing {
+    pub initial_temperature: f64,
+    pub cooling: CoolingSchedule,
+    pub iterations: usize,
+    /// Largest distance a business moves along each axis in one proposal
+    pub move_radius: f64,
+    rng: StdRng,
+}
+
+impl Default for SimulatedAnnealing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulatedAnnealing {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+    
+    /// Create an annealer with a fixed seed for reproducible layouts
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+    
+    fn with_rng(rng: StdRng) -> Self {
+        Self {
+            initial_temperature: 10.0,
+            cooling: CoolingSchedule::Geometric { factor: 0.995 },
+            iterations: 1000,
+            move_radius: 50.0,
+            rng,
+        }
+    }
+    
+    /// Search for a better business layout and move businesses to the best one found
+    ///
+    /// Proposed positions must be inside the city and outside obstacles.
+    pub fn optimize_business_placement(&mut self, agents: &mut AgentEngine, physics: &CityPhysics) -> AnnealingResult {
+        let mut citizen_ids: Vec<u32> = agents.citizens.keys().copied().collect();
+        citizen_ids.sort_unstable();
+        let citizens: Vec<Vector2<f64>> = citizen_ids.iter().map(|id| agents.citizens[id].position).collect();
+        
+        let mut business_ids: Vec<u32> = agents.businesses.keys().copied().collect();
+        business_ids.sort_unstable();
+        let mut layout: Vec<Vector2<f64>> = business_ids.iter().map(|id| agents.businesses[id].position).collect();
+        
+        let initial_objective = Self::average_nearest_distance(&citizens, &layout);
+        let mut current_objective = initial_objective;
+        let mut best_objective = initial_objective;
+        let mut best_layout = layout.clone();
+        let mut accepted_moves = 0;
+        
+        if !citizens.is_empty() && !layout.is_empty() {
+            for iteration in 0..self.iterations {
+                let temperature = self.cooling.temperature(self.initial_temperature, iteration, self.iterations);
+                let index = self.rng.gen_range(0..layout.len());
+                let offset = Vector2::new(
+                    self.rng.gen_range(-self.move_radius..=self.move_radius),
+                    self.rng.gen_range(-self.move_radius..=self.move_radius),
+                );
+                let candidate = layout[index] + offset;
+                if !physics.is_within_bounds(candidate.x, candidate.y) {
+                    continue;
+                }
+                
+                let previous = std::mem::replace(&mut layout[index], candidate);
+                let objective = Self::average_nearest_distance(&citizens, &layout);
+                let delta = objective - current_objective;
+                
+                // Metropolis criterion
+                let accept = delta <= 0.0
+                    || (temperature > 0.0 && self.rng.gen::<f64>() < (-delta / temperature).exp());
+                if accept {
+                    current_objective = objective;
+                    accepted_moves += 1;
+                    if objective < best_objective {
+                        best_objective = objective;
+                        best_layout.clone_from(&layout);
+                    }
+                } else {
+                    layout[index] = previous;
+                }
+            }
+        }
+        
+        for (id, position) in business_ids.iter().zip(&best_layout) {
+            if let Some(business) = agents.businesses.get_mut(id) {
+                business.position = *position;
+            }
+        }
+        
+        AnnealingResult {
+            initial_objective,
+            best_objective,
+            layout: business_ids
+                .iter()
+                .zip(&best_layout)
+                .map(|(id, position)| (*id, (position.x, position.y)))
+                .collect(),
+            accepted_moves,
+        }
+    }
+    
+    /// Average distance from each citizen to its nearest business
+    fn average_nearest_distance(citizens: &[Vector2<f64>], businesses: &[Vector2<f64>]) -> f64 {
+        if citizens.is_empty() || businesses.is_empty() {
+            return 0.0;
+        }
+        
+        let total: f64 = citizens
+            .iter()
+            .map(|citizen| {
+                businesses
+                    .iter()
+                    .map(|business| (business - citizen).magnitude())
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .sum();
+        total / citizens.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_route_cache_shared_by_agents_heading_the_same_way() {
+        let mut agents = AgentEngine::new();
+        let mut ids = Vec::new();
+        for i in 0..10 {
+            ids.push(agents.add_citizen(120.0 + i as f64, 120.0, HashMap::new()));
+        }
+        for id in &ids {
+            agents.citizens.get_mut(id).unwrap().velocity = Vector2::new(1.0, 0.0);
+        }
+        
+        let mut optimizer = TrafficOptimizer::new();
+        optimizer.optimize(&mut agents);
+        
+        assert_eq!(optimizer.get_path_computations(), 1);
+        assert_eq!(optimizer.get_cache_hits(), 9);
+        assert_eq!(optimizer.path_cache.len(), 1);
+        
+        // Recomputed once after the cache is cleared
+        optimizer.clear_cache();
+        optimizer.optimize(&mut agents);
+        assert_eq!(optimizer.get_path_computations(), 2);
+        
+        // Spreading agents out changes the congestion and invalidates the route
+        for (i, id) in ids.iter().enumerate().skip(7) {
+            agents.citizens.get_mut(id).unwrap().position = Vector2::new(400.0 + 40.0 * i as f64, 400.0);
+        }
+        optimizer.optimize(&mut agents);
+        assert_eq!(optimizer.get_path_computations(), 3);
+    }
+    
+    #[test]
+    fn test_genetic_optimizer_raises_fit_trait() {
+        let mut agents = AgentEngine::with_seed(5);
+        for i in 0..50 {
+            let personality = HashMap::from([
+                ("risk_tolerance".to_string(), (i % 10) as f64 / 20.0),
+                ("social_preference".to_string(), 0.5),
+            ]);
+            agents.add_citizen(i as f64, 0.0, personality);
+        }
+        
+        let average_risk = |agents: &AgentEngine| {
+            agents.citizens.values().map(|c| c.personality["risk_tolerance"]).sum::<f64>() / agents.citizens.len() as f64
+        };
+        let initial = average_risk(&agents);
+        
+        let mut optimizer = GeneticOptimizer::with_seed(9);
+        optimizer.generation_interval = 5;
+        optimizer.fitness = |citizen| citizen.energy;
+        
+        let mut previous = initial;
+        for cycle in 1..=50 {
+            // Toy environment: bolder citizens end up with more energy
+            for citizen in agents.citizens.values_mut() {
+                citizen.energy = 100.0 * citizen.personality["risk_tolerance"];
+            }
+            optimizer.optimize(&mut agents);
+            
+            if cycle % 25 == 0 {
+                let current = average_risk(&agents);
+                assert!(current > previous);
+                previous = current;
+            }
+        }
+        
+        assert_eq!(optimizer.generation, 10);
+        assert!(average_risk(&agents) > initial + 0.2);
+    }
+    
+    #[test]
+    fn test_mutation_seed_reproduces_evolution() {
+        let evolve = |mutation_seed: u64| {
+            let mut agents = AgentEngine::with_seed(3);
+            for i in 0..30 {
+                let personality = HashMap::from([
+                    ("risk_tolerance".to_string(), (i % 6) as f64 / 10.0),
+                    ("social_preference".to_string(), 0.5),
+                ]);
+                let id = agents.add_citizen(i as f64, 0.0, personality);
+                agents.citizens.get_mut(&id).unwrap().energy = i as f64;
+            }
+            
+            let mut optimizer = GeneticOptimizer::with_seed(11);
+            optimizer.mutation_rate = 1.0;
+            optimizer.set_mutation_seed(mutation_seed);
+            for _ in 0..5 {
+                optimizer.evolve(&mut agents);
+            }
+            
+            let mut personalities: Vec<(u32, Vec<(String, f64)>)> = agents
+                .citizens
+                .iter()
+                .map(|(id, citizen)| {
+                    let mut traits: Vec<(String, f64)> = citizen.personality.clone().into_iter().collect();
+                    traits.sort_by(|a, b| a.0.cmp(&b.0));
+                    (*id, traits)
+                })
+                .collect();
+            personalities.sort_by_key(|(id, _)| *id);
+            personalities
+        };
+        
+        assert_eq!(evolve(42), evolve(42));
+        assert_ne!(evolve(42), evolve(43));
+    }
+    
+    #[test]
+    fn test_annealing_moves_businesses_toward_citizen_clusters() {
+        let physics = CityPhysics::new(1000.0, 1000.0);
+        let mut agents = AgentEngine::with_seed(1);
+        let mut rng = StdRng::seed_from_u64(2);
+        for &(cx, cy) in &[(200.0, 200.0), (800.0, 700.0)] {
+            for _ in 0..30 {
+                agents.add_citizen(cx + rng.gen_range(-20.0..20.0), cy + rng.gen_range(-20.0..20.0), HashMap::new());
+            }
+        }
+        for i in 0..3 {
+            agents.add_business(100.0 + 400.0 * i as f64, 950.0, "shop".to_string());
+        }
+        
+        let mut annealing = SimulatedAnnealing::with_seed(4);
+        annealing.iterations = 2000;
+        let result = annealing.optimize_business_placement(&mut agents, &physics);
+        
+        assert!(result.best_objective < result.initial_objective);
+        assert!(result.best_objective < result.initial_objective * 0.5);
+        assert_eq!(result.layout.len(), 3);
+        for (id, (x, y)) in &result.layout {
+            assert_eq!(agents.businesses[id].position, Vector2::new(*x, *y));
+        }
+    }
+    
+    #[test]
+    fn test_resource_optimizer_conserves_energy() {
+        let mut agents = AgentEngine::with_seed(6);
+        for i in 0..20 {
+            let id = agents.add_citizen(i as f64 * 10.0, 0.0, HashMap::new());
+            agents.citizens.get_mut(&id).unwrap().energy = (i * 7 % 100) as f64;
+        }
+        for i in 0..6 {
+            let id = agents.add_business(i as f64 * 30.0, 50.0, "shop".to_string());
+            let business = agents.businesses.get_mut(&id).unwrap();
+            business.energy = 20.0 + 15.0 * i as f64;
+            business.revenue = [0.0, 1.0, 10.0, 10.0, 40.0, 60.0][i];
+        }
+        agents.add_government(100.0, 100.0, HashMap::new());
+        
+        let total_energy = |agents: &AgentEngine| -> f64 {
+            agents.citizens.values().map(|c| c.energy).sum::<f64>()
+                + agents.businesses.values().map(|b| b.energy).sum::<f64>()
+                + agents.government.values().map(|g| g.energy).sum::<f64>()
+        };
+        let lowest_energy = |agents: &AgentEngine| agents.citizens.values().map(|c| c.energy).fold(f64::INFINITY, f64::min);
+        
+        let before = total_energy(&agents);
+        let lowest_before = lowest_energy(&agents);
+        let mut optimizer = ResourceOptimizer::new();
+        for _ in 0..10 {
+            optimizer.optimize(&mut agents);
+            assert!((total_energy(&agents) - before).abs() < 1e-9);
         }
+        assert!(lowest_energy(&agents) > lowest_before);
     }
 }
@@ -5,9 +5,395 @@
 //! - Environmental factors
 //! - Spatial queries and optimizations
 
-use crate::agents::AgentEngine;
+use crate::agents::{AgentEngine, CITIZEN_RADIUS};
 use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::fmt;
+
+/// Default number of attempts when sampling a free spawn position
+pub const DEFAULT_SPAWN_ATTEMPTS: usize = 1000;
+
+/// Agents a quadtree leaf holds before it is subdivided
+pub const QUADTREE_CAPACITY: usize = 8;
+
+/// Depth below which quadtree nodes are never subdivided, e.g. for stacked agents
+pub const QUADTREE_MAX_DEPTH: usize = 16;
+
+/// Distance from a boundary outline within which a point counts as on it
+const BOUNDARY_TOLERANCE: f64 = 1e-9;
+
+/// Errors raised by the city simulation
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    /// No position outside obstacles was found within the retry limit
+    NoFreePosition { attempts: usize },
+    /// A boundary polygon needs at least three vertices
+    InvalidBoundary { vertices: usize },
+    /// A boundary polygon must have finite vertices enclosing a positive area
+    DegenerateBoundary,
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::NoFreePosition { attempts } => {
+                write!(f, "no free position found after {} attempts", attempts)
+            }
+            SimulationError::InvalidBoundary { vertices } => {
+                write!(f, "boundary polygon needs at least 3 vertices, got {}", vertices)
+            }
+            SimulationError::DegenerateBoundary => {
+                write!(f, "boundary polygon must have finite vertices enclosing a positive area")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+/// Axis-aligned rectangular obstacle (buildings, parks, water...)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Obstacle {
+    /// Create an obstacle from two opposite corners
+    pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        Self {
+            min_x: x1.min(x2),
+            min_y: y1.min(y2),
+            max_x: x1.max(x2),
+            max_y: y1.max(y2),
+        }
+    }
+    
+    /// Create an obstacle from its origin corner and size
+    pub fn from_rect(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self::new(x, y, x + width, y + height)
+    }
+    
+    /// Check if a point lies strictly inside the obstacle; its edges are free space
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        x > self.min_x && x < self.max_x && y > self.min_y && y < self.max_y
+    }
+    
+    /// Points on each edge closest to `position`, nearest first
+    fn edge_projections(&self, position: Vector2<f64>) -> [Vector2<f64>; 4] {
+        let mut projections = [
+            Vector2::new(self.min_x, position.y),
+            Vector2::new(self.max_x, position.y),
+            Vector2::new(position.x, self.min_y),
+            Vector2::new(position.x, self.max_y),
+        ];
+        projections.sort_by(|a, b| (a - position).magnitude().total_cmp(&(b - position).magnitude()));
+        projections
+    }
+}
+
+/// Polygonal outline of the city that agents must stay within
+///
+/// Vertices may be in either winding order; edges join consecutive vertices and the
+/// last back to the first. Points on an edge count as inside, like the rectangle walls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CityBoundary {
+    pub vertices: Vec<Vector2<f64>>,
+}
+
+impl CityBoundary {
+    /// Create a boundary from its vertices
+    ///
+    /// Fails for fewer than three vertices, non-finite coordinates or an outline
+    /// enclosing no area, such as collinear vertices.
+    pub fn polygon(vertices: Vec<(f64, f64)>) -> Result<Self, SimulationError> {
+        if vertices.len() < 3 {
+            return Err(SimulationError::InvalidBoundary { vertices: vertices.len() });
+        }
+        let boundary = Self {
+            vertices: vertices.into_iter().map(|(x, y)| Vector2::new(x, y)).collect(),
+        };
+        let finite = boundary.vertices.iter().all(|v| v.x.is_finite() && v.y.is_finite());
+        if !finite || boundary.area() <= 0.0 {
+            return Err(SimulationError::DegenerateBoundary);
+        }
+        Ok(boundary)
+    }
+    
+    /// Area enclosed by the outline (shoelace formula), whatever the winding order
+    pub fn area(&self) -> f64 {
+        let twice_signed: f64 = self.edges().map(|(a, b)| a.x * b.y - b.x * a.y).sum();
+        twice_signed.abs() / 2.0
+    }
+    
+    /// The default city outline, `[0, width] x [0, height]`, as a polygon
+    pub fn rectangle(width: f64, height: f64) -> Self {
+        Self {
+            vertices: vec![
+                Vector2::new(0.0, 0.0),
+                Vector2::new(width, 0.0),
+                Vector2::new(width, height),
+                Vector2::new(0.0, height),
+            ],
+        }
+    }
+    
+    fn edges(&self) -> impl Iterator<Item = (Vector2<f64>, Vector2<f64>)> + '_ {
+        self.vertices.iter().copied().zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+    
+    /// Check if a point lies inside the polygon or on its outline (even-odd rule)
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let point = Vector2::new(x, y);
+        if (self.nearest_point(point) - point).magnitude() < BOUNDARY_TOLERANCE {
+            return true;
+        }
+        
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if (a.y > y) != (b.y > y) && x < a.x + (y - a.y) / (b.y - a.y) * (b.x - a.x) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+    
+    /// Closest point of the outline to `position`
+    pub fn nearest_point(&self, position: Vector2<f64>) -> Vector2<f64> {
+        self.edges()
+            .map(|(a, b)| {
+                let edge = b - a;
+                let length_squared = edge.norm_squared();
+                let t = if length_squared > 0.0 {
+                    ((position - a).dot(&edge) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                a + edge * t
+            })
+            .min_by(|p, q| (p - position).magnitude().total_cmp(&(q - position).magnitude()))
+            .unwrap_or(position)
+    }
+    
+    /// Smallest axis-aligned box containing the polygon, as `(min_x, min_y, max_x, max_y)`
+    pub fn bounding_box(&self) -> (f64, f64, f64, f64) {
+        self.vertices.iter().fold(
+            (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            |(min_x, min_y, max_x, max_y), v| (min_x.min(v.x), min_y.min(v.y), max_x.max(v.x), max_y.max(v.y)),
+        )
+    }
+    
+    /// Project an out-of-bounds agent onto the nearest edge
+    ///
+    /// The agent loses `1 - retained` of its velocity component pointing out of the
+    /// city; agents inside are left untouched.
+    pub fn constrain(&self, position: &mut Vector2<f64>, velocity: &mut Vector2<f64>, retained: f64) {
+        if self.contains(position.x, position.y) {
+            return;
+        }
+        let nearest = self.nearest_point(*position);
+        let outward = (*position - nearest).normalize();
+        *position = nearest;
+        
+        let speed_out = velocity.dot(&outward);
+        if speed_out > 0.0 {
+            *velocity -= outward * speed_out * (1.0 - retained);
+        }
+    }
+}
+
+/// Radial pull toward a point of the city, e.g. downtown
+///
+/// The force points at `center` with magnitude `strength / (1 + falloff * distance)`,
+/// so a zero falloff pulls equally hard from anywhere in the city.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attractor {
+    pub center: Vector2<f64>,
+    pub strength: f64,
+    pub falloff: f64,
+}
+
+impl Attractor {
+    pub fn new(x: f64, y: f64, strength: f64, falloff: f64) -> Self {
+        Self {
+            center: Vector2::new(x, y),
+            strength,
+            falloff: falloff.max(0.0),
+        }
+    }
+    
+    /// Force exerted on an agent at `position`
+    pub fn force_at(&self, position: Vector2<f64>) -> Vector2<f64> {
+        let offset = self.center - position;
+        let distance = offset.magnitude();
+        if distance == 0.0 {
+            return Vector2::zeros();
+        }
+        offset / distance * self.strength / (1.0 + self.falloff * distance)
+    }
+}
+
+/// City-wide weather acting on every agent, e.g. a storm
+///
+/// `wind` is a constant acceleration; `friction_modifier` scales the city's `friction`
+/// (1 leaves it unchanged, lower values slow agents down more).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Weather {
+    pub wind: Vector2<f64>,
+    pub friction_modifier: f64,
+}
+
+impl Weather {
+    pub fn new(wind_x: f64, wind_y: f64, friction_modifier: f64) -> Self {
+        Self {
+            wind: Vector2::new(wind_x, wind_y),
+            friction_modifier: friction_modifier.clamp(0.0, 1.0),
+        }
+    }
+}
+
+/// Node of a `QuadTree` covering an axis-aligned box
+#[derive(Debug, Clone)]
+struct QuadNode {
+    min: Vector2<f64>,
+    max: Vector2<f64>,
+    points: Vec<(u32, Vector2<f64>)>,
+    children: Option<Box<[QuadNode; 4]>>,
+}
+
+impl QuadNode {
+    fn build(min: Vector2<f64>, max: Vector2<f64>, points: Vec<(u32, Vector2<f64>)>, depth: usize) -> Self {
+        if points.len() <= QUADTREE_CAPACITY || depth >= QUADTREE_MAX_DEPTH {
+            return Self { min, max, points, children: None };
+        }
+        
+        let mid = (min + max) / 2.0;
+        let mut quadrants: [Vec<(u32, Vector2<f64>)>; 4] = Default::default();
+        for point in points {
+            let index = usize::from(point.1.x >= mid.x) + 2 * usize::from(point.1.y >= mid.y);
+            quadrants[index].push(point);
+        }
+        let [bottom_left, bottom_right, top_left, top_right] = quadrants;
+        
+        let children = [
+            Self::build(min, mid, bottom_left, depth + 1),
+            Self::build(Vector2::new(mid.x, min.y), Vector2::new(max.x, mid.y), bottom_right, depth + 1),
+            Self::build(Vector2::new(min.x, mid.y), Vector2::new(mid.x, max.y), top_left, depth + 1),
+            Self::build(mid, max, top_right, depth + 1),
+        ];
+        Self { min, max, points: Vec::new(), children: Some(Box::new(children)) }
+    }
+    
+    /// Whether the box intersects the circle of `radius` around `center`
+    fn intersects(&self, center: Vector2<f64>, radius: f64) -> bool {
+        let closest = Vector2::new(center.x.clamp(self.min.x, self.max.x), center.y.clamp(self.min.y, self.max.y));
+        (closest - center).magnitude() <= radius
+    }
+    
+    fn query(&self, center: Vector2<f64>, radius: f64, found: &mut Vec<u32>, visits: &mut usize) {
+        *visits += 1;
+        match &self.children {
+            Some(children) => {
+                for child in children.iter().filter(|child| child.intersects(center, radius)) {
+                    child.query(center, radius, found, visits);
+                }
+            }
+            None => {
+                *visits += self.points.len();
+                found.extend(self.points.iter()
+                    .filter(|(_, position)| (position - center).magnitude() <= radius)
+                    .map(|(id, _)| *id));
+            }
+        }
+    }
+    
+    fn remove(&mut self, id: u32) {
+        self.points.retain(|(point_id, _)| *point_id != id);
+        if let Some(children) = &mut self.children {
+            children.iter_mut().for_each(|child| child.remove(id));
+        }
+    }
+}
+
+/// Point quadtree over agent positions
+///
+/// Leaves hold up to `QUADTREE_CAPACITY` agents and are split into four quadrants when
+/// more fall inside, so dense clusters get deep, small cells and empty areas stay coarse.
+#[derive(Debug, Clone, Default)]
+pub struct QuadTree {
+    root: Option<QuadNode>,
+    visits: Cell<usize>,
+}
+
+impl QuadTree {
+    /// Build a tree covering the bounding box of the given agent positions
+    pub fn build(points: Vec<(u32, Vector2<f64>)>) -> Self {
+        let Some(&(_, first)) = points.first() else {
+            return Self::default();
+        };
+        let (min, max) = points.iter().fold((first, first), |(min, max), (_, p)| {
+            (Vector2::new(min.x.min(p.x), min.y.min(p.y)), Vector2::new(max.x.max(p.x), max.y.max(p.y)))
+        });
+        Self {
+            root: Some(QuadNode::build(min, max, points, 0)),
+            visits: Cell::new(0),
+        }
+    }
+    
+    /// Ids of the agents within `radius` of `(x, y)`
+    pub fn query_radius(&self, x: f64, y: f64, radius: f64) -> Vec<u32> {
+        let center = Vector2::new(x, y);
+        let mut found = Vec::new();
+        let mut visits = 0;
+        if let Some(root) = self.root.as_ref().filter(|root| root.intersects(center, radius)) {
+            root.query(center, radius, &mut found, &mut visits);
+        }
+        self.visits.set(visits);
+        found
+    }
+    
+    /// Nodes and agents examined by the last `query_radius`
+    pub fn last_query_visits(&self) -> usize {
+        self.visits.get()
+    }
+    
+    /// Remove an agent from the tree; nodes are not merged back
+    pub fn remove(&mut self, id: u32) {
+        if let Some(root) = &mut self.root {
+            root.remove(id);
+        }
+    }
+}
+
+/// Tunable physics constants of a `CityPhysics`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsConfig {
+    /// Constant acceleration toward +y; 0 for a top-down city
+    pub gravity: f64,
+    /// Fraction of velocity kept per unit of time, applied by `update_physics` (1 disables damping)
+    pub friction: f64,
+    /// Fraction of the into-wall velocity component removed when an agent touches the city edge
+    pub wall_friction: f64,
+    /// Collision radius of a citizen; other agent types are scaled in proportion
+    pub collision_radius: f64,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            gravity: 0.0, // No gravity in 2D city simulation
+            friction: 0.95, // Air resistance
+            wall_friction: 1.0, // Agents stop pushing into walls
+            collision_radius: CITIZEN_RADIUS,
+        }
+    }
+}
 
 /// City physics engine
 #[derive(Clone)]
@@ -16,31 +402,97 @@ pub struct CityPhysics {
     pub height: f64,
     pub gravity: f64,
     pub friction: f64,
+    /// Fraction of the into-wall velocity component removed when an agent touches the city edge
+    pub wall_friction: f64,
+    /// Collision radius of a citizen; other agent types are scaled in proportion
     pub collision_radius: f64,
+    /// Polygonal outline replacing the `width` x `height` rectangle, if any
+    pub boundary: Option<CityBoundary>,
     pub spatial_grid: HashMap<(i32, i32), Vec<u32>>,
     pub grid_size: f64,
+    /// Cells and agent ids examined by the last `get_agents_in_grid_area`
+    grid_visits: Cell<usize>,
+    pub quadtree: QuadTree,
+    pub obstacles: Vec<Obstacle>,
+    pub attractor: Option<Attractor>,
+    pub weather: Option<Weather>,
+    pub rng: StdRng,
 }
 
 impl CityPhysics {
     /// Create new city physics engine
     pub fn new(width: f64, height: f64) -> Self {
+        Self::with_config(width, height, PhysicsConfig::default())
+    }
+    
+    /// Create a city physics engine with tuned physics constants
+    pub fn with_config(width: f64, height: f64, config: PhysicsConfig) -> Self {
         let grid_size = 50.0; // Grid cell size for spatial optimization
-        Self {
+        let mut physics = Self {
             width,
             height,
-            gravity: 0.0, // No gravity in 2D city simulation
-            friction: 0.95, // Air resistance
-            collision_radius: 5.0,
+            gravity: 0.0,
+            friction: 0.0,
+            wall_friction: 0.0,
+            collision_radius: 0.0,
+            boundary: None,
             spatial_grid: HashMap::new(),
             grid_size,
+            grid_visits: Cell::new(0),
+            quadtree: QuadTree::default(),
+            obstacles: Vec::new(),
+            attractor: None,
+            weather: None,
+            rng: StdRng::from_entropy(),
+        };
+        physics.set_config(config);
+        physics
+    }
+    
+    /// Current physics constants
+    pub fn config(&self) -> PhysicsConfig {
+        PhysicsConfig {
+            gravity: self.gravity,
+            friction: self.friction,
+            wall_friction: self.wall_friction,
+            collision_radius: self.collision_radius,
         }
     }
     
+    /// Replace the physics constants, clamping fractions to [0, 1] and the radius to be non-negative
+    pub fn set_config(&mut self, config: PhysicsConfig) {
+        self.gravity = config.gravity;
+        self.friction = config.friction.clamp(0.0, 1.0);
+        self.set_wall_friction(config.wall_friction);
+        self.collision_radius = config.collision_radius.max(0.0);
+    }
+    
     /// Update physics for all agents
+    #[cfg_attr(feature = "profiling", tracing::instrument(level = "trace", skip_all))]
     pub fn update_physics(&mut self, agents: &mut AgentEngine, delta_time: f64) {
         // Clear spatial grid
         self.spatial_grid.clear();
         
+        // Pull agents toward the attractor, if any
+        if let Some(attractor) = &self.attractor {
+            agents.apply_force_field(|position| attractor.force_at(position), delta_time);
+        }
+        
+        // Uniform pull for vertical layouts
+        if self.gravity != 0.0 {
+            let gravity = Vector2::new(0.0, self.gravity);
+            agents.apply_force_field(|_| gravity, delta_time);
+        }
+        
+        // Wind and air resistance act before the move, so walls and obstacles still constrain the result
+        if let Some(weather) = &self.weather {
+            agents.apply_force_field(|_| weather.wind, delta_time);
+        }
+        let retained = self.effective_friction().powf(delta_time);
+        if retained < 1.0 {
+            agents.constrain_motion(|_, velocity| *velocity *= retained);
+        }
+        
         // Update agent positions and velocities
         agents.update_positions(delta_time);
         
@@ -50,18 +502,55 @@ impl CityPhysics {
         // Handle collisions
         self.handle_collisions(agents);
         
-        // Update spatial grid for next frame
+        // Collision separation may push agents back into buildings
+        self.apply_obstacle_constraints(agents);
+        
+        // Update spatial indexes for next frame
         self.update_spatial_grid(agents);
+        self.build_quadtree(agents);
     }
     
-    /// Apply boundary constraints to keep agents within city bounds
+    /// Apply boundary constraints to keep agents within city bounds and out of obstacles
     fn apply_boundary_constraints(&self, agents: &mut AgentEngine) {
-        agents.apply_boundary_constraints(self.width, self.height);
+        match &self.boundary {
+            Some(boundary) => {
+                let retained = 1.0 - self.wall_friction;
+                agents.constrain_motion(|position, velocity| boundary.constrain(position, velocity, retained));
+            }
+            None => agents.apply_boundary_constraints(self.width, self.height, self.wall_friction),
+        }
+        self.apply_obstacle_constraints(agents);
+    }
+    
+    /// Push agents inside obstacles out to the nearest free edge
+    fn apply_obstacle_constraints(&self, agents: &mut AgentEngine) {
+        if !self.obstacles.is_empty() {
+            agents.constrain_positions(|position| self.eject_from_obstacles(position));
+        }
+    }
+    
+    /// Move a position out of any obstacle containing it, to the nearest edge inside the city
+    pub fn eject_from_obstacles(&self, mut position: Vector2<f64>) -> Vector2<f64> {
+        // Overlapping obstacles may eject into each other, so retry a bounded number of times
+        for _ in 0..=self.obstacles.len() {
+            let Some(obstacle) = self.obstacles.iter().find(|o| o.contains(position.x, position.y)) else {
+                break;
+            };
+            
+            let inside_city = |p: &Vector2<f64>| match &self.boundary {
+                Some(boundary) => boundary.contains(p.x, p.y),
+                None => p.x > 0.0 && p.x < self.width && p.y > 0.0 && p.y < self.height,
+            };
+            let projections = obstacle.edge_projections(position);
+            position = projections.iter().copied().find(inside_city).unwrap_or(projections[0]);
+        }
+        position
     }
     
     /// Handle collisions between agents
     fn handle_collisions(&self, agents: &mut AgentEngine) {
-        agents.handle_collisions(self.collision_radius);
+        agents.collision_radius_scale = self.collision_radius / CITIZEN_RADIUS;
+        agents.handle_collisions();
     }
     
     /// Update spatial grid for efficient neighbor queries
@@ -73,8 +562,38 @@ impl CityPhysics {
         }
     }
     
-    /// Get agents in a specific area (for spatial queries)
+    /// Rebuild the quadtree from the current agent positions
+    pub fn build_quadtree(&mut self, agents: &AgentEngine) {
+        self.quadtree = QuadTree::build(agents.get_all_positions());
+    }
+    
+    /// Rebuild the spatial grid and quadtree, e.g. after agents were replaced wholesale
+    pub fn rebuild_spatial_indexes(&mut self, agents: &AgentEngine) {
+        self.spatial_grid.clear();
+        self.update_spatial_grid(agents);
+        self.build_quadtree(agents);
+    }
+    
+    /// Remove an agent from the spatial grid and quadtree
+    pub fn remove_from_grid(&mut self, agent_id: u32) {
+        for agent_ids in self.spatial_grid.values_mut() {
+            agent_ids.retain(|&id| id != agent_id);
+        }
+        self.spatial_grid.retain(|_, agent_ids| !agent_ids.is_empty());
+        self.quadtree.remove(agent_id);
+    }
+    
+    /// Get agents within `radius` of `(x, y)`, as of the last physics update
     pub fn get_agents_in_area(&self, x: f64, y: f64, radius: f64) -> Vec<u32> {
+        self.quadtree.query_radius(x, y, radius)
+    }
+    
+    /// Get agents in the grid cells overlapping the square around `(x, y)`
+    ///
+    /// Coarser than `get_agents_in_area`: whole cells are returned, so some agents may
+    /// lie farther than `radius`.
+    pub fn get_agents_in_grid_area(&self, x: f64, y: f64, radius: f64) -> Vec<u32> {
+        let mut visits = 0;
         let mut agents_in_area = Vec::new();
         let grid_radius = (radius / self.grid_size).ceil() as i32;
         let center_grid_x = (x / self.grid_size) as i32;
@@ -85,50 +604,409 @@ impl CityPhysics {
                 let grid_x = center_grid_x + dx;
                 let grid_y = center_grid_y + dy;
                 
+                visits += 1;
                 if let Some(agent_ids) = self.spatial_grid.get(&(grid_x, grid_y)) {
                     for &agent_id in agent_ids {
                         agents_in_area.push(agent_id);
                     }
+                    visits += agent_ids.len();
                 }
             }
         }
         
+        self.grid_visits.set(visits);
         agents_in_area
     }
     
+    /// Cells and agent ids examined by the last `get_agents_in_grid_area`
+    pub fn last_grid_query_visits(&self) -> usize {
+        self.grid_visits.get()
+    }
+    
     /// Calculate distance between two points
     pub fn distance(&self, x1: f64, y1: f64, x2: f64, y2: f64) -> f64 {
         ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt()
     }
     
-    /// Check if two agents are colliding
-    pub fn are_colliding(&self, pos1: (f64, f64), pos2: (f64, f64)) -> bool {
-        self.distance(pos1.0, pos1.1, pos2.0, pos2.1) < self.collision_radius * 2.0
+    /// Check if two agents with the given radii are colliding
+    pub fn are_colliding(&self, pos1: (f64, f64), radius1: f64, pos2: (f64, f64), radius2: f64) -> bool {
+        self.distance(pos1.0, pos1.1, pos2.0, pos2.1) < radius1 + radius2
     }
     
-    /// Apply force to an agent
+    /// Apply force to an agent, then friction over `delta_time` as `update_physics` does
     pub fn apply_force(&self, velocity: &mut Vector2<f64>, force: Vector2<f64>, delta_time: f64) {
         *velocity += force * delta_time;
-        *velocity *= self.friction; // Apply friction
+        *velocity *= self.effective_friction().powf(delta_time); // Apply friction
+    }
+    
+    /// Fraction of velocity kept per unit of time: `friction`, scaled by the weather if any
+    pub fn effective_friction(&self) -> f64 {
+        match &self.weather {
+            Some(weather) => self.friction * weather.friction_modifier,
+            None => self.friction,
+        }
     }
     
     /// Get city bounds
+    ///
+    /// With a polygonal boundary, this is its bounding box.
     pub fn get_bounds(&self) -> (f64, f64, f64, f64) {
-        (0.0, 0.0, self.width, self.height)
+        match &self.boundary {
+            Some(boundary) => boundary.bounding_box(),
+            None => (0.0, 0.0, self.width, self.height),
+        }
     }
     
-    /// Check if position is within city bounds
+    /// Check if position is within city bounds and outside every obstacle
     pub fn is_within_bounds(&self, x: f64, y: f64) -> bool {
-        x >= 0.0 && x < self.width && y >= 0.0 && y < self.height
+        let inside_city = match &self.boundary {
+            Some(boundary) => boundary.contains(x, y),
+            None => x >= 0.0 && x < self.width && y >= 0.0 && y < self.height,
+        };
+        inside_city && !self.obstacles.iter().any(|obstacle| obstacle.contains(x, y))
     }
     
-    /// Get random position within city bounds
-    pub fn get_random_position(&self) -> (f64, f64) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        (
-            rng.gen_range(0.0..self.width),
-            rng.gen_range(0.0..self.height),
-        )
+    /// Get random position within city bounds, outside obstacles
+    pub fn get_random_position(&self) -> Result<(f64, f64), SimulationError> {
+        self.get_free_random_position(&mut rand::thread_rng(), DEFAULT_SPAWN_ATTEMPTS)
+    }
+    
+    /// Sample a free position from the physics random stream
+    pub fn next_spawn_position(&mut self, max_attempts: usize) -> Result<(f64, f64), SimulationError> {
+        // Temporarily take the generator so it can be borrowed alongside `self`
+        let mut rng = std::mem::replace(&mut self.rng, StdRng::seed_from_u64(0));
+        let position = self.get_free_random_position(&mut rng, max_attempts);
+        self.rng = rng;
+        position
+    }
+    
+    /// Pull agents toward `(x, y)` each step; see `Attractor` for the force law
+    pub fn set_attractor(&mut self, x: f64, y: f64, strength: f64, falloff: f64) {
+        self.attractor = Some(Attractor::new(x, y, strength, falloff));
+    }
+    
+    /// Remove the attractor
+    pub fn clear_attractor(&mut self) {
+        self.attractor = None;
+    }
+    
+    /// Apply wind to every agent and scale friction each step; see `Weather`
+    pub fn set_weather(&mut self, wind_x: f64, wind_y: f64, friction_modifier: f64) {
+        self.weather = Some(Weather::new(wind_x, wind_y, friction_modifier));
+    }
+    
+    /// Return to calm weather
+    pub fn clear_weather(&mut self) {
+        self.weather = None;
+    }
+    
+    /// Set the fraction (0 to 1) of into-wall velocity removed on contact with the city edge
+    pub fn set_wall_friction(&mut self, wall_friction: f64) {
+        self.wall_friction = wall_friction.clamp(0.0, 1.0);
+    }
+    
+    /// Replace the rectangular city outline with a polygon
+    pub fn set_boundary_polygon(&mut self, vertices: Vec<(f64, f64)>) -> Result<(), SimulationError> {
+        self.boundary = Some(CityBoundary::polygon(vertices)?);
+        Ok(())
+    }
+    
+    /// Go back to the `width` x `height` rectangle
+    pub fn clear_boundary(&mut self) {
+        self.boundary = None;
+    }
+    
+    /// Add a rectangular obstacle (building) with origin corner `(x, y)` and the given size
+    pub fn add_obstacle(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.obstacles.push(Obstacle::from_rect(x, y, width, height));
+    }
+    
+    /// Sample a random position outside obstacles using rejection sampling
+    ///
+    /// Draws from `rng` so spawns are reproducible with a seeded generator.
+    pub fn get_free_random_position<R: Rng>(&self, rng: &mut R, max_attempts: usize) -> Result<(f64, f64), SimulationError> {
+        let (min_x, min_y, max_x, max_y) = self.get_bounds();
+        for _ in 0..max_attempts {
+            let x = rng.gen_range(min_x..max_x);
+            let y = rng.gen_range(min_y..max_y);
+            if self.is_within_bounds(x, y) {
+                return Ok((x, y));
+            }
+        }
+        
+        Err(SimulationError::NoFreePosition { attempts: max_attempts })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    
+    #[test]
+    fn test_free_spawn_in_mostly_blocked_city() {
+        let mut physics = CityPhysics::new(100.0, 100.0);
+        // Leave only a 10x10 free corner
+        physics.add_obstacle(10.0, 0.0, 90.0, 100.0);
+        physics.add_obstacle(0.0, 10.0, 10.0, 90.0);
+        
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut spawned = 0;
+        for _ in 0..200 {
+            match physics.get_free_random_position(&mut rng, 50) {
+                Ok((x, y)) => {
+                    assert!(physics.is_within_bounds(x, y));
+                    assert!(x < 10.0 && y < 10.0);
+                    spawned += 1;
+                }
+                Err(error) => assert_eq!(error, SimulationError::NoFreePosition { attempts: 50 }),
+            }
+        }
+        assert!(spawned > 0);
+        
+        physics.add_obstacle(0.0, 0.0, 10.0, 10.0);
+        assert_eq!(
+            physics.get_free_random_position(&mut rng, 50),
+            Err(SimulationError::NoFreePosition { attempts: 50 })
+        );
+    }
+    
+    #[test]
+    fn test_agents_ejected_to_nearest_obstacle_edge() {
+        let mut physics = CityPhysics::new(200.0, 200.0);
+        physics.add_obstacle(20.0, 20.0, 40.0, 20.0);
+        physics.add_obstacle(0.0, 100.0, 10.0, 50.0);
+        
+        let mut agents = AgentEngine::new();
+        let near_left = agents.add_citizen(25.0, 30.0, HashMap::new());
+        let near_top = agents.add_citizen(50.0, 38.0, HashMap::new());
+        let against_city_edge = agents.add_citizen(2.0, 120.0, HashMap::new());
+        let outside = agents.add_citizen(100.0, 100.0, HashMap::new());
+        
+        physics.update_physics(&mut agents, 0.0);
+        
+        assert_eq!(agents.citizens[&near_left].position, Vector2::new(20.0, 30.0));
+        assert_eq!(agents.citizens[&near_top].position, Vector2::new(50.0, 40.0));
+        // The left edge lies on the city boundary, so the agent is pushed right instead
+        assert_eq!(agents.citizens[&against_city_edge].position, Vector2::new(10.0, 120.0));
+        assert_eq!(agents.citizens[&outside].position, Vector2::new(100.0, 100.0));
+        assert!(!physics.is_within_bounds(30.0, 30.0));
+    }
+    
+    #[test]
+    fn test_attractor_pulls_agents_toward_center() {
+        let mut physics = CityPhysics::new(200.0, 200.0);
+        physics.set_attractor(100.0, 100.0, 2.0, 0.01);
+        
+        let mut agents = AgentEngine::new();
+        let far = agents.add_citizen(20.0, 30.0, HashMap::new());
+        let near = agents.add_citizen(150.0, 120.0, HashMap::new());
+        let center = Vector2::new(100.0, 100.0);
+        let initial_far = (agents.citizens[&far].position - center).magnitude();
+        let initial_near = (agents.citizens[&near].position - center).magnitude();
+        
+        for _ in 0..10 {
+            physics.update_physics(&mut agents, 0.5);
+        }
+        
+        assert!((agents.citizens[&far].position - center).magnitude() < initial_far);
+        assert!((agents.citizens[&near].position - center).magnitude() < initial_near);
+        
+        physics.set_attractor(100.0, 100.0, 0.0, 0.01);
+        let mut still = AgentEngine::new();
+        let id = still.add_citizen(20.0, 30.0, HashMap::new());
+        for _ in 0..10 {
+            physics.update_physics(&mut still, 0.5);
+        }
+        assert_eq!(still.citizens[&id].position, Vector2::new(20.0, 30.0));
+    }
+    
+    #[test]
+    fn test_wall_friction_damps_velocity_into_wall() {
+        let mut physics = CityPhysics::with_config(100.0, 100.0, PhysicsConfig { friction: 1.0, ..PhysicsConfig::default() });
+        physics.set_wall_friction(0.75);
+        
+        let mut agents = AgentEngine::new();
+        let left = agents.add_citizen(1.0, 50.0, HashMap::new());
+        let corner = agents.add_citizen(99.0, 99.0, HashMap::new());
+        let free = agents.add_citizen(50.0, 50.0, HashMap::new());
+        agents.citizens.get_mut(&left).unwrap().velocity = Vector2::new(-4.0, 2.0);
+        agents.citizens.get_mut(&corner).unwrap().velocity = Vector2::new(4.0, 8.0);
+        agents.citizens.get_mut(&free).unwrap().velocity = Vector2::new(-4.0, 2.0);
+        
+        physics.update_physics(&mut agents, 1.0);
+        
+        // Only the component into the wall is damped; sliding along it is untouched
+        assert_eq!(agents.citizens[&left].position, Vector2::new(0.0, 52.0));
+        assert_eq!(agents.citizens[&left].velocity, Vector2::new(-1.0, 2.0));
+        assert_eq!(agents.citizens[&corner].position, Vector2::new(100.0, 100.0));
+        assert_eq!(agents.citizens[&corner].velocity, Vector2::new(1.0, 2.0));
+        assert_eq!(agents.citizens[&free].velocity, Vector2::new(-4.0, 2.0));
+        
+        // Moving away from the wall is not damped
+        agents.citizens.get_mut(&left).unwrap().velocity = Vector2::new(3.0, 0.0);
+        physics.update_physics(&mut agents, 1.0);
+        assert_eq!(agents.citizens[&left].velocity, Vector2::new(3.0, 0.0));
+    }
+    
+    #[test]
+    fn test_degenerate_boundary_polygons_are_rejected() {
+        let mut physics = CityPhysics::new(100.0, 100.0);
+        let collinear = vec![(0.0, 0.0), (50.0, 0.0), (100.0, 0.0)];
+        assert_eq!(CityBoundary::polygon(collinear.clone()), Err(SimulationError::DegenerateBoundary));
+        assert!(physics.set_boundary_polygon(collinear).is_err());
+        assert!(physics.set_boundary_polygon(vec![(0.0, 0.0), (100.0, 0.0), (f64::NAN, 50.0)]).is_err());
+        assert!(physics.set_boundary_polygon(vec![(0.0, 0.0), (f64::INFINITY, 0.0), (0.0, 100.0)]).is_err());
+        
+        // The rejected outlines leave the rectangle in place, so spawning still works
+        assert!(physics.boundary.is_none());
+        assert!(physics.get_free_random_position(&mut StdRng::seed_from_u64(1), DEFAULT_SPAWN_ATTEMPTS).is_ok());
+    }
+    
+    #[test]
+    fn test_polygon_boundary_pushes_agents_out_of_cutout() {
+        // L shape: the 100 x 100 square without its top-right quarter
+        let mut physics = CityPhysics::new(100.0, 100.0);
+        let outline = vec![(0.0, 0.0), (100.0, 0.0), (100.0, 50.0), (50.0, 50.0), (50.0, 100.0), (0.0, 100.0)];
+        physics.set_boundary_polygon(outline).unwrap();
+        assert!(physics.set_boundary_polygon(vec![(0.0, 0.0), (1.0, 1.0)]).is_err());
+        assert_eq!(CityBoundary::rectangle(100.0, 100.0).area(), 10000.0);
+        
+        let boundary = physics.boundary.clone().unwrap();
+        assert!(boundary.contains(25.0, 75.0));
+        assert!(boundary.contains(75.0, 25.0));
+        assert!(boundary.contains(50.0, 75.0));
+        assert!(!boundary.contains(75.0, 75.0));
+        assert!(!physics.is_within_bounds(75.0, 75.0));
+        assert_eq!(physics.get_bounds(), (0.0, 0.0, 100.0, 100.0));
+        
+        let mut agents = AgentEngine::new();
+        let near_vertical = agents.add_citizen(55.0, 80.0, HashMap::new());
+        let near_horizontal = agents.add_citizen(80.0, 53.0, HashMap::new());
+        let valid = agents.add_citizen(20.0, 20.0, HashMap::new());
+        agents.citizens.get_mut(&near_vertical).unwrap().velocity = Vector2::new(2.0, 1.0);
+        
+        physics.update_physics(&mut agents, 0.0);
+        
+        // Each agent in the cutout lands on the nearest inner edge, facing velocity removed
+        assert_eq!(agents.citizens[&near_vertical].position, Vector2::new(50.0, 80.0));
+        assert_eq!(agents.citizens[&near_vertical].velocity, Vector2::new(0.0, 1.0));
+        assert_eq!(agents.citizens[&near_horizontal].position, Vector2::new(80.0, 50.0));
+        assert_eq!(agents.citizens[&valid].position, Vector2::new(20.0, 20.0));
+        for (_, position) in agents.get_all_positions() {
+            assert!(physics.is_within_bounds(position.x, position.y));
+        }
+        
+        // The rectangle is the polygon special case
+        let rectangle = CityBoundary::rectangle(100.0, 100.0);
+        assert!(rectangle.contains(100.0, 0.0) && rectangle.contains(99.0, 99.0));
+        assert!(!rectangle.contains(101.0, 50.0));
+        assert_eq!(rectangle.nearest_point(Vector2::new(120.0, 50.0)), Vector2::new(100.0, 50.0));
+        
+        let mut spawner = physics.clone();
+        spawner.rng = StdRng::seed_from_u64(5);
+        for _ in 0..100 {
+            let (x, y) = spawner.next_spawn_position(DEFAULT_SPAWN_ATTEMPTS).unwrap();
+            assert!(!(x > 50.0 && y > 50.0));
+        }
+    }
+    
+    #[test]
+    fn test_quadtree_query_matches_grid_with_fewer_visits() {
+        let mut physics = CityPhysics::new(1000.0, 1000.0);
+        let mut agents = AgentEngine::new();
+        let mut rng = StdRng::seed_from_u64(21);
+        // 2000 agents packed into one grid cell, plus a sparse background
+        for _ in 0..2000 {
+            agents.add_citizen(rng.gen_range(410.0..440.0), rng.gen_range(410.0..440.0), HashMap::new());
+        }
+        for i in 0..50 {
+            agents.add_citizen(20.0 * i as f64, 900.0, HashMap::new());
+        }
+        physics.update_spatial_grid(&agents);
+        physics.build_quadtree(&agents);
+        
+        let positions: HashMap<u32, Vector2<f64>> = agents.get_all_positions().into_iter().collect();
+        let (x, y, radius) = (425.0, 425.0, 4.0);
+        let mut from_grid: Vec<u32> = physics.get_agents_in_grid_area(x, y, radius).into_iter()
+            .filter(|id| (positions[id] - Vector2::new(x, y)).magnitude() <= radius)
+            .collect();
+        let mut from_tree = physics.get_agents_in_area(x, y, radius);
+        from_grid.sort_unstable();
+        from_tree.sort_unstable();
+        
+        assert!(!from_tree.is_empty());
+        assert_eq!(from_tree, from_grid);
+        assert!(physics.quadtree.last_query_visits() * 4 < physics.last_grid_query_visits());
+        
+        // Removed agents no longer show up
+        physics.remove_from_grid(from_tree[0]);
+        assert!(!physics.get_agents_in_area(x, y, radius).contains(&from_tree[0]));
+        assert!(physics.get_agents_in_area(-500.0, -500.0, 10.0).is_empty());
+    }
+    
+    #[test]
+    fn test_friction_free_config_preserves_speed() {
+        let speed_after_step = |physics: &mut CityPhysics| {
+            let mut agents = AgentEngine::new();
+            let id = agents.add_citizen(50.0, 50.0, HashMap::new());
+            agents.citizens.get_mut(&id).unwrap().velocity = Vector2::new(3.0, 4.0);
+            physics.update_physics(&mut agents, 0.1);
+            agents.citizens[&id].velocity.magnitude()
+        };
+        
+        let config = PhysicsConfig { friction: 1.0, ..PhysicsConfig::default() };
+        let mut frictionless = CityPhysics::with_config(100.0, 100.0, config.clone());
+        assert_eq!(frictionless.config(), config);
+        assert!((speed_after_step(&mut frictionless) - 5.0).abs() < 1e-12);
+        
+        // The default configuration damps the same step by friction^dt
+        let damped = speed_after_step(&mut CityPhysics::new(100.0, 100.0));
+        assert!((damped - 5.0 * 0.95f64.powf(0.1)).abs() < 1e-12);
+    }
+    
+    #[test]
+    fn test_wind_drifts_agents_until_the_wall() {
+        let mut physics = CityPhysics::new(100.0, 100.0);
+        physics.set_weather(5.0, 0.0, 0.9);
+        physics.add_obstacle(40.0, 60.0, 10.0, 10.0);
+        
+        let mut agents = AgentEngine::new();
+        let open = agents.add_citizen(20.0, 20.0, HashMap::new());
+        let blocked = agents.add_citizen(35.0, 65.0, HashMap::new());
+        
+        let mut last_x = agents.citizens[&open].position.x;
+        for _ in 0..20 {
+            physics.update_physics(&mut agents, 0.5);
+            let x = agents.citizens[&open].position.x;
+            assert!(x > last_x || x == 100.0);
+            last_x = x;
+            
+            let blocked_position = agents.citizens[&blocked].position;
+            assert!(!physics.obstacles[0].contains(blocked_position.x, blocked_position.y));
+        }
+        
+        // Blown to the wall and held there, without sideways drift
+        assert_eq!(agents.citizens[&open].position, Vector2::new(100.0, 20.0));
+        
+        // Calm weather leaves agents alone
+        physics.clear_weather();
+        let mut still = AgentEngine::new();
+        let id = still.add_citizen(20.0, 20.0, HashMap::new());
+        physics.update_physics(&mut still, 0.5);
+        assert_eq!(still.citizens[&id].position, Vector2::new(20.0, 20.0));
+        
+        // The modifier scales the city's friction rather than adding a second drag
+        let mut frictionless = CityPhysics::with_config(100.0, 100.0, PhysicsConfig { friction: 1.0, ..PhysicsConfig::default() });
+        frictionless.set_weather(0.0, 0.0, 0.5);
+        assert_eq!(frictionless.effective_friction(), 0.5);
+        physics.set_weather(0.0, 0.0, 0.5);
+        assert_eq!(physics.effective_friction(), 0.95 * 0.5);
+        let mut agents = AgentEngine::new();
+        let id = agents.add_citizen(50.0, 50.0, HashMap::new());
+        agents.citizens.get_mut(&id).unwrap().velocity = Vector2::new(3.0, 4.0);
+        frictionless.update_physics(&mut agents, 1.0);
+        assert!((agents.citizens[&id].velocity.magnitude() - 2.5).abs() < 1e-12);
     }
 }
@@ -0,0 +1,486 @@
+//! Snapshot module - Saving and restoring simulation state
+//! 
+//! Snapshots can be encoded with any of the supported backends:
+//! - JSON for human-readable snapshots
+//! - bincode for compact and fast binary snapshots
+//! - MessagePack for compact snapshots readable from other languages
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::agents::{AgentEngine, AgingConfig, DEFAULT_COLLISION_GRID_THRESHOLD, Business, Citizen, EconomicCycle, Government, InteractionCache, InteractionConfig, InteractionMatrix, SpawnEnergy, UpdateSchedule};
+use crate::simulation::{Attractor, CityBoundary, CityPhysics, Obstacle, Weather};
+use crate::utils::serialization::{id_ordered, sorted_pairs};
+
+/// Errors raised while encoding or decoding snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    Encode(String),
+    Decode(String),
+    UnknownFormat(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Encode(message) => write!(f, "failed to encode snapshot: {}", message),
+            SnapshotError::Decode(message) => write!(f, "failed to decode snapshot: {}", message),
+            SnapshotError::UnknownFormat(format) => write!(f, "unknown snapshot format: {}", format),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// Serialization backend used to encode snapshots
+pub trait SnapshotBackend {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, SnapshotError>;
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SnapshotError>;
+}
+
+/// JSON backend
+pub struct JsonBackend;
+
+impl SnapshotBackend for JsonBackend {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, SnapshotError> {
+        serde_json::to_vec(value).map_err(|e| SnapshotError::Encode(e.to_string()))
+    }
+    
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SnapshotError> {
+        serde_json::from_slice(bytes).map_err(|e| SnapshotError::Decode(e.to_string()))
+    }
+}
+
+/// bincode backend
+pub struct BincodeBackend;
+
+impl SnapshotBackend for BincodeBackend {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, SnapshotError> {
+        bincode::serialize(value).map_err(|e| SnapshotError::Encode(e.to_string()))
+    }
+    
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SnapshotError> {
+        bincode::deserialize(bytes).map_err(|e| SnapshotError::Decode(e.to_string()))
+    }
+}
+
+/// MessagePack backend, encoding structs as maps so fields are self-describing
+pub struct MessagePackBackend;
+
+impl SnapshotBackend for MessagePackBackend {
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, SnapshotError> {
+        rmp_serde::to_vec_named(value).map_err(|e| SnapshotError::Encode(e.to_string()))
+    }
+    
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SnapshotError> {
+        rmp_serde::from_slice(bytes).map_err(|e| SnapshotError::Decode(e.to_string()))
+    }
+}
+
+/// Supported snapshot formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Json,
+    Bincode,
+    MessagePack,
+}
+
+impl SnapshotFormat {
+    /// Encode a value with this format's backend
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, SnapshotError> {
+        match self {
+            SnapshotFormat::Json => JsonBackend::encode(value),
+            SnapshotFormat::Bincode => BincodeBackend::encode(value),
+            SnapshotFormat::MessagePack => MessagePackBackend::encode(value),
+        }
+    }
+    
+    /// Decode a value with this format's backend
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, SnapshotError> {
+        match self {
+            SnapshotFormat::Json => JsonBackend::decode(bytes),
+            SnapshotFormat::Bincode => BincodeBackend::decode(bytes),
+            SnapshotFormat::MessagePack => MessagePackBackend::decode(bytes),
+        }
+    }
+}
+
+impl FromStr for SnapshotFormat {
+    type Err = SnapshotError;
+    
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(SnapshotFormat::Json),
+            "bincode" => Ok(SnapshotFormat::Bincode),
+            "msgpack" | "messagepack" => Ok(SnapshotFormat::MessagePack),
+            _ => Err(SnapshotError::UnknownFormat(format.to_string())),
+        }
+    }
+}
+
+/// Snapshots from before agents could be made immovable restore every type as movable
+fn movable_default() -> bool {
+    true
+}
+
+fn collision_grid_threshold_default() -> usize {
+    DEFAULT_COLLISION_GRID_THRESHOLD
+}
+
+/// Serializable state of the physics and agent engines
+///
+/// Per-agent behavior streams derive from the captured behavior seed, agent seeds and
+/// tick, so existing agents keep drawing the same values as in the original engine.
+/// The engines' own generators are not captured: the agent engine's generator, used for
+/// spawn energy, restarts from the behavior seed, and the physics generator, used for
+/// free spawn positions, restarts from entropy. Agents added after a restore may
+/// therefore differ from those the original engine would have added.
+/// Agents are encoded in ascending id order and maps with sorted keys, so the same
+/// state always encodes to the same bytes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulationSnapshot {
+    pub width: f64,
+    pub height: f64,
+    pub gravity: f64,
+    pub friction: f64,
+    pub wall_friction: f64,
+    pub collision_radius: f64,
+    pub boundary: Option<CityBoundary>,
+    pub grid_size: f64,
+    pub obstacles: Vec<Obstacle>,
+    pub attractor: Option<Attractor>,
+    pub weather: Option<Weather>,
+    #[serde(with = "id_ordered")]
+    pub citizens: HashMap<u32, Citizen>,
+    #[serde(with = "id_ordered")]
+    pub businesses: HashMap<u32, Business>,
+    #[serde(with = "id_ordered")]
+    pub government: HashMap<u32, Government>,
+    pub next_id: u32,
+    pub interaction_count: u32,
+    pub social_interaction_count: u32,
+    #[serde(default)]
+    pub interaction_matrix: InteractionMatrix,
+    pub tick: u64,
+    pub economic_cycle: Option<EconomicCycle>,
+    #[serde(default)]
+    pub behavior_seed: u64,
+    #[serde(default, with = "sorted_pairs")]
+    pub agent_seeds: HashMap<u32, u64>,
+    pub citizens_enabled: bool,
+    pub businesses_enabled: bool,
+    pub government_enabled: bool,
+    #[serde(default = "movable_default")]
+    pub citizens_movable: bool,
+    #[serde(default = "movable_default")]
+    pub businesses_movable: bool,
+    #[serde(default = "movable_default")]
+    pub government_movable: bool,
+    /// Snapshots from before restitution was captured restore collisions without impulses
+    #[serde(default)]
+    pub collision_restitution: Option<f64>,
+    #[serde(default = "collision_grid_threshold_default")]
+    pub collision_grid_threshold: usize,
+    pub citizen_schedule: UpdateSchedule,
+    pub business_schedule: UpdateSchedule,
+    pub government_schedule: UpdateSchedule,
+    pub citizen_spawn_energy: SpawnEnergy,
+    pub business_spawn_energy: SpawnEnergy,
+    pub government_spawn_energy: SpawnEnergy,
+    pub death_grace_cycles: u32,
+    #[serde(default)]
+    pub aging: AgingConfig,
+    pub citizen_history: usize,
+    pub field_of_view: Option<f64>,
+    #[serde(with = "sorted_pairs")]
+    pub depleted_cycles: HashMap<u32, u32>,
+    pub interaction_config: InteractionConfig,
+    pub transaction_volume: f64,
+}
+
+impl SimulationSnapshot {
+    /// Capture the current state of the simulation
+    pub fn capture(physics: &CityPhysics, agents: &AgentEngine) -> Self {
+        Self {
+            width: physics.width,
+            height: physics.height,
+            gravity: physics.gravity,
+            friction: physics.friction,
+            wall_friction: physics.wall_friction,
+            collision_radius: physics.collision_radius,
+            boundary: physics.boundary.clone(),
+            grid_size: physics.grid_size,
+            obstacles: physics.obstacles.clone(),
+            attractor: physics.attractor.clone(),
+            weather: physics.weather.clone(),
+            citizens: agents.citizens.clone(),
+            businesses: agents.businesses.clone(),
+            government: agents.government.clone(),
+            next_id: agents.next_id,
+            interaction_count: agents.interaction_count,
+            social_interaction_count: agents.social_interaction_count,
+            interaction_matrix: agents.interaction_matrix,
+            tick: agents.tick,
+            economic_cycle: agents.economic_cycle.clone(),
+            behavior_seed: agents.behavior_seed,
+            agent_seeds: agents.agent_seeds.clone(),
+            citizens_enabled: agents.citizens_enabled,
+            businesses_enabled: agents.businesses_enabled,
+            government_enabled: agents.government_enabled,
+            citizens_movable: agents.citizens_movable,
+            businesses_movable: agents.businesses_movable,
+            government_movable: agents.government_movable,
+            collision_restitution: agents.collision_restitution,
+            collision_grid_threshold: agents.collision_grid_threshold,
+            citizen_schedule: agents.citizen_schedule.clone(),
+            business_schedule: agents.business_schedule.clone(),
+            government_schedule: agents.government_schedule.clone(),
+            citizen_spawn_energy: agents.citizen_spawn_energy.clone(),
+            business_spawn_energy: agents.business_spawn_energy.clone(),
+            government_spawn_energy: agents.government_spawn_energy.clone(),
+            death_grace_cycles: agents.death_grace_cycles,
+            aging: agents.aging,
+            citizen_history: agents.citizen_history,
+            field_of_view: agents.field_of_view,
+            depleted_cycles: agents.depleted_cycles.clone(),
+            interaction_config: agents.interaction_config.clone(),
+            transaction_volume: agents.transaction_volume,
+        }
+    }
+    
+    /// Rebuild the physics and agent engines from this snapshot
+    pub fn restore(self) -> (CityPhysics, AgentEngine) {
+        let mut physics = CityPhysics::new(self.width, self.height);
+        physics.gravity = self.gravity;
+        physics.friction = self.friction;
+        physics.wall_friction = self.wall_friction;
+        physics.collision_radius = self.collision_radius;
+        physics.boundary = self.boundary;
+        physics.grid_size = self.grid_size;
+        physics.obstacles = self.obstacles;
+        physics.attractor = self.attractor;
+        physics.weather = self.weather;
+        
+        let mut agents = AgentEngine::with_seed(self.behavior_seed);
+        agents.behavior_seed = self.behavior_seed;
+        agents.agent_seeds = self.agent_seeds;
+        agents.citizens = self.citizens;
+        agents.businesses = self.businesses;
+        agents.government = self.government;
+        agents.next_id = self.next_id;
+        agents.interaction_count = self.interaction_count;
+        agents.social_interaction_count = self.social_interaction_count;
+        agents.interaction_matrix = self.interaction_matrix;
+        agents.tick = self.tick;
+        agents.economic_cycle = self.economic_cycle;
+        agents.citizens_enabled = self.citizens_enabled;
+        agents.businesses_enabled = self.businesses_enabled;
+        agents.government_enabled = self.government_enabled;
+        agents.citizens_movable = self.citizens_movable;
+        agents.businesses_movable = self.businesses_movable;
+        agents.government_movable = self.government_movable;
+        agents.collision_restitution = self.collision_restitution;
+        agents.collision_grid_threshold = self.collision_grid_threshold;
+        agents.citizen_schedule = self.citizen_schedule;
+        agents.business_schedule = self.business_schedule;
+        agents.government_schedule = self.government_schedule;
+        agents.citizen_spawn_energy = self.citizen_spawn_energy;
+        agents.business_spawn_energy = self.business_spawn_energy;
+        agents.government_spawn_energy = self.government_spawn_energy;
+        agents.death_grace_cycles = self.death_grace_cycles;
+        agents.aging = self.aging;
+        agents.citizen_history = self.citizen_history;
+        agents.field_of_view = self.field_of_view;
+        agents.depleted_cycles = self.depleted_cycles;
+        agents.interaction_config = self.interaction_config;
+        agents.transaction_volume = self.transaction_volume;
+        
+        physics.rebuild_spatial_indexes(&agents);
+        (physics, agents)
+    }
+    
+    /// Encode the snapshot in the given format
+    pub fn to_snapshot(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        format.encode(self)
+    }
+    
+    /// Decode a snapshot previously encoded in the given format
+    pub fn from_snapshot(bytes: &[u8], format: SnapshotFormat) -> Result<Self, SnapshotError> {
+        format.decode(bytes)
+    }
+}
+
+/// Agents of an `AgentEngine` with its id counter and interaction count
+///
+/// Unlike `SimulationSnapshot`, it leaves physics and engine configuration out, so it
+/// can be loaded into a running engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentEngineState {
+    #[serde(with = "id_ordered")]
+    pub citizens: HashMap<u32, Citizen>,
+    #[serde(with = "id_ordered")]
+    pub businesses: HashMap<u32, Business>,
+    #[serde(with = "id_ordered")]
+    pub government: HashMap<u32, Government>,
+    pub next_id: u32,
+    pub interaction_count: u32,
+}
+
+impl AgentEngineState {
+    /// Capture the agents of an engine
+    pub fn capture(agents: &AgentEngine) -> Self {
+        Self {
+            citizens: agents.citizens.clone(),
+            businesses: agents.businesses.clone(),
+            government: agents.government.clone(),
+            next_id: agents.next_id,
+            interaction_count: agents.interaction_count,
+        }
+    }
+    
+    /// Replace the agents of an engine with the captured ones
+    ///
+    /// Per-agent bookkeeping of agents that no longer exist is dropped, and the
+    /// incremental interaction cache, if enabled, starts over.
+    pub fn apply(self, agents: &mut AgentEngine) {
+        agents.citizens = self.citizens;
+        agents.businesses = self.businesses;
+        agents.government = self.government;
+        agents.next_id = self.next_id;
+        agents.interaction_count = self.interaction_count;
+        
+        let exists = |id: &u32| {
+            agents.citizens.contains_key(id) || agents.businesses.contains_key(id) || agents.government.contains_key(id)
+        };
+        agents.depleted_cycles.retain(|id, _| exists(id));
+        agents.agent_seeds.retain(|id, _| exists(id));
+        agents.recordings.retain(|id, _| exists(id));
+        if let Some(cache) = &mut agents.interaction_cache {
+            *cache = InteractionCache::new(cache.move_threshold);
+        }
+    }
+    
+    /// Encode the agents as JSON
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        let bytes = SnapshotFormat::Json.encode(self)?;
+        String::from_utf8(bytes).map_err(|e| SnapshotError::Encode(e.to_string()))
+    }
+    
+    /// Decode agents encoded with `to_json`
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        SnapshotFormat::Json.decode(json.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_snapshot_round_trip_in_every_format() {
+        let mut physics = CityPhysics::new(300.0, 300.0);
+        physics.add_obstacle(100.0, 100.0, 50.0, 20.0);
+        let mut agents = AgentEngine::with_seed(21);
+        for i in 0..10 {
+            let personality = HashMap::from([("risk_tolerance".to_string(), 0.1 * i as f64)]);
+            agents.add_citizen(25.0 * i as f64, 40.0, personality);
+        }
+        agents.add_business(60.0, 45.0, "shop".to_string());
+        agents.add_government(200.0, 200.0, HashMap::new());
+        agents.economic_cycle = Some(EconomicCycle::new(30.0, 0.4));
+        agents.set_collision_restitution(Some(0.4));
+        agents.collision_grid_threshold = 8;
+        for _ in 0..5 {
+            physics.update_physics(&mut agents, 0.1);
+            agents.process_cycle(0.1);
+        }
+        
+        let snapshot = SimulationSnapshot::capture(&physics, &agents);
+        for format in [SnapshotFormat::Json, SnapshotFormat::Bincode, SnapshotFormat::MessagePack] {
+            let bytes = snapshot.to_snapshot(format).unwrap();
+            let decoded = SimulationSnapshot::from_snapshot(&bytes, format).unwrap();
+            assert_eq!(decoded, snapshot, "{:?} round trip changed the snapshot", format);
+            
+            let (restored_physics, restored_agents) = decoded.restore();
+            assert_eq!(SimulationSnapshot::capture(&restored_physics, &restored_agents), snapshot);
+            assert_eq!(restored_agents.collision_restitution, Some(0.4));
+            assert_eq!(restored_agents.collision_grid_threshold, 8);
+        }
+        
+        assert_eq!("msgpack".parse::<SnapshotFormat>(), Ok(SnapshotFormat::MessagePack));
+        assert!("yaml".parse::<SnapshotFormat>().is_err());
+    }
+    
+    #[test]
+    fn test_snapshots_of_same_state_are_byte_identical() {
+        let build = || {
+            let physics = CityPhysics::new(300.0, 300.0);
+            let mut agents = AgentEngine::with_seed(8);
+            for i in 0..20 {
+                let personality = HashMap::from([
+                    ("risk_tolerance".to_string(), 0.05 * i as f64),
+                    ("social_preference".to_string(), 0.5),
+                    ("patience".to_string(), 0.3),
+                ]);
+                agents.add_citizen(10.0 * i as f64, 40.0, personality);
+            }
+            agents.add_business(60.0, 45.0, "shop".to_string());
+            agents.add_government(200.0, 200.0, HashMap::from([
+                ("tax_rate".to_string(), 0.2),
+                ("welfare_threshold".to_string(), 40.0),
+            ]));
+            agents.depleted_cycles.extend((1..10).map(|id| (id, id * 2)));
+            (physics, agents)
+        };
+        
+        // Separately built maps iterate in different orders
+        let (first_physics, first_agents) = build();
+        let (second_physics, second_agents) = build();
+        let first = SimulationSnapshot::capture(&first_physics, &first_agents);
+        let second = SimulationSnapshot::capture(&second_physics, &second_agents);
+        
+        for format in [SnapshotFormat::Json, SnapshotFormat::Bincode, SnapshotFormat::MessagePack] {
+            let bytes = first.to_snapshot(format).unwrap();
+            assert_eq!(bytes, second.to_snapshot(format).unwrap(), "{:?} output is not reproducible", format);
+            
+            let (physics, agents) = SimulationSnapshot::from_snapshot(&bytes, format).unwrap().restore();
+            assert_eq!(SimulationSnapshot::capture(&physics, &agents).to_snapshot(format).unwrap(), bytes);
+        }
+    }
+    
+    #[test]
+    fn test_restored_seeded_engine_continues_deterministically() {
+        let mut physics = CityPhysics::new(300.0, 300.0);
+        let mut agents = AgentEngine::with_seed(17);
+        for i in 0..8 {
+            let personality = HashMap::from([("risk_tolerance".to_string(), 0.1 * i as f64)]);
+            agents.add_citizen(30.0 * i as f64, 60.0, personality);
+        }
+        agents.add_business(120.0, 80.0, "shop".to_string());
+        assert!(agents.set_agent_seed(3, 99));
+        for _ in 0..3 {
+            physics.update_physics(&mut agents, 0.1);
+            agents.process_cycle(0.1);
+        }
+        
+        let bytes = SimulationSnapshot::capture(&physics, &agents).to_snapshot(SnapshotFormat::Bincode).unwrap();
+        let (mut restored_physics, mut restored_agents) =
+            SimulationSnapshot::from_snapshot(&bytes, SnapshotFormat::Bincode).unwrap().restore();
+        assert_eq!(restored_agents.behavior_seed, agents.behavior_seed);
+        assert_eq!(restored_agents.agent_seed(3), Some(99));
+        
+        for _ in 0..10 {
+            physics.update_physics(&mut agents, 0.1);
+            agents.process_cycle(0.1);
+            restored_physics.update_physics(&mut restored_agents, 0.1);
+            restored_agents.process_cycle(0.1);
+        }
+        assert_eq!(
+            SimulationSnapshot::capture(&restored_physics, &restored_agents),
+            SimulationSnapshot::capture(&physics, &agents)
+        );
+    }
+}
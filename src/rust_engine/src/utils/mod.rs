@@ -8,7 +8,8 @@
 
 use std::collections::HashMap;
 use nalgebra::Vector2;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 /// Mathematical utilities
 pub mod math {
@@ -55,6 +56,67 @@ pub mod math {
     pub fn rad_to_deg(radians: f64) -> f64 {
         radians * 180.0 / std::f64::consts::PI
     }
+    
+    /// Arithmetic mean, or None for an empty slice
+    pub fn mean(values: &[f64]) -> Option<f64> {
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f64>() / values.len() as f64)
+        }
+    }
+    
+    /// Population variance, or None for an empty slice
+    pub fn variance(values: &[f64]) -> Option<f64> {
+        let mean = mean(values)?;
+        Some(values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / values.len() as f64)
+    }
+    
+    /// Population standard deviation, or None for an empty slice
+    pub fn std_dev(values: &[f64]) -> Option<f64> {
+        variance(values).map(f64::sqrt)
+    }
+    
+    /// Median, averaging the two middle values for even lengths
+    pub fn median(values: &[f64]) -> Option<f64> {
+        percentile(values, 50.0)
+    }
+    
+    /// Percentile `p` in [0, 100], linearly interpolating between ranks
+    pub fn percentile(values: &[f64], p: f64) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+        
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        Some(lerp(sorted[lower], sorted[upper], rank - lower as f64))
+    }
+    
+    /// Count values into `bins` equal-width bins over [min, max]
+    ///
+    /// Values outside the range are clamped into the first or last bin.
+    pub fn histogram(values: impl IntoIterator<Item = f64>, bins: usize, min: f64, max: f64) -> Vec<usize> {
+        let mut counts = vec![0; bins];
+        if bins == 0 {
+            return counts;
+        }
+        
+        let width = (max - min) / bins as f64;
+        for value in values {
+            let index = if width > 0.0 {
+                ((value - min) / width).floor().clamp(0.0, (bins - 1) as f64) as usize
+            } else {
+                0
+            };
+            counts[index] += 1;
+        }
+        counts
+    }
 }
 
 /// Random number generation utilities
@@ -115,13 +177,140 @@ pub mod random {
         use rand::seq::SliceRandom;
         vec.shuffle(&mut rand::thread_rng());
     }
+    
+    /// Substream used by physics (spawn positions, environmental noise)
+    pub const PHYSICS_STREAM: &str = "physics";
+    /// Substream used by agent behavior
+    pub const BEHAVIOR_STREAM: &str = "behavior";
+    /// Substream used by learning and optimization
+    pub const LEARNING_STREAM: &str = "learning";
+    /// Substream used by genetic mutation, split from the optimization stream
+    pub const MUTATION_STREAM: &str = "mutation";
+    
+    /// Derive the seed of a named substream from a master seed
+    ///
+    /// Each subsystem draws from its own stream, so extra draws in one
+    /// subsystem never shift the values seen by another.
+    pub fn derive_seed(master_seed: u64, stream: &str) -> u64 {
+        // FNV-1a over the stream name keeps derivation stable across platforms
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in stream.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        
+        // SplitMix64 finalizer to decorrelate nearby master seeds
+        split_mix(master_seed ^ hash)
+    }
+    
+    /// Seed of an agent's random stream, derived from the engine's behavior seed
+    pub fn agent_stream_seed(behavior_seed: u64, agent_id: u32) -> u64 {
+        split_mix(behavior_seed ^ split_mix(agent_id as u64))
+    }
+    
+    /// Create the RNG an agent uses during one tick of its stream
+    ///
+    /// Depends only on the agent's stream seed and the tick, so agents can be
+    /// processed on any thread in any order with identical results.
+    pub fn agent_rng(stream_seed: u64, tick: u64) -> StdRng {
+        StdRng::seed_from_u64(split_mix(stream_seed ^ split_mix(tick)))
+    }
+    
+    fn split_mix(mut z: u64) -> u64 {
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    
+    /// Create the RNG for a named substream of a master seed
+    pub fn stream_rng(master_seed: u64, stream: &str) -> StdRng {
+        StdRng::seed_from_u64(derive_seed(master_seed, stream))
+    }
+}
+
+/// Serialization helpers for reproducible output
+pub mod serialization {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+    use std::hash::Hash;
+    
+    /// Serialize a map with its keys in ascending order
+    pub fn sorted_map<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        K: Ord + Serialize,
+        V: Serialize,
+    {
+        map.iter().collect::<BTreeMap<_, _>>().serialize(serializer)
+    }
+    
+    /// Items stored in maps keyed by their own id
+    pub trait Identified {
+        fn id(&self) -> u32;
+    }
+    
+    /// Serialize an id-keyed map as a list sorted by id, for `#[serde(with = ...)]`
+    pub mod id_ordered {
+        use super::*;
+        
+        pub fn serialize<S, T>(map: &HashMap<u32, T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            T: Serialize,
+        {
+            let mut items: Vec<(&u32, &T)> = map.iter().collect();
+            items.sort_unstable_by_key(|(id, _)| **id);
+            serializer.collect_seq(items.into_iter().map(|(_, item)| item))
+        }
+        
+        pub fn deserialize<'de, D, T>(deserializer: D) -> Result<HashMap<u32, T>, D::Error>
+        where
+            D: Deserializer<'de>,
+            T: Deserialize<'de> + Identified,
+        {
+            let items = Vec::<T>::deserialize(deserializer)?;
+            Ok(items.into_iter().map(|item| (item.id(), item)).collect())
+        }
+    }
+    
+    /// Serialize a map as a list of key-value pairs sorted by key, for `#[serde(with = ...)]`
+    pub mod sorted_pairs {
+        use super::*;
+        
+        pub fn serialize<S, K, V>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+            K: Ord + Serialize,
+            V: Serialize,
+        {
+            let mut pairs: Vec<(&K, &V)> = map.iter().collect();
+            pairs.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            pairs.serialize(serializer)
+        }
+        
+        pub fn deserialize<'de, D, K, V>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+        where
+            D: Deserializer<'de>,
+            K: Deserialize<'de> + Eq + Hash,
+            V: Deserialize<'de>,
+        {
+            Ok(Vec::<(K, V)>::deserialize(deserializer)?.into_iter().collect())
+        }
+    }
 }
 
 /// Data structure utilities
 pub mod data_structures {
     use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BinaryHeap;
     
     /// Circular buffer for storing recent values
+    ///
+    /// Serializes as its capacity and items oldest first, so equal contents give equal
+    /// output whatever the internal rotation.
+    #[derive(Debug, Clone)]
     pub struct CircularBuffer<T> {
         buffer: Vec<T>,
         head: usize,
@@ -140,6 +329,9 @@ pub mod data_structures {
         }
         
         pub fn push(&mut self, item: T) {
+            if self.capacity == 0 {
+                return;
+            }
             if self.size < self.capacity {
                 self.buffer.push(item);
                 self.size += 1;
@@ -166,6 +358,36 @@ pub mod data_structures {
             self.size == 0
         }
         
+        pub fn capacity(&self) -> usize {
+            self.capacity
+        }
+        
+        /// Remove all items, leaving the buffer as if freshly created
+        pub fn clear(&mut self) {
+            self.buffer.clear();
+            self.head = 0;
+            self.size = 0;
+        }
+        
+        /// Most recently pushed item
+        pub fn latest(&self) -> Option<&T> {
+            self.size.checked_sub(1).and_then(|index| self.get(index))
+        }
+        
+        /// Up to `n` most recent items, oldest first
+        pub fn recent(&self, n: usize) -> Vec<T> {
+            self.iter().skip(self.size.saturating_sub(n)).cloned().collect()
+        }
+        
+        /// Change the capacity, keeping the most recent items that still fit
+        pub fn resize(&mut self, capacity: usize) {
+            let items = self.recent(capacity);
+            *self = Self::new(capacity);
+            for item in items {
+                self.push(item);
+            }
+        }
+        
         pub fn iter(&self) -> CircularBufferIterator<T> {
             CircularBufferIterator {
                 buffer: self,
@@ -174,12 +396,45 @@ pub mod data_structures {
         }
     }
     
+    impl<T: Clone + PartialEq> PartialEq for CircularBuffer<T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.capacity == other.capacity && self.iter().eq(other.iter())
+        }
+    }
+    
+    #[derive(Serialize, Deserialize)]
+    struct CircularBufferRepr<T> {
+        capacity: usize,
+        items: Vec<T>,
+    }
+    
+    impl<T: Clone + Serialize> Serialize for CircularBuffer<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            CircularBufferRepr {
+                capacity: self.capacity,
+                items: self.iter().cloned().collect(),
+            }
+            .serialize(serializer)
+        }
+    }
+    
+    impl<'de, T: Clone + Deserialize<'de>> Deserialize<'de> for CircularBuffer<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = CircularBufferRepr::<T>::deserialize(deserializer)?;
+            let mut buffer = Self::new(repr.capacity);
+            for item in repr.items {
+                buffer.push(item);
+            }
+            Ok(buffer)
+        }
+    }
+    
     pub struct CircularBufferIterator<'a, T> {
         buffer: &'a CircularBuffer<T>,
         index: usize,
     }
     
-    impl<'a, T> Iterator for CircularBufferIterator<'a, T> {
+    impl<'a, T: Clone> Iterator for CircularBufferIterator<'a, T> {
         type Item = &'a T;
         
         fn next(&mut self) -> Option<Self::Item> {
@@ -193,29 +448,57 @@ pub mod data_structures {
         }
     }
     
-    /// Priority queue for efficient priority-based operations
+    /// Heap entry ordered by priority only
+    struct PriorityEntry<T> {
+        priority: f64,
+        item: T,
+    }
+    
+    impl<T> PartialEq for PriorityEntry<T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.priority.total_cmp(&other.priority).is_eq()
+        }
+    }
+    
+    impl<T> Eq for PriorityEntry<T> {}
+    
+    impl<T> PartialOrd for PriorityEntry<T> {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    
+    impl<T> Ord for PriorityEntry<T> {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.priority.total_cmp(&other.priority)
+        }
+    }
+    
+    /// Max-priority queue backed by a binary heap
     pub struct PriorityQueue<T> {
-        items: Vec<(f64, T)>, // (priority, item)
+        items: BinaryHeap<PriorityEntry<T>>,
     }
     
     impl<T: Clone> PriorityQueue<T> {
         pub fn new() -> Self {
             Self {
-                items: Vec::new(),
+                items: BinaryHeap::new(),
             }
         }
         
+        /// Insert an item in O(log n)
         pub fn push(&mut self, item: T, priority: f64) {
-            self.items.push((priority, item));
-            self.items.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+            self.items.push(PriorityEntry { priority, item });
         }
         
+        /// Remove the highest-priority item in O(log n)
         pub fn pop(&mut self) -> Option<T> {
-            self.items.pop().map(|(_, item)| item)
+            self.items.pop().map(|entry| entry.item)
         }
         
+        /// Highest-priority item without removing it
         pub fn peek(&self) -> Option<&T> {
-            self.items.last().map(|(_, item)| item)
+            self.items.peek().map(|entry| &entry.item)
         }
         
         pub fn len(&self) -> usize {
@@ -226,10 +509,160 @@ pub mod data_structures {
             self.items.is_empty()
         }
     }
+    
+    /// Candidate neighbor ordered by squared distance, then id
+    #[derive(Clone, Copy, PartialEq)]
+    struct Neighbor {
+        distance_squared: f64,
+        id: u32,
+    }
+    
+    impl Eq for Neighbor {}
+    
+    impl PartialOrd for Neighbor {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    
+    impl Ord for Neighbor {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.distance_squared.total_cmp(&other.distance_squared).then(self.id.cmp(&other.id))
+        }
+    }
+    
+    /// Static 2D k-d tree for nearest-neighbor queries over identified points
+    ///
+    /// The tree is stored implicitly: each range of `points` holds its splitting point
+    /// at the middle, with the lower half on its left. Ties in distance are broken by
+    /// lower id, so queries are deterministic even with duplicate coordinates.
+    #[derive(Debug, Clone)]
+    pub struct KdTree2D {
+        points: Vec<(u32, Vector2<f64>)>,
+    }
+    
+    impl KdTree2D {
+        /// Build a tree over `points` in O(n log n)
+        pub fn build(points: &[(u32, Vector2<f64>)]) -> Self {
+            let mut points = points.to_vec();
+            Self::partition(&mut points, 0);
+            Self { points }
+        }
+        
+        fn partition(points: &mut [(u32, Vector2<f64>)], depth: usize) {
+            if points.len() <= 1 {
+                return;
+            }
+            let axis = depth % 2;
+            let middle = points.len() / 2;
+            points.select_nth_unstable_by(middle, |a, b| a.1[axis].total_cmp(&b.1[axis]));
+            
+            let (left, right) = points.split_at_mut(middle);
+            Self::partition(left, depth + 1);
+            Self::partition(&mut right[1..], depth + 1);
+        }
+        
+        pub fn len(&self) -> usize {
+            self.points.len()
+        }
+        
+        pub fn is_empty(&self) -> bool {
+            self.points.is_empty()
+        }
+        
+        /// Id of the point closest to `query`, or None for an empty tree
+        pub fn nearest(&self, query: Vector2<f64>) -> Option<u32> {
+            self.k_nearest(query, 1).first().copied()
+        }
+        
+        /// Ids of the `k` points closest to `query`, nearest first
+        ///
+        /// Returns every point when the tree holds fewer than `k`.
+        pub fn k_nearest(&self, query: Vector2<f64>, k: usize) -> Vec<u32> {
+            if k == 0 {
+                return Vec::new();
+            }
+            
+            let mut best = BinaryHeap::with_capacity(k + 1);
+            Self::search(&self.points, 0, query, k, &mut best);
+            best.into_sorted_vec().into_iter().map(|neighbor| neighbor.id).collect()
+        }
+        
+        fn search(
+            points: &[(u32, Vector2<f64>)],
+            depth: usize,
+            query: Vector2<f64>,
+            k: usize,
+            best: &mut BinaryHeap<Neighbor>,
+        ) {
+            if points.is_empty() {
+                return;
+            }
+            let middle = points.len() / 2;
+            let (id, position) = points[middle];
+            
+            // `best` is a max-heap, so its top is the worst of the k kept so far
+            best.push(Neighbor { distance_squared: (position - query).norm_squared(), id });
+            if best.len() > k {
+                best.pop();
+            }
+            
+            let axis = depth % 2;
+            let offset = query[axis] - position[axis];
+            let (near, far) = if offset < 0.0 {
+                (&points[..middle], &points[middle + 1..])
+            } else {
+                (&points[middle + 1..], &points[..middle])
+            };
+            
+            Self::search(near, depth + 1, query, k, best);
+            // Points across the split are at least `offset` away; equal distances may still win on id
+            let worst = best.peek().map_or(f64::INFINITY, |neighbor| neighbor.distance_squared);
+            if best.len() < k || offset * offset <= worst {
+                Self::search(far, depth + 1, query, k, best);
+            }
+        }
+        
+        /// Points at most `radius` from `query`, in ascending id order
+        pub fn within(&self, query: Vector2<f64>, radius: f64) -> Vec<(u32, Vector2<f64>)> {
+            let mut found = Vec::new();
+            Self::collect_within(&self.points, 0, query, radius, &mut found);
+            found.sort_unstable_by_key(|(id, _)| *id);
+            found
+        }
+        
+        fn collect_within(
+            points: &[(u32, Vector2<f64>)],
+            depth: usize,
+            query: Vector2<f64>,
+            radius: f64,
+            found: &mut Vec<(u32, Vector2<f64>)>,
+        ) {
+            if points.is_empty() {
+                return;
+            }
+            let middle = points.len() / 2;
+            let (id, position) = points[middle];
+            if (position - query).magnitude() <= radius {
+                found.push((id, position));
+            }
+            
+            let axis = depth % 2;
+            let offset = query[axis] - position[axis];
+            if offset - radius <= 0.0 {
+                Self::collect_within(&points[..middle], depth + 1, query, radius, found);
+            }
+            if offset + radius >= 0.0 {
+                Self::collect_within(&points[middle + 1..], depth + 1, query, radius, found);
+            }
+        }
+    }
 }
 
 /// Performance monitoring utilities
 pub mod performance {
+    use pyo3::prelude::*;
+    use serde::{Deserialize, Serialize};
     use std::time::Instant;
     
     /// Simple timer for measuring execution time
@@ -258,6 +691,7 @@ pub mod performance {
     }
     
     /// Performance counter for tracking metrics
+    #[derive(Clone)]
     pub struct PerformanceCounter {
         count: u64,
         total_time: std::time::Duration,
@@ -310,6 +744,257 @@ pub mod performance {
             self.max_time
         }
     }
+    
+    /// Time spent in each phase of a simulation update
+    #[derive(Clone)]
+    pub struct PhaseTimings {
+        pub physics: PerformanceCounter,
+        pub behavior: PerformanceCounter,
+        pub optimization: PerformanceCounter,
+    }
+    
+    impl Default for PhaseTimings {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    
+    impl PhaseTimings {
+        pub fn new() -> Self {
+            Self {
+                physics: PerformanceCounter::new(),
+                behavior: PerformanceCounter::new(),
+                optimization: PerformanceCounter::new(),
+            }
+        }
+    }
+    
+    /// Resource usage of the current process, read through the `sysinfo` crate
+    ///
+    /// Returns `None` on platforms `sysinfo` does not support.
+    #[cfg(feature = "sysinfo")]
+    pub mod process {
+        use std::time::Duration;
+        use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+        
+        /// Resident set size of the process in megabytes and total CPU time consumed
+        /// by all its threads
+        pub fn usage() -> Option<(f64, Duration)> {
+            let pid = sysinfo::get_current_pid().ok()?;
+            let mut system = System::new();
+            system.refresh_processes_specifics(
+                ProcessesToUpdate::Some(&[pid]),
+                false,
+                ProcessRefreshKind::nothing().with_memory().with_cpu(),
+            );
+            let process = system.process(pid)?;
+            Some((
+                process.memory() as f64 / (1024.0 * 1024.0),
+                Duration::from_millis(process.accumulated_cpu_time()),
+            ))
+        }
+    }
+    
+    /// One row of recorded simulation metrics
+    #[pyclass]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct MetricsRow {
+        pub tick: u64,
+        pub agent_count: f64,
+        pub avg_energy: f64,
+        pub interactions: f64,
+        pub update_time_ms: f64,
+    }
+    
+    /// Records simulation metrics, averaging every `interval` ticks into one row
+    #[derive(Clone)]
+    pub struct MetricsRecorder {
+        interval: usize,
+        window: Vec<MetricsRow>,
+        rows: Vec<MetricsRow>,
+    }
+    
+    impl Default for MetricsRecorder {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+    
+    impl MetricsRecorder {
+        /// Create a recorder that keeps one row per tick
+        pub fn new() -> Self {
+            Self::with_interval(1)
+        }
+        
+        /// Create a recorder that keeps one averaged row every `interval` ticks
+        pub fn with_interval(interval: usize) -> Self {
+            Self {
+                interval: interval.max(1),
+                window: Vec::new(),
+                rows: Vec::new(),
+            }
+        }
+        
+        /// Change the aggregation interval, discarding the partially filled window
+        pub fn set_interval(&mut self, interval: usize) {
+            self.interval = interval.max(1);
+            self.window.clear();
+        }
+        
+        pub fn interval(&self) -> usize {
+            self.interval
+        }
+        
+        /// Record the metrics of one tick
+        pub fn record(&mut self, row: MetricsRow) {
+            self.window.push(row);
+            if self.window.len() >= self.interval {
+                let aggregated = Self::aggregate(&self.window);
+                self.rows.push(aggregated);
+                self.window.clear();
+            }
+        }
+        
+        /// Average a window of rows; the tick is the first tick of the window
+        fn aggregate(window: &[MetricsRow]) -> MetricsRow {
+            let count = window.len() as f64;
+            let mean = |value: fn(&MetricsRow) -> f64| window.iter().map(value).sum::<f64>() / count;
+            
+            MetricsRow {
+                tick: window[0].tick,
+                agent_count: mean(|row| row.agent_count),
+                avg_energy: mean(|row| row.avg_energy),
+                interactions: mean(|row| row.interactions),
+                update_time_ms: mean(|row| row.update_time_ms),
+            }
+        }
+        
+        /// Aggregated rows recorded so far
+        pub fn rows(&self) -> &[MetricsRow] {
+            &self.rows
+        }
+        
+        /// Drop all recorded rows
+        pub fn clear(&mut self) {
+            self.window.clear();
+            self.rows.clear();
+        }
+    }
+}
+
+/// Span timing for performance investigations (`profiling` feature)
+///
+/// The hot paths of the engine are instrumented with `tracing` spans when the
+/// feature is enabled. A `Profile` collects how long each span was entered.
+///
+/// The AI engine (`ai-engine/src/profiling.rs`) carries an identical copy of this
+/// module; changes to one belong in the other as well.
+#[cfg(feature = "profiling")]
+pub mod profiling {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+    use tracing::span::Id;
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+    
+    /// Timing samples of one span
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub struct SpanStats {
+        pub samples: u64,
+        pub total: Duration,
+        pub max: Duration,
+    }
+    
+    impl SpanStats {
+        /// Mean time per sample
+        pub fn mean(&self) -> Duration {
+            if self.samples == 0 {
+                Duration::ZERO
+            } else {
+                self.total / self.samples as u32
+            }
+        }
+    }
+    
+    /// Shared table of span timings, filled by the layers it hands out
+    #[derive(Debug, Clone, Default)]
+    pub struct Profile {
+        spans: Arc<Mutex<HashMap<&'static str, SpanStats>>>,
+    }
+    
+    impl Profile {
+        pub fn new() -> Self {
+            Self::default()
+        }
+        
+        /// Layer recording into this profile, to be added to a `tracing` subscriber
+        pub fn layer(&self) -> ProfileLayer {
+            ProfileLayer { profile: self.clone() }
+        }
+        
+        /// Install this profile's layer as the global subscriber
+        ///
+        /// Returns false if a global subscriber was already set.
+        pub fn install(&self) -> bool {
+            let subscriber = tracing_subscriber::registry().with(self.layer());
+            tracing::subscriber::set_global_default(subscriber).is_ok()
+        }
+        
+        /// Timings of one span, if it was entered since the last reset
+        pub fn stats(&self, name: &str) -> Option<SpanStats> {
+            self.spans.lock().unwrap().get(name).copied()
+        }
+        
+        /// Timings of every span entered since the last reset
+        pub fn snapshot(&self) -> HashMap<String, SpanStats> {
+            self.spans.lock().unwrap()
+                .iter()
+                .map(|(name, stats)| (name.to_string(), *stats))
+                .collect()
+        }
+        
+        /// Forget all recorded timings
+        pub fn reset(&self) {
+            self.spans.lock().unwrap().clear();
+        }
+        
+        fn record(&self, name: &'static str, elapsed: Duration) {
+            let mut spans = self.spans.lock().unwrap();
+            let stats = spans.entry(name).or_default();
+            stats.samples += 1;
+            stats.total += elapsed;
+            stats.max = stats.max.max(elapsed);
+        }
+    }
+    
+    /// Time at which a span was last entered
+    struct Entered(Instant);
+    
+    /// `tracing` layer timing each entry into a span
+    pub struct ProfileLayer {
+        profile: Profile,
+    }
+    
+    impl<S> Layer<S> for ProfileLayer
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().replace(Entered(Instant::now()));
+            }
+        }
+        
+        fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(Entered(start)) = span.extensions_mut().remove::<Entered>() {
+                    self.profile.record(span.name(), start.elapsed());
+                }
+            }
+        }
+    }
 }
 
 /// String utilities
@@ -338,3 +1023,163 @@ pub mod string {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::performance::{MetricsRecorder, MetricsRow};
+    
+    #[test]
+    fn test_metrics_recorder_aggregates_windows() {
+        let mut recorder = MetricsRecorder::with_interval(10);
+        for tick in 0..100u64 {
+            recorder.record(MetricsRow {
+                tick,
+                agent_count: 50.0,
+                avg_energy: tick as f64,
+                interactions: (tick % 10) as f64 * 2.0,
+                update_time_ms: 1.0,
+            });
+        }
+        
+        let rows = recorder.rows();
+        assert_eq!(rows.len(), 10);
+        for (window, row) in rows.iter().enumerate() {
+            let start = window as u64 * 10;
+            assert_eq!(row.tick, start);
+            assert_eq!(row.agent_count, 50.0);
+            assert!((row.avg_energy - (start as f64 + 4.5)).abs() < 1e-9);
+            assert!((row.interactions - 9.0).abs() < 1e-9);
+            assert_eq!(row.update_time_ms, 1.0);
+        }
+    }
+    
+    #[test]
+    fn test_priority_queue_pops_highest_first() {
+        use super::data_structures::PriorityQueue;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut queue = PriorityQueue::new();
+        for i in 0..10_000 {
+            let priority: f64 = rng.gen_range(-1000.0..1000.0);
+            queue.push((i, priority), priority);
+        }
+        assert_eq!(queue.len(), 10_000);
+        let top = *queue.peek().unwrap();
+        assert_eq!(queue.pop(), Some(top));
+        
+        let mut last = top.1;
+        while let Some((_, priority)) = queue.pop() {
+            assert!(priority <= last);
+            last = priority;
+        }
+        assert!(queue.is_empty());
+    }
+    
+    #[test]
+    fn test_kd_tree_matches_brute_force() {
+        use super::data_structures::KdTree2D;
+        use nalgebra::Vector2;
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+        
+        let empty = KdTree2D::build(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.nearest(Vector2::new(1.0, 1.0)), None);
+        assert!(empty.k_nearest(Vector2::new(1.0, 1.0), 3).is_empty());
+        
+        let mut rng = StdRng::seed_from_u64(21);
+        for size in [1, 2, 7, 64, 500] {
+            // Integer coordinates on a small grid force duplicates and distance ties
+            let points: Vec<(u32, Vector2<f64>)> = (0..size)
+                .map(|id| (id as u32 * 3, Vector2::new(rng.gen_range(0..20) as f64, rng.gen_range(0..20) as f64)))
+                .collect();
+            let tree = KdTree2D::build(&points);
+            assert_eq!(tree.len(), size);
+            
+            for _ in 0..50 {
+                let query = Vector2::new(rng.gen_range(-5.0..25.0), rng.gen_range(-5.0..25.0));
+                let mut expected = points.clone();
+                expected.sort_by(|a, b| {
+                    (a.1 - query).norm_squared().total_cmp(&(b.1 - query).norm_squared()).then(a.0.cmp(&b.0))
+                });
+                let expected: Vec<u32> = expected.into_iter().map(|(id, _)| id).collect();
+                
+                assert_eq!(tree.nearest(query), expected.first().copied());
+                for k in [0, 1, 5, size + 3] {
+                    assert_eq!(tree.k_nearest(query, k), expected[..k.min(size)].to_vec());
+                }
+                
+                let mut in_radius: Vec<(u32, Vector2<f64>)> = points.iter()
+                    .filter(|(_, position)| (position - query).magnitude() <= 4.0)
+                    .copied()
+                    .collect();
+                in_radius.sort_by_key(|(id, _)| *id);
+                assert_eq!(tree.within(query, 4.0), in_radius);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_circular_buffer_latest_and_clear() {
+        use super::data_structures::CircularBuffer;
+        
+        let mut buffer = CircularBuffer::new(3);
+        assert_eq!(buffer.capacity(), 3);
+        assert_eq!(buffer.latest(), None);
+        
+        for value in 1..=3 {
+            buffer.push(value);
+            assert_eq!(buffer.latest(), Some(&value));
+        }
+        
+        // Wrap around past capacity
+        buffer.push(4);
+        buffer.push(5);
+        assert_eq!(buffer.latest(), Some(&5));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5]);
+        
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.latest(), None);
+        assert_eq!(buffer.capacity(), 3);
+        
+        // After clear the buffer behaves like a fresh one, including wrap-around
+        for value in 10..=14 {
+            buffer.push(value);
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.get(0), Some(&12));
+        assert_eq!(buffer.latest(), Some(&14));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), vec![12, 13, 14]);
+    }
+    
+    #[test]
+    fn test_statistical_helpers() {
+        use super::math::{mean, median, percentile, std_dev, variance};
+        
+        let values = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(mean(&values), Some(5.0));
+        assert_eq!(variance(&values), Some(4.0));
+        assert_eq!(std_dev(&values), Some(2.0));
+        
+        // Even length: average of the two middle values
+        assert_eq!(median(&values), Some(4.5));
+        // Odd length, unsorted input
+        assert_eq!(median(&[9.0, 1.0, 3.0]), Some(3.0));
+        
+        assert_eq!(percentile(&values, 0.0), Some(2.0));
+        assert_eq!(percentile(&values, 100.0), Some(9.0));
+        // Rank 0.25 * 7 = 1.75 lies between 4.0 and 4.0
+        assert_eq!(percentile(&values, 25.0), Some(4.0));
+        // Rank 0.9 * 7 = 6.3 lies between 7.0 and 9.0
+        assert!((percentile(&values, 90.0).unwrap() - 7.6).abs() < 1e-12);
+        
+        assert_eq!(mean(&[]), None);
+        assert_eq!(variance(&[]), None);
+        assert_eq!(std_dev(&[]), None);
+        assert_eq!(median(&[]), None);
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+}